@@ -3,24 +3,61 @@
 
 use std::{
     borrow::BorrowMut,
+    collections::HashMap,
     env,
     error::Error,
     fs::{self, File},
     io,
-    sync::{Arc, Mutex},
+    path::PathBuf,
+    str::FromStr as _,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
 };
 
-use device_query::{DeviceEvents as _, DeviceState};
+use device_query::{DeviceEvents as _, DeviceQuery as _, DeviceState, Keycode};
 use live_ocrs::{
-    capture::CaptureState, dict, toggle, update_hover, Definitions, LiveOcr, OcrState,
+    anki,
+    capture::{self, CaptureOptions, CapturePreview, CaptureState, CaptureTarget, WindowInfo},
+    collocation,
+    config::{self, Config, PanelSide, ScreenEdge, WindowEffect},
+    correction,
+    dict::{self, DictionaryEntry, DictionaryFormat, DictionarySource, LoadProgress},
+    engine_cache::{self, EngineCacheEntry},
+    environment::SessionStatus,
+    grammar,
+    history::{self, HistoryEntry},
+    hover::{expire_hover, update_hover, HoverEvent, HoverState, HoveredWord},
+    known,
+    lens::{Lens, LensConfig, LensView},
+    live::{self, Refresh},
+    memory::MemoryUsage,
+    metrics, monitor_bounds,
+    payload::{
+        build_entry_details, build_tooltip, route_tooltip, BlockInfo, EntryDetails, OverlayBlocks,
+        TooltipEntry, TooltipSection, TOOLTIP_PAGE_SIZE,
+    },
+    reading::{self, ReadingSession},
+    recognize_image,
+    ruby::{build_ruby, RubyOverlay},
+    server,
+    session::{self, PinnedTooltip, SavedSession, Snapshot},
+    speech::{self, Speaker},
+    stats::{self, AppReadingTime},
+    toggle,
+    user_dict::{self, UserEntry, UserEntryDraft},
+    websocket::EventStream,
+    Definitions, LiveOcr, OcrState, IMAGE_APP,
 };
 use parking_lot::RwLock;
 use rapidocr::{ExecutionProvider, RapidOCRBuilder};
 use serde::{Deserialize, Serialize};
 use tauri::{
     async_runtime::{channel, spawn, spawn_blocking},
-    AppHandle, GlobalShortcutManager, LogicalSize, Manager, PhysicalPosition, State, Window,
-    WindowBuilder, WindowUrl,
+    AppHandle, GlobalShortcutManager, LogicalSize, Manager, PhysicalPosition, PhysicalSize,
+    RunEvent, State, Window, WindowBuilder, WindowEvent, WindowUrl,
 };
 use tracing_subscriber::{
     fmt::{self, format::FmtSpan},
@@ -28,6 +65,8 @@ use tracing_subscriber::{
     EnvFilter,
 };
 
+mod style;
+
 fn main() {
     #[cfg(windows)]
     {
@@ -36,7 +75,70 @@ fn main() {
     }
 
     tauri::Builder::default()
-        .invoke_handler(tauri::generate_handler![content_size_changed])
+        .manage(Environment(
+            parking_lot::Mutex::new(SessionStatus::detect()),
+        ))
+        .manage(Pins::default())
+        .manage(OpenEntry::default())
+        .manage(PendingSession::default())
+        .manage(LoadingProgress::default())
+        .manage(PendingCorrection::default())
+        .manage(PendingUserEntry::default())
+        .manage(PendingPreview::default())
+        .manage(CurrentLens::default())
+        .manage(PendingImage::default())
+        .manage(Speaker::new())
+        .invoke_handler(tauri::generate_handler![
+            loading_progress,
+            content_size_changed,
+            pinned_definitions,
+            close_pin,
+            more_definitions,
+            reading_time,
+            lookup_history,
+            export_history,
+            engine_cache_entries,
+            memory_usage,
+            reading_sessions,
+            export_reading_session,
+            session_status,
+            clear_engine_cache,
+            toggle_ocr,
+            tooltip_effect,
+            entry_details,
+            pending_session,
+            restore_session,
+            discard_session,
+            save_ocr_session,
+            saved_sessions,
+            open_session,
+            correction_text,
+            correct_text,
+            pending_user_entry,
+            user_entries,
+            save_user_entry,
+            delete_user_entry,
+            ocr_blocks,
+            capture_preview,
+            lens_view,
+            ruby_overlay,
+            overlay_blocks,
+            clipboard_image,
+            close_image,
+            capture_windows,
+            adjust_capture,
+            confirm_capture,
+            cancel_capture
+        ])
+        .on_window_event(|event| {
+            if let WindowEvent::Destroyed = event.event() {
+                let window = event.window();
+                let removed = window.state::<Pins>().windows.lock().remove(window.label());
+                if removed.is_some() {
+                    save_session(&window.app_handle());
+                }
+            }
+        })
         .setup(|app| {
             let log_dir = app.path_resolver().app_log_dir().unwrap();
             log::info!("Log Dir: {log_dir:?}");
@@ -56,45 +158,179 @@ fn main() {
 
             tracing::subscriber::set_global_default(subscriber).unwrap();
 
+            let can_track_input = app.state::<Environment>().0.lock().can_track_input();
+            let safe_mode = is_safe_mode(can_track_input);
+            if safe_mode {
+                log::warn!("Starting in safe mode");
+            }
+
             let app = app.handle();
             spawn_blocking(move || {
-                let state = init_state(app.clone());
+                let state = init_state(app.clone(), safe_mode);
                 if let Err(err) = &state {
                     log::error!("{err}");
                     app.exit(-1);
                 }
                 let state = state.unwrap();
                 app.manage(state.clone());
+                let (websocket_port, websocket_origins) = {
+                    let state = state.read();
+                    (
+                        state.config.websocket_port,
+                        state.config.websocket_origins.clone(),
+                    )
+                };
+                if let Some(port) = websocket_port {
+                    match EventStream::listen(("127.0.0.1", port), websocket_origins) {
+                        Ok(stream) => {
+                            app.manage(stream);
+                        }
+                        Err(err) => log::error!("Failed to start the event stream: {err}"),
+                    }
+                }
+                let server_port = state.read().config.server_port;
+                if let Some(port) = server_port {
+                    let state = state.clone();
+                    std::thread::spawn(move || {
+                        if let Err(err) = server::serve(("127.0.0.1", port), state) {
+                            log::error!("Failed to start the HTTP API: {err}");
+                        }
+                    });
+                }
+                *app.state::<PendingSession>().0.lock() = session::load(session_path(&app));
 
                 if let Some(splash) = app.get_window("splashscreen") {
                     splash.close().unwrap();
                 }
                 if let Some(main) = app.get_window("main") {
+                    if safe_mode {
+                        main.set_title("LiveOCR (Safe Mode)").unwrap();
+                    }
                     main.show().unwrap();
+                    // Loading can report issues after the window first asked for them
+                    main.emit("status-changed", ()).unwrap();
                 }
 
-                let mut global_shortcuts = app.global_shortcut_manager();
                 {
                     let handle = app.clone();
                     let state = state.clone();
-                    global_shortcuts
-                        .register("alt+x", move || {
-                            handle_toggle(handle.clone(), state.clone());
-                        })
-                        .unwrap();
+                    register_shortcut(&app, "alt+x", move || {
+                        handle_toggle(handle.clone(), state.clone(), CaptureOptions::default());
+                    });
+                }
+                {
+                    let handle = app.clone();
+                    let state = state.clone();
+                    register_shortcut(&app, "alt+p", move || {
+                        handle_pin(handle.clone(), state.clone());
+                    });
                 }
-
                 {
+                    let handle = app.clone();
+                    let state = state.clone();
+                    register_shortcut(&app, "alt+e", move || {
+                        handle_open_entry(handle.clone(), state.clone());
+                    });
+                }
+                {
+                    let handle = app.clone();
+                    let state = state.clone();
+                    register_shortcut(&app, "alt+r", move || {
+                        handle_recapture(handle.clone(), state.clone());
+                    });
+                }
+                {
+                    let handle = app.clone();
+                    let state = state.clone();
+                    register_shortcut(&app, "alt+c", move || {
+                        handle_correct(handle.clone(), state.clone());
+                    });
+                }
+                {
+                    let handle = app.clone();
+                    let state = state.clone();
+                    register_shortcut(&app, "alt+d", move || {
+                        handle_user_entry(handle.clone(), state.clone());
+                    });
+                }
+                {
+                    let handle = app.clone();
+                    let state = state.clone();
+                    register_shortcut(&app, "alt+k", move || {
+                        handle_toggle_known(handle.clone(), state.clone());
+                    });
+                }
+                {
+                    let handle = app.clone();
+                    let state = state.clone();
+                    register_shortcut(&app, "alt+s", move || {
+                        handle_speak(handle.clone(), state.clone());
+                    });
+                }
+                {
+                    let handle = app.clone();
+                    let state = state.clone();
+                    register_shortcut(&app, "alt+a", move || {
+                        handle_anki_export(handle.clone(), state.clone());
+                    });
+                }
+                {
+                    let handle = app.clone();
+                    let state = state.clone();
+                    register_shortcut(&app, "alt+v", move || {
+                        handle_clipboard_ocr(handle.clone(), state.clone());
+                    });
+                }
+                {
+                    let handle = app.clone();
+                    let state = state.clone();
+                    register_shortcut(&app, "alt+y", move || {
+                        handle_ruby(handle.clone(), state.clone());
+                    });
+                }
+                {
+                    let handle = app.clone();
+                    register_shortcut(&app, "alt+shift+p", move || {
+                        close_pins(&handle);
+                    });
+                }
+
+                let can_track_input = app.state::<Environment>().0.lock().can_track_input();
+                if can_track_input {
                     let app = app.clone();
                     let state = state.clone();
                     spawn(track_cursor(state, app));
                 }
+                {
+                    let app = app.clone();
+                    let state = state.clone();
+                    spawn_blocking(move || expire_tooltip(state, app));
+                }
+                let lens = state.read().config.lens.clone();
+                if let Some(lens) = lens.filter(|_| can_track_input) {
+                    let app = app.clone();
+                    let state = state.clone();
+                    spawn_blocking(move || watch_lens(state, app, lens));
+                }
+                let live_interval = state.read().config.live_interval_ms;
+                if let Some(interval) = live_interval {
+                    let app = app.clone();
+                    let state = state.clone();
+                    spawn_blocking(move || {
+                        refresh_live(state, app, Duration::from_millis(interval))
+                    });
+                }
             });
 
             Ok(())
         })
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(|app, event| {
+            if let RunEvent::Exit = event {
+                session::clear(session_path(app));
+            }
+        });
 }
 
 #[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
@@ -103,13 +339,59 @@ struct Rect {
     height: f32,
 }
 
+#[derive(Default)]
+struct Pins {
+    next_id: AtomicUsize,
+    windows: parking_lot::Mutex<HashMap<String, PinnedTooltip>>,
+}
+
+/// The word whose block is being corrected in the correction window
+#[derive(Default)]
+struct PendingCorrection(parking_lot::Mutex<Option<HoveredWord>>);
+
+/// Entry being edited in the user dictionary window
+#[derive(Default)]
+struct PendingUserEntry(parking_lot::Mutex<Option<UserEntryDraft>>);
+
+/// Capture waiting for confirmation in the preview window
+#[derive(Default)]
+struct PendingPreview(parking_lot::Mutex<Option<(CapturePreview, CaptureOptions)>>);
+
+/// Latest dictionary loading progress, for the splash screen to pick up when it loads
+#[derive(Default)]
+struct LoadingProgress(parking_lot::Mutex<Option<LoadProgress>>);
+
+/// Snapshot left behind by a crashed session, until it's restored or discarded
+#[derive(Default)]
+struct PendingSession(parking_lot::Mutex<Option<Snapshot>>);
+
+/// Latest magnifier view, for the lens window to pick up when it loads
+#[derive(Default)]
+struct CurrentLens(parking_lot::Mutex<Option<LensView>>);
+
+/// Clipboard image shown in the image window, as a PNG data URL
+#[derive(Default)]
+struct PendingImage(parking_lot::Mutex<Option<String>>);
+
+/// What the session supports, detected at startup
+struct Environment(parking_lot::Mutex<SessionStatus>);
+
+/// The word shown in the entry window
+#[derive(Default)]
+struct OpenEntry(parking_lot::Mutex<Option<EntryDetails>>);
+
 #[tauri::command]
 async fn content_size_changed(
     window: Window,
     state: State<'_, OcrState>,
+    pins: State<'_, Pins>,
     width: f32,
     height: f32,
 ) -> tauri::Result<()> {
+    if let Some(pin) = pins.windows.lock().get(window.label()) {
+        return place_tooltip(&window, pin.rect, pin.monitor, width, height);
+    }
+
     let state = state.read();
     if window.label() != "tooltip" {
         return Ok(());
@@ -118,36 +400,741 @@ async fn content_size_changed(
         window.hide()?;
         return Ok(());
     }
+    if let Some((word, monitor)) = state.hover.word().zip(state.monitor.as_ref()) {
+        place_tooltip(&window, word.rect, monitor_bounds(monitor), width, height)?;
+    } else {
+        window.hide()?;
+    }
+
+    Ok(())
+}
+
+#[tauri::command]
+fn close_pin(window: Window) -> tauri::Result<()> {
+    window.close()
+}
+
+#[tauri::command]
+fn pinned_definitions(window: Window, pins: State<'_, Pins>) -> Vec<TooltipEntry> {
+    pins.windows
+        .lock()
+        .get(window.label())
+        .map(|pin| pin.entries.clone())
+        .unwrap_or_default()
+}
+
+/// The next page of definitions for the hovered word, empty once all were sent.
+#[tauri::command]
+fn more_definitions(state: State<'_, OcrState>, offset: usize) -> Vec<TooltipEntry> {
+    let state = state.read();
+    build_tooltip(
+        state.definitions.page(offset, TOOLTIP_PAGE_SIZE),
+        &state.config,
+    )
+}
+
+#[tauri::command]
+fn loading_progress(progress: State<'_, LoadingProgress>) -> Option<LoadProgress> {
+    progress.0.lock().clone()
+}
+
+#[tauri::command]
+fn entry_details(entry: State<'_, OpenEntry>) -> Option<EntryDetails> {
+    entry.0.lock().clone()
+}
+
+#[tauri::command]
+fn reading_time(state: State<'_, OcrState>) -> Vec<AppReadingTime> {
+    state.read().stats.reading_time()
+}
+
+/// Lookups since `since` in seconds since the Unix epoch, or all of them.
+#[tauri::command]
+fn lookup_history(state: State<'_, OcrState>, since: Option<u64>) -> Vec<HistoryEntry> {
+    state.read().history.since(since.unwrap_or(0)).to_vec()
+}
+
+/// Exports the whole lookup history for flashcard apps, returning the file path.
+#[tauri::command]
+fn export_history(state: State<'_, OcrState>) -> Result<PathBuf, String> {
+    let dir = tauri::api::path::download_dir().ok_or("No downloads folder")?;
+    let path = dir.join(format!("live-ocrs-history-{}.tsv", history::now()));
+    let state = state.read();
+    history::export_tsv(
+        &state.history.all(),
+        &state.definitions.dict,
+        state.config.headword_script,
+        &path,
+    )
+    .map_err(|err| err.to_string())?;
+    Ok(path)
+}
+
+#[tauri::command]
+fn session_status(environment: State<'_, Environment>) -> SessionStatus {
+    environment.0.lock().clone()
+}
+
+#[tauri::command]
+fn reading_sessions(state: State<'_, OcrState>) -> Vec<ReadingSession> {
+    let state = state.read();
+    reading::sessions(
+        &state.history.all(),
+        state.config.reading_session_gap_secs(),
+    )
+}
+
+/// Exports the lookups of the reading session starting at `start` like [`export_history`], with
+/// the lines they were found in as a transcript next to it. Returns the lookup file path.
+#[tauri::command]
+fn export_reading_session(state: State<'_, OcrState>, start: u64) -> Result<PathBuf, String> {
+    let dir = tauri::api::path::download_dir().ok_or("No downloads folder")?;
+    let path = dir.join(format!("live-ocrs-session-{start}.tsv"));
+    let state = state.read();
+    let history = state.history.all();
+    let entries =
+        reading::session_entries(&history, state.config.reading_session_gap_secs(), start);
+    if entries.is_empty() {
+        return Err("The session no longer exists".to_string());
+    }
+    history::export_tsv(
+        entries,
+        &state.definitions.dict,
+        state.config.headword_script,
+        &path,
+    )
+    .map_err(|err| err.to_string())?;
+    let transcript = reading::transcript(entries).join("\n");
+    fs::write(path.with_extension("txt"), transcript).map_err(|err| err.to_string())?;
+    Ok(path)
+}
+
+#[tauri::command]
+fn memory_usage(state: State<'_, OcrState>) -> MemoryUsage {
+    MemoryUsage::of(&state.read())
+}
+
+#[tauri::command]
+fn engine_cache_entries(app: AppHandle) -> Vec<EngineCacheEntry> {
+    engine_cache::list(engine_cache_dir(&app))
+}
+
+#[tauri::command]
+fn clear_engine_cache(app: AppHandle) -> Result<(), String> {
+    engine_cache::clear(engine_cache_dir(&app)).map_err(|err| err.to_string())
+}
+
+/// Toggles OCR like the hotkey, but with detection options specific to this capture.
+#[tauri::command]
+fn toggle_ocr(app: AppHandle, state: State<'_, OcrState>, options: Option<CaptureOptions>) {
+    handle_toggle(app, state.inner().clone(), options.unwrap_or_default());
+}
+
+#[tauri::command]
+fn pending_session(pending: State<'_, PendingSession>) -> bool {
+    pending.0.lock().is_some()
+}
+
+#[tauri::command]
+fn restore_session(
+    app: AppHandle,
+    state: State<'_, OcrState>,
+    pending: State<'_, PendingSession>,
+) -> Result<(), String> {
+    let Some(snapshot) = pending.0.lock().take() else {
+        return Ok(());
+    };
+    if !snapshot.restore(&mut state.write()) {
+        session::clear(session_path(&app));
+        return Err("The monitor from the previous session is no longer connected".into());
+    }
+    show_ocr_results(&app, state.inner());
+    for pin in snapshot.pins {
+        open_pin(&app, pin);
+    }
+    save_session(&app);
+    Ok(())
+}
+
+#[tauri::command]
+fn discard_session(app: AppHandle, pending: State<'_, PendingSession>) {
+    pending.0.lock().take();
+    session::clear(session_path(&app));
+}
+
+/// Saves the current OCR results to the sessions folder, returning the file path.
+#[tauri::command]
+fn save_ocr_session(app: AppHandle, state: State<'_, OcrState>) -> Result<PathBuf, String> {
+    let dir = sessions_dir(&app);
+    fs::create_dir_all(&dir).map_err(|err| err.to_string())?;
+    let path = dir.join(format!("{}.json", history::now()));
+    state
+        .read()
+        .save_session(&path)
+        .map_err(|err| err.to_string())?;
+    Ok(path)
+}
+
+#[tauri::command]
+fn saved_sessions(app: AppHandle) -> Vec<SavedSession> {
+    session::list_saved(sessions_dir(&app))
+}
+
+#[tauri::command]
+fn open_session(app: AppHandle, state: State<'_, OcrState>, path: PathBuf) -> Result<(), String> {
+    let snapshot = state
+        .write()
+        .load_session(path)
+        .map_err(|err| err.to_string())?;
+    show_ocr_results(&app, state.inner());
+    for pin in snapshot.pins {
+        open_pin(&app, pin);
+    }
+    save_session(&app);
+    Ok(())
+}
+
+#[tauri::command]
+fn correction_text(pending: State<'_, PendingCorrection>) -> Option<String> {
+    pending.0.lock().as_ref().map(|word| word.text.clone())
+}
+
+#[tauri::command]
+fn correct_text(
+    app: AppHandle,
+    state: State<'_, OcrState>,
+    pending: State<'_, PendingCorrection>,
+    text: String,
+    always: bool,
+) {
+    let Some(word) = pending.0.lock().take() else {
+        return;
+    };
+    let update = correction::correct_block(&mut state.write(), &word, text, always);
+    emit_refresh(&app, state.inner(), Refresh::Changed(update));
+}
+
+#[tauri::command]
+fn pending_user_entry(pending: State<'_, PendingUserEntry>) -> Option<UserEntryDraft> {
+    pending.0.lock().clone()
+}
+
+#[tauri::command]
+fn user_entries(state: State<'_, OcrState>) -> Vec<UserEntry> {
+    state.read().user_dictionary.entries().to_vec()
+}
+
+#[tauri::command]
+fn save_user_entry(
+    app: AppHandle,
+    state: State<'_, OcrState>,
+    entry: UserEntry,
+) -> Result<(), String> {
+    let update = user_dict::save_entry(&mut state.write(), entry).map_err(|err| err.to_string())?;
+    emit_hover_update(&app, update);
+    Ok(())
+}
+
+#[tauri::command]
+fn delete_user_entry(
+    app: AppHandle,
+    state: State<'_, OcrState>,
+    word: String,
+) -> Result<(), String> {
+    let update =
+        user_dict::delete_entry(&mut state.write(), &word).map_err(|err| err.to_string())?;
+    emit_hover_update(&app, update);
+    Ok(())
+}
+
+#[tauri::command]
+fn capture_preview(pending: State<'_, PendingPreview>) -> Option<CapturePreview> {
+    pending
+        .0
+        .lock()
+        .as_ref()
+        .map(|(preview, _)| preview.clone())
+}
+
+#[tauri::command]
+fn lens_view(lens: State<'_, CurrentLens>) -> Option<LensView> {
+    lens.0.lock().clone()
+}
+
+#[tauri::command]
+fn capture_windows() -> Vec<WindowInfo> {
+    capture::list_windows()
+}
+
+/// Previews a different window, or the monitor under the cursor for `None`.
+#[tauri::command]
+fn adjust_capture(app: AppHandle, pending: State<'_, PendingPreview>, window: Option<u32>) {
+    let Some((_, options)) = pending.0.lock().take() else {
+        return;
+    };
+    let target = window.map(|id| id.to_string());
+    spawn_blocking(move || open_preview(&app, target.as_deref(), options));
+}
+
+/// Longest wait for the preview window to be destroyed before capturing anyway
+const PREVIEW_CLOSE_TIMEOUT: Duration = Duration::from_millis(500);
+/// Time for the compositor to present a frame without the preview window
+const COMPOSITOR_FRAME: Duration = Duration::from_millis(50);
+
+/// Runs OCR on the previewed source. The preview is closed first, so it isn't captured along
+/// with what it shows.
+#[tauri::command]
+fn confirm_capture(app: AppHandle, state: State<'_, OcrState>, pending: State<'_, PendingPreview>) {
+    let Some((preview, options)) = pending.0.lock().take() else {
+        return;
+    };
+    let options = CaptureOptions {
+        target: Some(match preview.window {
+            Some(window) => CaptureTarget::Window(window.id.to_string()),
+            None => CaptureTarget::Monitor,
+        }),
+        ..options
+    };
+    let closed = app.get_window("preview").map(|window| {
+        let (tx, rx) = std::sync::mpsc::channel();
+        window.on_window_event(move |event| {
+            if let WindowEvent::Destroyed = event {
+                tx.send(()).ok();
+            }
+        });
+        window.hide().unwrap();
+        window.close().unwrap();
+        rx
+    });
+    let state = state.inner().clone();
+    spawn_blocking(move || {
+        if let Some(closed) = closed {
+            closed.recv_timeout(PREVIEW_CLOSE_TIMEOUT).ok();
+            // The compositor may still show the last frame of the window
+            std::thread::sleep(COMPOSITOR_FRAME);
+        }
+        run_toggle(app, state, options);
+    });
+}
+
+#[tauri::command]
+fn cancel_capture(app: AppHandle, pending: State<'_, PendingPreview>) {
+    pending.0.lock().take();
+    if let Some(window) = app.get_window("preview") {
+        window.close().unwrap();
+    }
+}
+
+#[tauri::command]
+fn overlay_blocks(state: State<'_, OcrState>) -> Option<OverlayBlocks> {
+    state.read().overlay_blocks()
+}
+
+#[tauri::command]
+fn ruby_overlay(state: State<'_, OcrState>) -> Option<RubyOverlay> {
+    build_ruby(&state.read())
+}
+
+#[tauri::command]
+fn clipboard_image(pending: State<'_, PendingImage>) -> Option<String> {
+    pending.0.lock().clone()
+}
+
+/// Closes the image window, turning OCR off if it's still running on the image.
+#[tauri::command]
+fn close_image(app: AppHandle, state: State<'_, OcrState>) {
+    let showing_image = {
+        let state = state.read();
+        state.enabled && state.app.as_deref() == Some(IMAGE_APP)
+    };
+    if showing_image {
+        run_toggle(app, state.inner().clone(), CaptureOptions::default());
+    } else if let Some(window) = app.get_window("image") {
+        window.close().unwrap();
+    }
+}
+
+#[tauri::command]
+fn ocr_blocks(state: State<'_, OcrState>) -> Vec<BlockInfo> {
+    state.read().blocks()
+}
+
+#[tauri::command]
+fn tooltip_effect(state: State<'_, OcrState>) -> WindowEffect {
+    state.read().config.tooltip_effect
+}
+
+/// Shows a dimmed snapshot of the source over it, to confirm or adjust before running OCR.
+fn open_preview(handle: &AppHandle, target: Option<&str>, options: CaptureOptions) {
+    let preview = match capture::preview(target) {
+        Ok(preview) => preview,
+        Err(err) => {
+            log::error!("{err}");
+            handle
+                .emit_to("main", "capture-failed", err.to_string())
+                .unwrap();
+            return;
+        }
+    };
+    let bounds = preview.bounds;
+    *handle.state::<PendingPreview>().0.lock() = Some((preview, options));
+    if let Some(window) = handle.get_window("preview") {
+        window.close().unwrap();
+    }
+    let window = WindowBuilder::new(handle, "preview", WindowUrl::App("preview.html".into()))
+        .title("LiveOCR - Capture Preview")
+        .always_on_top(true)
+        .decorations(false)
+        .transparent(true)
+        .skip_taskbar(true)
+        .visible(false)
+        .build()
+        .unwrap();
+    window
+        .set_size(PhysicalSize::new(bounds.width(), bounds.height()))
+        .unwrap();
+    window
+        .set_position(PhysicalPosition::new(bounds.min().x, bounds.min().y))
+        .unwrap();
+    window.show().unwrap();
+    window.set_focus().unwrap();
+}
+
+fn build_tooltip_window(handle: &AppHandle, label: &str) -> Window {
+    let effect = handle.state::<OcrState>().read().config.tooltip_effect;
+    let window = WindowBuilder::new(handle, label, WindowUrl::App("tooltip.html".into()))
+        .always_on_top(true)
+        .decorations(false)
+        .focused(false)
+        .visible(false)
+        .transparent(effect != WindowEffect::None)
+        .build()
+        .unwrap();
+    window.set_ignore_cursor_events(true).unwrap();
+    style::apply_effect(&window, effect);
+    window
+}
+
+/// Height of the reading strip in logical pixels
+const STRIP_HEIGHT: f64 = 44.0;
+
+/// Opens the reading strip, or moves it to `monitor` if it's already open.
+fn show_strip_window(handle: &AppHandle, edge: ScreenEdge, monitor: geo::Rect<f32>) {
+    let window = handle.get_window("strip").unwrap_or_else(|| {
+        let window = WindowBuilder::new(handle, "strip", WindowUrl::App("strip.html".into()))
+            .always_on_top(true)
+            .decorations(false)
+            .focused(false)
+            .skip_taskbar(true)
+            .build()
+            .unwrap();
+        window.set_ignore_cursor_events(true).unwrap();
+        window
+    });
+    let height = (STRIP_HEIGHT * window.scale_factor().unwrap()) as f32;
+    let y = match edge {
+        ScreenEdge::Top => monitor.min().y,
+        ScreenEdge::Bottom => monitor.max().y - height,
+    };
+    window
+        .set_size(PhysicalSize::new(monitor.width(), height))
+        .unwrap();
+    window
+        .set_position(PhysicalPosition::new(monitor.min().x, y))
+        .unwrap();
+}
+
+/// Width of the details panel in logical pixels
+const PANEL_WIDTH: f64 = 360.0;
+
+/// Opens the details panel, or moves it to `monitor` if it's already open.
+fn show_panel_window(handle: &AppHandle, side: PanelSide, monitor: geo::Rect<f32>) {
+    let window = handle.get_window("panel").unwrap_or_else(|| {
+        let effect = handle.state::<OcrState>().read().config.tooltip_effect;
+        let window = WindowBuilder::new(handle, "panel", WindowUrl::App("tooltip.html".into()))
+            .title("LiveOCR - Details")
+            .always_on_top(true)
+            .decorations(false)
+            .focused(false)
+            .skip_taskbar(true)
+            .transparent(effect != WindowEffect::None)
+            .build()
+            .unwrap();
+        style::apply_effect(&window, effect);
+        window
+    });
+    let width = (PANEL_WIDTH * window.scale_factor().unwrap()) as f32;
+    let x = match side {
+        PanelSide::Left => monitor.min().x,
+        PanelSide::Right => monitor.max().x - width,
+    };
+    window
+        .set_size(PhysicalSize::new(width, monitor.height()))
+        .unwrap();
+    window
+        .set_position(PhysicalPosition::new(x, monitor.min().y))
+        .unwrap();
+}
+
+fn place_tooltip(
+    window: &Window,
+    rect: geo::Rect<f32>,
+    monitor: geo::Rect<f32>,
+    width: f32,
+    height: f32,
+) -> tauri::Result<()> {
     let width = width.ceil();
     let height = height.ceil();
-    if let Some(((_, _, rect), monitor)) = state.hovering.as_ref().zip(state.monitor.as_ref()) {
-        window.set_size(LogicalSize::new(width, height))?;
-        let actual_size = window.inner_size()?;
-        log::info!("Virtual size: ({width}, {height}), actual size: {actual_size:?}");
-        let width = actual_size.width as f32;
-        let height = actual_size.height as f32;
-        let align_left = rect.min().x + width > monitor.x() as f32 + monitor.width() as f32;
-        let align_top = rect.max().y + height > monitor.y() as f32 + monitor.height() as f32;
-        let x = if align_left {
-            rect.max().x - width as f32
-        } else {
-            rect.min().x
+    window.set_size(LogicalSize::new(width, height))?;
+    let actual_size = window.inner_size()?;
+    log::info!("Virtual size: ({width}, {height}), actual size: {actual_size:?}");
+    let width = actual_size.width as f32;
+    let mut height = actual_size.height as f32;
+    let space_below = monitor.max().y - rect.max().y;
+    let space_above = rect.min().y - monitor.min().y;
+    // Pinned tooltips scroll, so cut long ones off at the screen edge instead of letting them
+    // run past it
+    let max_height = space_below.max(space_above);
+    if height > max_height {
+        height = max_height;
+        window.set_size(PhysicalSize::new(width, height))?;
+    }
+    let align_left = rect.min().x + width > monitor.max().x;
+    let align_top = height > space_below;
+    let x = if align_left {
+        rect.max().x - width as f32
+    } else {
+        rect.min().x
+    };
+    let y = if align_top {
+        rect.min().y - height as f32
+    } else {
+        rect.max().y
+    };
+    window.set_position(PhysicalPosition::new(x, y))?;
+    window.show()?;
+    Ok(())
+}
+
+fn handle_pin(handle: AppHandle, state: OcrState) {
+    spawn_blocking(move || {
+        let pin = {
+            let state = state.read();
+            let hovering = state.hover.word().zip(state.monitor.as_ref());
+            hovering.map(|(word, monitor)| PinnedTooltip {
+                rect: word.rect,
+                monitor: monitor_bounds(monitor),
+                entries: build_tooltip(&state.definitions.definitions, &state.config),
+            })
         };
-        let y = if align_top {
-            rect.min().y - height as f32
-        } else {
-            rect.max().y
+        let Some(pin) = pin.filter(|pin| !pin.entries.is_empty()) else {
+            return;
         };
-        window.set_position(PhysicalPosition::new(x, y))?;
-        window.show()?;
-    } else {
-        window.hide()?;
+        open_pin(&handle, pin);
+        save_session(&handle);
+    });
+}
+
+fn open_pin(handle: &AppHandle, pin: PinnedTooltip) {
+    let pins = handle.state::<Pins>();
+    let label = format!("pin-{}", pins.next_id.fetch_add(1, Ordering::Relaxed));
+    pins.windows.lock().insert(label.clone(), pin);
+    let window = build_tooltip_window(handle, &label);
+    // Unlike the hover tooltip, pinned ones can be scrolled and their text selected
+    window.set_ignore_cursor_events(false).unwrap();
+}
+
+fn handle_correct(handle: AppHandle, state: OcrState) {
+    spawn_blocking(move || {
+        let Some(word) = state.read().hover.word().cloned() else {
+            return;
+        };
+        *handle.state::<PendingCorrection>().0.lock() = Some(word);
+        if let Some(window) = handle.get_window("correct") {
+            window.close().unwrap();
+        }
+        WindowBuilder::new(&handle, "correct", WindowUrl::App("correct.html".into()))
+            .title("LiveOCR - Correct Text")
+            .inner_size(400.0, 110.0)
+            .always_on_top(true)
+            .focused(true)
+            .build()
+            .unwrap();
+    });
+}
+
+fn handle_user_entry(handle: AppHandle, state: OcrState) {
+    spawn_blocking(move || {
+        let Some(draft) = user_dict::entry_for_hovered(&state.read()) else {
+            return;
+        };
+        *handle.state::<PendingUserEntry>().0.lock() = Some(draft);
+        if let Some(window) = handle.get_window("user-entry") {
+            window.close().unwrap();
+        }
+        WindowBuilder::new(
+            &handle,
+            "user-entry",
+            WindowUrl::App("user-entry.html".into()),
+        )
+        .title("LiveOCR - User Dictionary")
+        .inner_size(400.0, 300.0)
+        .always_on_top(true)
+        .focused(true)
+        .build()
+        .unwrap();
+    });
+}
+
+fn handle_open_entry(handle: AppHandle, state: OcrState) {
+    spawn_blocking(move || {
+        let details = {
+            let state = state.read();
+            build_entry_details(
+                &state.definitions.dict,
+                &state.definitions.definitions,
+                &state.collocations,
+                state.config.tone_palette,
+            )
+        };
+        let Some(details) = details else {
+            return;
+        };
+
+        *handle.state::<OpenEntry>().0.lock() = Some(details.clone());
+        if let Some(window) = handle.get_window("entry") {
+            handle.emit_to("entry", "entry-changed", details).unwrap();
+            window.set_focus().unwrap();
+        } else {
+            WindowBuilder::new(&handle, "entry", WindowUrl::App("entry.html".into()))
+                .title("LiveOCR - Entry")
+                .inner_size(480.0, 600.0)
+                .build()
+                .unwrap();
+        }
+    });
+}
+
+fn close_pins(handle: &AppHandle) {
+    let labels: Vec<String> = handle
+        .state::<Pins>()
+        .windows
+        .lock()
+        .drain()
+        .map(|(label, _)| label)
+        .collect();
+    for label in labels {
+        if let Some(window) = handle.get_window(&label) {
+            window.close().unwrap();
+        }
     }
+    save_session(handle);
+}
 
-    Ok(())
+fn session_path(app: &AppHandle) -> PathBuf {
+    let data_dir = app.path_resolver().app_data_dir();
+    data_dir
+        .unwrap_or_else(|| ".data".into())
+        .join("session.json")
+}
+
+fn sessions_dir(app: &AppHandle) -> PathBuf {
+    let data_dir = app.path_resolver().app_data_dir();
+    data_dir.unwrap_or_else(|| ".data".into()).join("sessions")
+}
+
+/// Writes the current session to disk while OCR is enabled, and removes it otherwise.
+fn save_session(handle: &AppHandle) {
+    let pins = handle
+        .state::<Pins>()
+        .windows
+        .lock()
+        .values()
+        .cloned()
+        .collect();
+    let snapshot = Snapshot::new(&handle.state::<OcrState>().read(), pins);
+    match snapshot {
+        Some(snapshot) => session::save(session_path(handle), &snapshot),
+        None => session::clear(session_path(handle)),
+    }
+}
+
+/// Opens the tooltip or reading strip for fresh OCR results and sends them to all windows.
+fn show_ocr_results(handle: &AppHandle, state: &OcrState) {
+    let strings: Vec<String> = state
+        .read()
+        .definitions
+        .ocr_strings
+        .iter()
+        .map(|it| it.text.clone())
+        .collect();
+    handle.emit_to("main", "ocr-changed", strings).unwrap();
+    run_ocr_hook(state);
+    stream_blocks(handle, state);
+    emit_ruby(handle, state);
+    let (strip, panel, highlight, monitor) = {
+        let state = state.read();
+        (
+            state.config.reading_strip,
+            state.config.details_panel,
+            state.config.highlight_blocks,
+            state.monitor.as_ref().map(monitor_bounds),
+        )
+    };
+    match monitor.filter(|_| highlight) {
+        Some(monitor) if handle.get_window("highlight").is_none() => {
+            build_overlay_window(handle, "highlight", monitor)
+        }
+        _ => emit_highlight(handle, state),
+    }
+    // OCR can run again while the results windows are still open, e.g. for a clipboard image
+    // or a reopened session, and labels have to be unique
+    match strip.zip(monitor) {
+        Some((edge, monitor)) => show_strip_window(handle, edge, monitor),
+        None if handle.get_window("tooltip").is_none() => {
+            build_tooltip_window(handle, "tooltip");
+        }
+        None => {}
+    }
+    if let Some((side, monitor)) = panel.zip(monitor) {
+        show_panel_window(handle, side, monitor);
+    }
+    {
+        let state = state.read();
+        emit_definitions(handle, state.definitions.page(0, TOOLTIP_PAGE_SIZE), &state);
+    }
+    handle.emit_to("main", "state-changed", "enabled").unwrap();
 }
 
-fn handle_toggle(handle: AppHandle, state: OcrState) {
+fn handle_toggle(handle: AppHandle, state: OcrState, options: CaptureOptions) {
+    let (enabled, preview, target) = {
+        let state = state.read();
+        let config = &state.config;
+        (
+            state.enabled,
+            config.capture_preview,
+            config.target_window.clone(),
+        )
+    };
+    if !enabled && preview {
+        spawn_blocking(move || open_preview(&handle, target.as_deref(), options));
+        return;
+    }
+    run_toggle(handle, state, options);
+}
+
+fn run_toggle(handle: AppHandle, state: OcrState, options: CaptureOptions) {
+    let can_capture = handle.state::<Environment>().0.lock().can_capture();
+    if !state.read().enabled && !can_capture {
+        handle
+            .emit_to(
+                "main",
+                "capture-failed",
+                "Screen capture isn't available in this session",
+            )
+            .unwrap();
+        return;
+    }
     spawn_blocking(move || {
         let ui_state = if state.read().enabled {
             "disabled"
@@ -157,79 +1144,182 @@ fn handle_toggle(handle: AppHandle, state: OcrState) {
         handle.emit_to("main", "state-changed", ui_state).unwrap();
         let action = {
             let mut state = state.write();
-            toggle(state.borrow_mut())
+            toggle(state.borrow_mut(), &options)
         };
 
         match action {
-            live_ocrs::Action::UpdateOcr => {
-                let strings: Vec<String> = state
-                    .read()
-                    .definitions
-                    .ocr_strings
-                    .iter()
-                    .map(|it| it.0.clone())
-                    .collect();
-                handle.emit_to("main", "ocr-changed", strings).unwrap();
-                let definitions = state.read().definitions.definitions.clone();
-                let window =
-                    WindowBuilder::new(&handle, "tooltip", WindowUrl::App("tooltip.html".into()))
-                        .always_on_top(true)
-                        .decorations(false)
-                        .focused(false)
-                        .visible(false)
-                        .build()
-                        .unwrap();
-                window.set_ignore_cursor_events(true).unwrap();
-                handle
-                    .emit_to("tooltip", "definitions-changed", definitions)
-                    .unwrap();
-                handle.emit_to("main", "state-changed", "enabled").unwrap();
+            Ok(live_ocrs::Action::UpdateOcr) => {
+                show_ocr_results(&handle, &state);
+                save_session(&handle);
             }
-            live_ocrs::Action::CloseTooltip => {
+            Ok(live_ocrs::Action::CloseTooltip) => {
+                save_session(&handle);
                 handle
                     .emit_to("main", "ocr-changed", Vec::<String>::new())
                     .unwrap();
-                if let Some(window) = handle.get_window("tooltip") {
-                    window.close().unwrap();
+                for label in ["tooltip", "strip", "panel", "image", "ruby", "highlight"] {
+                    if let Some(window) = handle.get_window(label) {
+                        window.close().unwrap();
+                    }
                 }
             }
-            live_ocrs::Action::None => {}
+            Ok(live_ocrs::Action::None) => {}
+            Err(err) => {
+                log::error!("{err}");
+                handle
+                    .emit_to("main", "capture-failed", err.to_string())
+                    .unwrap();
+                handle.emit_to("main", "state-changed", "disabled").unwrap();
+            }
         }
     });
 }
 
-fn init_state(app: AppHandle) -> Result<OcrState, Box<dyn Error>> {
+/// Safe mode skips GPU execution providers, the TensorRT engine cache and the user config, to
+/// diagnose crashes caused by any of them. Enabled by `--safe-mode` or holding shift on launch,
+/// if the keyboard can be read in this session.
+fn is_safe_mode(can_track_input: bool) -> bool {
+    if env::args().any(|arg| arg == "--safe-mode") {
+        return true;
+    }
+    if !can_track_input {
+        return false;
+    }
+    let keys = DeviceState::new().get_keys();
+    keys.contains(&Keycode::LShift) || keys.contains(&Keycode::RShift)
+}
+
+fn engine_cache_dir(app: &AppHandle) -> PathBuf {
+    app.path_resolver()
+        .app_cache_dir()
+        .unwrap_or_else(|| ".cache".into())
+        .join("engines")
+}
+
+fn init_state(app: AppHandle, safe_mode: bool) -> Result<OcrState, Box<dyn Error>> {
     let paths = app.path_resolver();
     let cache_dir = paths.app_cache_dir().unwrap_or_else(|| ".cache".into());
     if !cache_dir.exists() {
         fs::create_dir_all(&cache_dir).unwrap();
     }
-    let ocr = RapidOCRBuilder::new()
+    let config_path = paths
+        .app_config_dir()
+        .unwrap_or_else(|| ".config".into())
+        .join("config.json");
+    log::info!("Config Path: {config_path:?}");
+    let config = if safe_mode {
+        Config::default()
+    } else {
+        config::load(&config_path).unwrap_or_else(|err| {
+            app.state::<Environment>().0.lock().report(format!(
+                "Failed to load {}, using the default config: {err}",
+                config_path.display()
+            ));
+            Config::default()
+        })
+    };
+    let log_dir = paths.app_log_dir().unwrap_or_else(|| ".".into());
+    let data_dir = paths.app_data_dir().unwrap_or_else(|| ".data".into());
+    if !data_dir.exists() {
+        fs::create_dir_all(&data_dir).unwrap();
+    }
+    let base_dict = match &config.jmdict_path {
+        Some(jmdict_path) => {
+            DictionarySource::new(DictionaryFormat::Jmdict, jmdict_path).with_name("JMdict")
+        }
+        None => {
+            let dict_path = match &config.cedict_path {
+                Some(cedict_path) => cedict_path.clone(),
+                None => paths.resolve_resource("data/cedict.json").unwrap(),
+            };
+            println!("Dict Path: {dict_path:?}");
+            DictionarySource::new(DictionaryFormat::Cedict, dict_path).with_name("CC-CEDICT")
+        }
+    };
+    let sources = std::iter::once(base_dict)
+        .chain(config.dictionaries.iter().cloned())
+        .collect::<Vec<_>>();
+    // Dictionaries load while the OCR engine is built, which can take a while with TensorRT
+    let loader = {
+        let app = app.clone();
+        dict::load_in_background(sources, cache_dir.join("dict"), move |progress| {
+            *app.state::<LoadingProgress>().0.lock() = Some(progress.clone());
+            let _ = app.emit_to("splashscreen", "loading-progress", progress);
+        })
+    };
+    let det_model = paths
+        .resolve_resource("models/ch_PP-OCRv4_det_infer/ch_PP-OCRv4_det_infer.onnx")
+        .ok_or("Det Model not found")?;
+    let rec_model = paths
+        .resolve_resource("models/ch_PP-OCRv4_rec_infer/ch_PP-OCRv4_rec_infer.onnx")
+        .ok_or("Rec Model not found")?;
+    let keys = paths
+        .resolve_resource("models/ppocr_keys_v1.txt")
+        .ok_or("Keys not found")?;
+    let mut ocr = RapidOCRBuilder::new()
         .max_side_len(2048)
-        .det_model(
-            paths
-                .resolve_resource("models/ch_PP-OCRv4_det_infer/ch_PP-OCRv4_det_infer.onnx")
-                .ok_or("Det Model not found")?,
-        )
-        .rec_model(
-            paths
-                .resolve_resource("models/ch_PP-OCRv4_rec_infer/ch_PP-OCRv4_rec_infer.onnx")
-                .ok_or("Rec Model not found")?,
-            paths
-                .resolve_resource("models/ppocr_keys_v1.txt")
-                .ok_or("Keys not found")?,
-        )
-        .with_execution_providers([ExecutionProvider::TensorRT])
-        .with_engine_cache_path(&cache_dir)
-        .build()?;
-    let dict_path = paths.resolve_resource("data/cedict.json").unwrap();
-    println!("Dict Path: {dict_path:?}");
+        .det_model(det_model.clone())
+        .rec_model(rec_model.clone(), keys);
+    if !safe_mode {
+        let engine_cache_dir = engine_cache_dir(&app);
+        engine_cache::invalidate_if_stale(&engine_cache_dir, &[&det_model, &rec_model])?;
+        ocr = ocr
+            .with_execution_providers([ExecutionProvider::TensorRT])
+            .with_engine_cache_path(&engine_cache_dir);
+    }
+    let ocr = ocr.build()?;
+    let mut dict = loader.wait()?;
+    if let Some(path) = &config.frequency_list {
+        match dict::load_frequency_list(path) {
+            Ok(ranks) => dict.set_frequency_ranks(ranks),
+            Err(err) => log::error!("Failed to load frequency list {path:?}: {err}"),
+        }
+    }
+    for path in &config.level_lists {
+        match dict::load_level_list(path) {
+            Ok(levels) => dict.add_levels(levels),
+            Err(err) => log::error!("Failed to load level list {path:?}: {err}"),
+        }
+    }
+    for path in &config.idiom_packs {
+        match dict::load_idiom_pack(path) {
+            Ok(idioms) => dict.add_idioms(idioms),
+            Err(err) => log::error!("Failed to load idiom pack {path:?}: {err}"),
+        }
+    }
+    let user_dictionary = user_dict::load(data_dir.join("user_dictionary.json"));
+    dict.set_user_entries(user_dictionary.dictionary_entries());
+    let history = history::load(
+        data_dir.join("history.jsonl"),
+        config.memory_limits.history_entries,
+    );
     let state = LiveOcr {
         capture_state: Arc::new(CaptureState { ocr }),
+        config,
         enabled: false,
-        hovering: None,
-        definitions: Definitions::new(dict::load(dict_path, cache_dir.join("dict"))),
+        hover: HoverState::Idle,
+        dwell: None,
+        definitions: Definitions::new(dict),
         monitor: None,
+        target_window: None,
+        range_map: None,
+        app: None,
+        corrections: correction::load(data_dir.join("corrections.json")),
+        last_frame: None,
+        static_results: false,
+        live_capture: Default::default(),
+        known_words: known::load(data_dir.join("known_words.json")),
+        user_dictionary,
+        collocations: collocation::load(
+            data_dir.join("collocations.jsonl"),
+            config.memory_limits.counted_sentences,
+        ),
+        history,
+        stats: stats::load(data_dir.join("stats.json")),
+        metrics: metrics::load(
+            log_dir.join("metrics.json"),
+            if safe_mode { "CPU" } else { "TensorRT" },
+        ),
     };
     Ok(Arc::new(RwLock::new(state)))
 }
@@ -261,20 +1351,320 @@ async fn track_cursor(state: OcrState, app: AppHandle) {
                 update_hover(state.borrow_mut(), position)
             };
 
-            if let Some((_, definitions)) = update {
-                let tooltip = app.get_window("tooltip");
-                if let Some(tooltip) = &tooltip {
-                    tooltip.hide().unwrap();
-                    /*                     if let Some(rect) = rect {
-                        let position =
-                            PhysicalPosition::new(rect.min().x as i32, rect.max().y as i32);
-                        tooltip.set_position(position).unwrap()
-                    } */
+            emit_hover_update(&app, update);
+        }
+    }
+}
+
+/// Registers a global shortcut. Failures are reported in the session status instead of
+/// aborting startup, since other applications and remote desktop clients can hold shortcuts.
+fn register_shortcut(app: &AppHandle, accelerator: &str, handler: impl Fn() + Send + 'static) {
+    if let Err(err) = app.global_shortcut_manager().register(accelerator, handler) {
+        app.state::<Environment>()
+            .0
+            .lock()
+            .report(format!("Couldn't register the {accelerator} hotkey: {err}"));
+    }
+}
+
+fn expire_tooltip(state: OcrState, app: AppHandle) {
+    loop {
+        std::thread::sleep(Duration::from_millis(50));
+        let update = expire_hover(&mut state.write());
+        emit_hover_update(&app, update);
+    }
+}
+
+/// Distance between the cursor and the lens window, in physical pixels
+const LENS_OFFSET: i32 = 24;
+
+/// Shows the magnifier next to the cursor while the lens key is held. The screen is captured
+/// once when the key goes down and the lens follows the cursor over that frame.
+fn watch_lens(state: OcrState, app: AppHandle, config: LensConfig) {
+    let Ok(key) = Keycode::from_str(&config.key) else {
+        log::error!("Unknown lens key {}", config.key);
+        return;
+    };
+    let device_state = DeviceState::new();
+    let mut lens = None;
+    let mut last_position = None;
+    loop {
+        std::thread::sleep(Duration::from_millis(30));
+        if !device_state.get_keys().contains(&key) {
+            if lens.take().is_some() {
+                last_position = None;
+                if let Some(window) = app.get_window("lens") {
+                    window.hide().unwrap();
                 }
+            }
+            continue;
+        }
+        let position = device_state.get_mouse().coords;
+        if last_position == Some(position) {
+            continue;
+        }
 
-                app.emit_to("tooltip", "definitions-changed", definitions)
-                    .unwrap();
+        let view = {
+            let state = state.read();
+            if lens.is_none() {
+                lens = Lens::open(&state);
             }
+            lens.as_ref()
+                .and_then(|lens| lens.view(&state, position, &config))
+        };
+        let Some(view) = view else {
+            continue;
+        };
+        last_position = Some(position);
+        show_lens(&app, position, view);
+    }
+}
+
+fn show_lens(app: &AppHandle, cursor: (i32, i32), view: LensView) {
+    let window = app.get_window("lens").unwrap_or_else(|| {
+        let window = WindowBuilder::new(app, "lens", WindowUrl::App("lens.html".into()))
+            .always_on_top(true)
+            .decorations(false)
+            .focused(false)
+            .skip_taskbar(true)
+            .visible(false)
+            .build()
+            .unwrap();
+        window.set_ignore_cursor_events(true).unwrap();
+        window
+    });
+    window
+        .set_size(PhysicalSize::new(view.width, view.height))
+        .unwrap();
+    window
+        .set_position(PhysicalPosition::new(
+            cursor.0 + LENS_OFFSET,
+            cursor.1 + LENS_OFFSET,
+        ))
+        .unwrap();
+    *app.state::<CurrentLens>().0.lock() = Some(view.clone());
+    app.emit_to("lens", "lens-changed", view).unwrap();
+    window.show().unwrap();
+}
+
+/// Live mode: keeps OCR results up to date with the screen while enabled.
+fn refresh_live(state: OcrState, app: AppHandle, interval: Duration) {
+    loop {
+        std::thread::sleep(interval);
+        let refresh = live::refresh(&state, &CaptureOptions::default());
+        emit_refresh(&app, &state, refresh);
+    }
+}
+
+fn handle_recapture(handle: AppHandle, state: OcrState) {
+    spawn_blocking(move || {
+        let refresh = live::recapture_hovered(&state, &CaptureOptions::default());
+        emit_refresh(&handle, &state, refresh);
+    });
+}
+
+fn handle_toggle_known(handle: AppHandle, state: OcrState) {
+    spawn_blocking(move || {
+        let update = known::toggle_hovered(&mut state.write());
+        emit_hover_update(&handle, update);
+    });
+}
+
+fn handle_clipboard_ocr(handle: AppHandle, state: OcrState) {
+    spawn_blocking(move || {
+        handle
+            .emit_to("main", "state-changed", "detecting")
+            .unwrap();
+        if let Err(err) = show_clipboard_image(&handle, &state) {
+            log::error!("{err}");
+            handle
+                .emit_to("main", "capture-failed", err.to_string())
+                .unwrap();
+            let ui_state = if state.read().enabled {
+                "enabled"
+            } else {
+                "disabled"
+            };
+            handle.emit_to("main", "state-changed", ui_state).unwrap();
+        }
+    });
+}
+
+/// Shows the clipboard image in a window centered on the monitor under the cursor and runs
+/// OCR on it where it's displayed, so it can be hovered like any other text.
+fn show_clipboard_image(handle: &AppHandle, state: &OcrState) -> live_ocrs::Result<()> {
+    let frame = capture::center_on_monitor(capture::clipboard_image()?)?;
+    *handle.state::<PendingImage>().0.lock() = Some(capture::data_url(&frame.image)?);
+    if let Some(window) = handle.get_window("image") {
+        window.close().unwrap();
+    }
+    let window = WindowBuilder::new(handle, "image", WindowUrl::App("image.html".into()))
+        .title("LiveOCR - Clipboard Image")
+        .always_on_top(true)
+        .decorations(false)
+        .skip_taskbar(true)
+        .visible(false)
+        .build()
+        .unwrap();
+    window
+        .set_size(PhysicalSize::new(frame.image.width(), frame.image.height()))
+        .unwrap();
+    window
+        .set_position(PhysicalPosition::new(frame.origin.0, frame.origin.1))
+        .unwrap();
+    window.show().unwrap();
+    window.set_focus().unwrap();
+
+    recognize_image(state, frame, &CaptureOptions::default())?;
+    show_ocr_results(handle, state);
+    save_session(handle);
+    Ok(())
+}
+
+fn handle_speak(handle: AppHandle, state: OcrState) {
+    spawn_blocking(move || {
+        speech::speak_hovered(&state.read(), &handle.state::<Speaker>());
+    });
+}
+
+fn handle_anki_export(handle: AppHandle, state: OcrState) {
+    spawn_blocking(move || match anki::export_hovered(&state) {
+        Ok(Some(word)) => handle.emit_to("main", "anki-exported", word).unwrap(),
+        Ok(None) => {}
+        Err(err) => {
+            log::error!("{err}");
+            handle
+                .emit_to("main", "capture-failed", err.to_string())
+                .unwrap();
+        }
+    });
+}
+
+/// Sends the blocks of a new OCR pass to WebSocket clients.
+fn stream_blocks(app: &AppHandle, state: &OcrState) {
+    if let Some(stream) = app.try_state::<EventStream>() {
+        stream.send_blocks(&state.read().blocks());
+    }
+}
+
+/// Sends OCR results that changed while enabled to all windows.
+fn emit_refresh(app: &AppHandle, state: &OcrState, refresh: Refresh) {
+    let Refresh::Changed(update) = refresh else {
+        return;
+    };
+    let strings: Vec<String> = state
+        .read()
+        .definitions
+        .ocr_strings
+        .iter()
+        .map(|it| it.text.clone())
+        .collect();
+    app.emit_to("main", "ocr-changed", strings).unwrap();
+    run_ocr_hook(state);
+    stream_blocks(app, state);
+    emit_ruby(app, state);
+    emit_highlight(app, state);
+    emit_hover_update(app, update);
+    save_session(app);
+}
+
+/// Shows or hides the reading overlay, which draws pinyin above every word on screen.
+fn handle_ruby(handle: AppHandle, state: OcrState) {
+    spawn_blocking(move || {
+        if let Some(window) = handle.get_window("ruby") {
+            window.close().unwrap();
+            return;
+        }
+        if let Some(overlay) = build_ruby(&state.read()) {
+            build_overlay_window(&handle, "ruby", overlay.monitor);
         }
+    });
+}
+
+/// Transparent, click-through window covering the whole monitor, loading `<label>.html`.
+fn build_overlay_window(handle: &AppHandle, label: &str, monitor: geo::Rect<f32>) {
+    let url = WindowUrl::App(format!("{label}.html").into());
+    let window = WindowBuilder::new(handle, label, url)
+        .always_on_top(true)
+        .decorations(false)
+        .focused(false)
+        .skip_taskbar(true)
+        .transparent(true)
+        .build()
+        .unwrap();
+    window.set_ignore_cursor_events(true).unwrap();
+    window
+        .set_size(PhysicalSize::new(monitor.width(), monitor.height()))
+        .unwrap();
+    window
+        .set_position(PhysicalPosition::new(monitor.min().x, monitor.min().y))
+        .unwrap();
+}
+
+/// Sends fresh OCR results to the block highlight overlay, if it's open.
+fn emit_highlight(app: &AppHandle, state: &OcrState) {
+    if app.get_window("highlight").is_none() {
+        return;
+    }
+    let blocks = state.read().overlay_blocks();
+    app.emit_to("highlight", "blocks-changed", blocks).unwrap();
+}
+
+fn run_ocr_hook(state: &OcrState) {
+    let state = state.read();
+    state
+        .config
+        .hooks
+        .ocr_complete(&state.definitions.ocr_strings);
+}
+
+/// Sends fresh OCR results to the reading overlay, if it's open.
+fn emit_ruby(app: &AppHandle, state: &OcrState) {
+    if app.get_window("ruby").is_none() {
+        return;
+    }
+    let overlay = build_ruby(&state.read());
+    app.emit_to("ruby", "ruby-changed", overlay).unwrap();
+}
+
+fn emit_hover_update(app: &AppHandle, update: Option<HoverEvent>) {
+    if let Some((update, stream)) = update.as_ref().zip(app.try_state::<EventStream>()) {
+        stream.send(update);
+    }
+    let definitions = match update {
+        Some(HoverEvent::Show { definitions, .. }) => definitions,
+        Some(HoverEvent::Hide) => Vec::new(),
+        None => return,
+    };
+    if let Some(tooltip) = app.get_window("tooltip") {
+        tooltip.hide().unwrap();
+    }
+    let first_page = &definitions[..definitions.len().min(TOOLTIP_PAGE_SIZE)];
+    emit_definitions(app, first_page, &app.state::<OcrState>().read());
+}
+
+/// Sends definitions to the tooltip, reading strip and details panel. The panel keeps showing
+/// the last word when the tooltip hides, so it can still be read after moving the cursor away.
+fn emit_definitions(app: &AppHandle, definitions: &[DictionaryEntry], state: &LiveOcr) {
+    let grammar = state
+        .hover
+        .word()
+        .filter(|_| {
+            !definitions.is_empty()
+                && state
+                    .config
+                    .tooltip_sections
+                    .contains(&TooltipSection::Grammar)
+        })
+        .map(|word| grammar::notes(&state.definitions.dict, word))
+        .unwrap_or_default();
+    let targets = route_tooltip(definitions, grammar, &state.config);
+    app.emit_to("tooltip", "definitions-changed", targets.tooltip)
+        .unwrap();
+    if !definitions.is_empty() {
+        app.emit_to("panel", "definitions-changed", targets.docked.clone())
+            .unwrap();
     }
+    app.emit_to("strip", "definitions-changed", targets.docked)
+        .unwrap();
 }
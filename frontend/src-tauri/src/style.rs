@@ -0,0 +1,22 @@
+use live_ocrs::config::WindowEffect;
+use tauri::Window;
+use window_vibrancy::{
+    apply_acrylic, apply_blur, apply_mica, apply_vibrancy, NSVisualEffectMaterial,
+};
+
+/// Applies a compositor effect to the window background. macOS only has one kind of effect, so
+/// every effect maps to vibrancy there.
+pub fn apply_effect(window: &Window, effect: WindowEffect) {
+    let result = match effect {
+        WindowEffect::None => return,
+        _ if cfg!(target_os = "macos") => {
+            apply_vibrancy(window, NSVisualEffectMaterial::HudWindow, None, None)
+        }
+        WindowEffect::Acrylic => apply_acrylic(window, None),
+        WindowEffect::Mica => apply_mica(window, None),
+        WindowEffect::Blur => apply_blur(window, None),
+    };
+    if let Err(err) = result {
+        log::warn!("Failed to apply {effect:?} to {}: {err}", window.label());
+    }
+}
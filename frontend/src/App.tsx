@@ -1,14 +1,161 @@
 // @ts-ignore Typescript doesn't see the use directive
 import { copyToClipboard } from "@solid-primitives/clipboard";
+import { invoke } from "@tauri-apps/api";
 import { listen } from "@tauri-apps/api/event";
-import { createResource, createSignal, For, Match, Switch } from "solid-js";
+import { createResource, createSignal, For, Match, Show, Switch } from "solid-js";
 import toast, { Toaster } from "solid-toast";
+import { UserEntry } from "./user-entry/UserEntryForm";
 
 type State = "disabled" | "detecting" | "enabled";
 
+type AppReadingTime = {
+    app: string;
+    millis: number;
+};
+
+type HistoryEntry = {
+    word: string;
+    timestamp: number;
+    line: string;
+    app: string | null;
+};
+
+type SavedSession = {
+    path: string;
+    timestamp: number;
+    lines: number;
+    preview: string;
+};
+
+type ReadingSession = {
+    start: number;
+    end: number;
+    lookups: number;
+    words: number;
+    new_words: string[];
+    apps: string[];
+};
+
+type MemoryUsage = {
+    history_entries: number;
+    history_bytes: number;
+    ocr_blocks: number;
+    ocr_bytes: number;
+    frame_bytes: number;
+};
+
+type SessionStatus = {
+    kind: "local" | "remote_desktop" | "headless";
+    monitors: number;
+    issues: string[];
+};
+
+type EngineCacheEntry = {
+    name: string;
+    size: number;
+};
+
 function App() {
     const [ocrStrings, setOcrStrings] = createSignal<string[]>([]);
     const [state, setState] = createSignal<State>("disabled");
+    const [readingTime] = createResource(
+        () => state() == "disabled",
+        async () => await invoke<AppReadingTime[]>("reading_time")
+    );
+    const [history] = createResource(
+        () => state() == "disabled",
+        async () => await invoke<HistoryEntry[]>("lookup_history")
+    );
+    const exportHistory = async () => {
+        try {
+            const path = await invoke<string>("export_history");
+            toast(`Exported to ${path}`);
+        } catch (err) {
+            toast.error(err as string);
+        }
+    };
+    const [readingSessions] = createResource(
+        () => state() == "disabled",
+        async () => await invoke<ReadingSession[]>("reading_sessions")
+    );
+    const exportReadingSession = async (start: number) => {
+        try {
+            const path = await invoke<string>("export_reading_session", {
+                start,
+            });
+            toast(`Exported to ${path}, with the transcript next to it`);
+        } catch (err) {
+            toast.error(err as string);
+        }
+    };
+    const [userEntries, { refetch: refetchUserEntries }] = createResource(
+        () => state() == "disabled",
+        async () => await invoke<UserEntry[]>("user_entries")
+    );
+    const deleteUserEntry = async (word: string) => {
+        try {
+            await invoke("delete_user_entry", { word });
+            refetchUserEntries();
+        } catch (err) {
+            toast.error(err as string);
+        }
+    };
+    const [savedSessions] = createResource(
+        () => state() == "disabled",
+        async () => await invoke<SavedSession[]>("saved_sessions")
+    );
+    const saveSession = async () => {
+        try {
+            const path = await invoke<string>("save_ocr_session");
+            toast(`Saved to ${path}`);
+        } catch (err) {
+            toast.error(err as string);
+        }
+    };
+    const openSession = async (path: string) => {
+        try {
+            await invoke("open_session", { path });
+        } catch (err) {
+            toast.error(err as string);
+        }
+    };
+    const [engineCache, { refetch: refetchEngineCache }] = createResource(
+        () => state() == "disabled",
+        async () => await invoke<EngineCacheEntry[]>("engine_cache_entries")
+    );
+    const [memoryUsage] = createResource(
+        () => state() == "disabled",
+        async () => await invoke<MemoryUsage>("memory_usage")
+    );
+    const clearEngineCache = async () => {
+        await invoke("clear_engine_cache");
+        toast("Engine cache cleared. Engines are rebuilt on next launch.");
+        refetchEngineCache();
+    };
+    const [sessionStatus, { refetch: refetchSessionStatus }] = createResource(
+        async () => await invoke<SessionStatus>("session_status")
+    );
+    createResource(
+        async () =>
+            await listen("status-changed", () => {
+                refetchSessionStatus();
+            })
+    );
+    const [pendingSession, { mutate: setPendingSession }] = createResource(
+        async () => await invoke<boolean>("pending_session")
+    );
+    const restoreSession = async () => {
+        setPendingSession(false);
+        try {
+            await invoke("restore_session");
+        } catch (err) {
+            toast.error(err as string);
+        }
+    };
+    const discardSession = async () => {
+        setPendingSession(false);
+        await invoke("discard_session");
+    };
     createResource(
         async () =>
             await listen("state-changed", (event) => {
@@ -23,9 +170,29 @@ function App() {
                 setOcrStrings(event.payload as string[]);
             })
     );
+    createResource(
+        async () =>
+            await listen("capture-failed", (event) => {
+                console.log(event);
+                toast.error(event.payload as string);
+            })
+    );
+    createResource(
+        async () =>
+            await listen("anki-exported", (event) => {
+                toast.success(`Added ${event.payload as string} to Anki`);
+            })
+    );
 
     return (
         <div class="p-4 w-full h-full">
+            <Show when={sessionStatus()?.issues.length}>
+                <div class="mb-4 p-2 border border-amber-500 rounded text-sm">
+                    <For each={sessionStatus()?.issues}>
+                        {(issue) => <p>{issue}</p>}
+                    </For>
+                </div>
+            </Show>
             <Switch>
                 <Match when={state() == "enabled"}>
                     <h1 class="text-xl leading-loose text-center font-semibold">
@@ -47,6 +214,14 @@ function App() {
                             )}
                         </For>
                     </div>
+                    <div class="flex justify-center mt-2">
+                        <button
+                            class="px-2 py-1 border border-slate-300 rounded"
+                            onClick={saveSession}
+                        >
+                            Save session
+                        </button>
+                    </div>
                 </Match>
                 <Match when={state() == "detecting"}>
                     <h1 class="text-xl leading-loose text-center">
@@ -59,6 +234,211 @@ function App() {
                     <p class="text-sm text-slate-300 text-center">
                         Press Alt+X to toggle
                     </p>
+                    <Show when={pendingSession()}>
+                        <div class="flex flex-row justify-center items-center space-x-4 mt-4">
+                            <p>LiveOCR didn't shut down properly last time.</p>
+                            <button
+                                class="px-2 py-1 border border-slate-300 rounded"
+                                onClick={restoreSession}
+                            >
+                                Restore session
+                            </button>
+                            <button
+                                class="px-2 py-1 border border-slate-300 rounded"
+                                onClick={discardSession}
+                            >
+                                Discard
+                            </button>
+                        </div>
+                    </Show>
+                    <h2 class="text-lg leading-loose text-center font-semibold mt-4">
+                        Reading Time
+                    </h2>
+                    <table class="mx-auto">
+                        <tbody>
+                            <For each={readingTime() ?? []}>
+                                {(entry, _) => (
+                                    <tr>
+                                        <td class="pr-4">{entry.app}</td>
+                                        <td class="text-right">
+                                            {formatDuration(entry.millis)}
+                                        </td>
+                                    </tr>
+                                )}
+                            </For>
+                        </tbody>
+                    </table>
+                    <h2 class="text-lg leading-loose text-center font-semibold mt-4">
+                        Recent Lookups
+                    </h2>
+                    <table class="mx-auto">
+                        <tbody>
+                            <For each={(history() ?? []).slice(-20).reverse()}>
+                                {(entry, _) => (
+                                    <tr>
+                                        <td class="pr-4 font-medium">
+                                            {entry.word}
+                                        </td>
+                                        <td class="text-sm text-slate-300">
+                                            {entry.line}
+                                        </td>
+                                    </tr>
+                                )}
+                            </For>
+                        </tbody>
+                    </table>
+                    <div class="flex justify-center mt-2">
+                        <button
+                            class="px-2 py-1 border border-slate-300 rounded"
+                            onClick={exportHistory}
+                        >
+                            Export for flashcards
+                        </button>
+                    </div>
+                    <Show when={readingSessions()?.length}>
+                        <h2 class="text-lg leading-loose text-center font-semibold mt-4">
+                            Reading Sessions
+                        </h2>
+                        <table class="mx-auto">
+                            <tbody>
+                                <For each={readingSessions()}>
+                                    {(session, _) => (
+                                        <tr>
+                                            <td class="pr-4">
+                                                {new Date(
+                                                    session.start * 1000
+                                                ).toLocaleString()}
+                                            </td>
+                                            <td class="pr-4">
+                                                {formatDuration(
+                                                    (session.end - session.start) *
+                                                        1000
+                                                )}
+                                            </td>
+                                            <td
+                                                class="pr-4 text-sm text-slate-300"
+                                                title={session.new_words.join(" ")}
+                                            >
+                                                {session.lookups} lookups,{" "}
+                                                {session.new_words.length} new words
+                                                {session.apps.length
+                                                    ? ` in ${session.apps.join(", ")}`
+                                                    : ""}
+                                            </td>
+                                            <td>
+                                                <button
+                                                    class="px-2 py-1 border border-slate-300 rounded"
+                                                    onClick={() =>
+                                                        exportReadingSession(
+                                                            session.start
+                                                        )
+                                                    }
+                                                >
+                                                    Export
+                                                </button>
+                                            </td>
+                                        </tr>
+                                    )}
+                                </For>
+                            </tbody>
+                        </table>
+                    </Show>
+                    <Show when={savedSessions()?.length}>
+                        <h2 class="text-lg leading-loose text-center font-semibold mt-4">
+                            Saved Sessions
+                        </h2>
+                        <table class="mx-auto">
+                            <tbody>
+                                <For each={savedSessions()}>
+                                    {(session, _) => (
+                                        <tr>
+                                            <td class="pr-4">
+                                                {new Date(
+                                                    session.timestamp * 1000
+                                                ).toLocaleString()}
+                                            </td>
+                                            <td class="pr-4 text-sm text-slate-300">
+                                                {session.preview} ({session.lines}{" "}
+                                                lines)
+                                            </td>
+                                            <td>
+                                                <button
+                                                    class="px-2 py-1 border border-slate-300 rounded"
+                                                    onClick={() =>
+                                                        openSession(session.path)
+                                                    }
+                                                >
+                                                    Open
+                                                </button>
+                                            </td>
+                                        </tr>
+                                    )}
+                                </For>
+                            </tbody>
+                        </table>
+                    </Show>
+                    <Show when={userEntries()?.length}>
+                        <h2 class="text-lg leading-loose text-center font-semibold mt-4">
+                            User Dictionary
+                        </h2>
+                        <table class="mx-auto">
+                            <tbody>
+                                <For each={userEntries()}>
+                                    {(entry, _) => (
+                                        <tr>
+                                            <td class="pr-4">{entry.word}</td>
+                                            <td class="pr-4">{entry.pinyin}</td>
+                                            <td class="pr-4 text-sm text-slate-300">
+                                                {entry.translations.join("; ")}
+                                            </td>
+                                            <td>
+                                                <button
+                                                    class="px-2 py-1 border border-slate-300 rounded"
+                                                    onClick={() =>
+                                                        deleteUserEntry(entry.word)
+                                                    }
+                                                >
+                                                    Delete
+                                                </button>
+                                            </td>
+                                        </tr>
+                                    )}
+                                </For>
+                            </tbody>
+                        </table>
+                    </Show>
+                    <h2 class="text-lg leading-loose text-center font-semibold mt-4">
+                        Settings
+                    </h2>
+                    <div class="flex flex-row justify-center items-center space-x-4">
+                        <p>
+                            TensorRT engine cache: {engineCache()?.length ?? 0}{" "}
+                            files,{" "}
+                            {formatSize(
+                                (engineCache() ?? []).reduce(
+                                    (total, entry) => total + entry.size,
+                                    0
+                                )
+                            )}
+                        </p>
+                        <button
+                            class="px-2 py-1 border border-slate-300 rounded"
+                            onClick={clearEngineCache}
+                        >
+                            Clear
+                        </button>
+                    </div>
+                    <Show when={memoryUsage()}>
+                        {(usage) => (
+                            <p class="text-center text-sm text-slate-300 mt-2">
+                                Memory: {usage().history_entries} recent lookups (
+                                {formatSize(usage().history_bytes)}),{" "}
+                                {usage().ocr_blocks} lines (
+                                {formatSize(usage().ocr_bytes)}), frame{" "}
+                                {formatSize(usage().frame_bytes)}
+                            </p>
+                        )}
+                    </Show>
                 </Match>
             </Switch>
             <Toaster />
@@ -66,4 +446,14 @@ function App() {
     );
 }
 
+function formatDuration(millis: number): string {
+    const minutes = Math.floor(millis / 60000);
+    const hours = Math.floor(minutes / 60);
+    return hours > 0 ? `${hours}h ${minutes % 60}m` : `${minutes}m`;
+}
+
+function formatSize(bytes: number): string {
+    return `${(bytes / 1024 / 1024).toFixed(1)} MB`;
+}
+
 export default App;
@@ -0,0 +1,45 @@
+import { invoke } from "@tauri-apps/api";
+import { appWindow } from "@tauri-apps/api/window";
+import { createSignal, onMount } from "solid-js";
+
+function Correct() {
+    const [text, setText] = createSignal("");
+    const [always, setAlways] = createSignal(false);
+    let input: HTMLInputElement | undefined;
+    onMount(async () => {
+        setText((await invoke<string | null>("correction_text")) ?? "");
+        input?.select();
+    });
+    const submit = async (event: SubmitEvent) => {
+        event.preventDefault();
+        await invoke("correct_text", { text: text(), always: always() });
+        await appWindow.close();
+    };
+
+    return (
+        <form class="p-4" onSubmit={submit}>
+            <input
+                ref={input}
+                class="w-full px-2 py-1 text-lg border border-slate-300 rounded dark:bg-slate-800"
+                value={text()}
+                onInput={(event) => setText(event.currentTarget.value)}
+                onKeyDown={(event) => {
+                    if (event.key == "Escape") {
+                        appWindow.close();
+                    }
+                }}
+                autofocus
+            />
+            <label class="flex items-center mt-2 space-x-2 text-sm">
+                <input
+                    type="checkbox"
+                    checked={always()}
+                    onChange={(event) => setAlways(event.currentTarget.checked)}
+                />
+                <span>Always read the changed characters this way in this app</span>
+            </label>
+        </form>
+    );
+}
+
+export default Correct;
@@ -0,0 +1,9 @@
+/* @refresh reload */
+import { render } from "solid-js/web";
+
+import "../tooltip/index.css";
+import Correct from "./Correct";
+
+const root = document.getElementById("root");
+
+render(() => <Correct />, root!);
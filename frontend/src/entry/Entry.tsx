@@ -0,0 +1,168 @@
+import { invoke } from "@tauri-apps/api";
+import { listen } from "@tauri-apps/api/event";
+import { createResource, createSignal, For, Show } from "solid-js";
+import {
+    classForTone,
+    DictionaryEntry,
+    EntryDetails,
+    styleForTone,
+    ToneColor,
+} from "../payload";
+
+function Entry() {
+    const [details, setDetails] = createSignal<EntryDetails | null>(null);
+    invoke<EntryDetails | null>("entry_details").then(setDetails);
+    createResource(
+        async () =>
+            await listen("entry-changed", (event) => {
+                setDetails(event.payload as EntryDetails);
+            })
+    );
+
+    return (
+        <div class="p-4">
+            <Show when={details()}>
+                {(details) => (
+                    <>
+                        <For each={details().entries}>
+                            {(entry, _) => (
+                                <FullEntry
+                                    entry={entry}
+                                    colors={details().tone_colors}
+                                />
+                            )}
+                        </For>
+                        <Show when={details().idiom}>
+                            {(idiom) => (
+                                <>
+                                    <h2 class="text-lg leading-loose font-semibold mt-4">
+                                        Origin
+                                    </h2>
+                                    <Show when={idiom().literal}>
+                                        <p class="italic">
+                                            Literally "{idiom().literal}"
+                                        </p>
+                                    </Show>
+                                    <p class="font-light">{idiom().story}</p>
+                                    <Show when={idiom().source}>
+                                        <p class="text-sm text-slate-500 dark:text-slate-400">
+                                            From {idiom().source}
+                                        </p>
+                                    </Show>
+                                </>
+                            )}
+                        </Show>
+                        <Show when={details().characters.length > 0}>
+                            <h2 class="text-lg leading-loose font-semibold mt-4">
+                                Characters
+                            </h2>
+                            <div class="flex flex-col divide-y divide-slate-600">
+                                <For each={details().characters}>
+                                    {(entry, _) => (
+                                        <CharacterEntry
+                                            entry={entry}
+                                            colors={details().tone_colors}
+                                        />
+                                    )}
+                                </For>
+                            </div>
+                        </Show>
+                        <Show when={details().collocates.length > 0}>
+                            <h2 class="text-lg leading-loose font-semibold mt-4">
+                                Often seen with
+                            </h2>
+                            <div class="flex flex-row flex-wrap gap-2">
+                                <For each={details().collocates}>
+                                    {(collocate, _) => (
+                                        <span
+                                            class="text-lg"
+                                            title={`Seen together in ${collocate.count} sentences`}
+                                        >
+                                            {collocate.word}
+                                        </span>
+                                    )}
+                                </For>
+                            </div>
+                        </Show>
+                    </>
+                )}
+            </Show>
+        </div>
+    );
+}
+
+type EntryProps = { entry: DictionaryEntry; colors: (ToneColor | null)[] };
+
+function Pinyin(props: EntryProps) {
+    const color = (tone: number) => props.colors[tone] ?? null;
+    return (
+        <div class="flex flex-row space-x-1">
+            <For each={props.entry.pinyin}>
+                {(pinyin, _) => (
+                    <p
+                        class={classForTone(color(pinyin.tone))}
+                        style={styleForTone(color(pinyin.tone))}
+                    >
+                        {pinyin.syllable}
+                    </p>
+                )}
+            </For>
+        </div>
+    );
+}
+
+function FullEntry(props: EntryProps) {
+    return (
+        <div class="mb-4">
+            <div class="flex flex-row items-baseline space-x-2">
+                <p class="font-medium text-3xl">{props.entry.simplified}</p>
+                <Show when={props.entry.traditional != props.entry.simplified}>
+                    <p class="text-xl text-slate-500 dark:text-slate-400">
+                        {props.entry.traditional}
+                    </p>
+                </Show>
+            </div>
+            <Pinyin entry={props.entry} colors={props.colors} />
+            <Show when={props.entry.reading}>
+                <p>{props.entry.reading}</p>
+            </Show>
+            <div class="flex flex-row flex-wrap gap-1 mt-1">
+                <For each={props.entry.levels}>
+                    {(level, _) => (
+                        <span class="px-1 text-xs text-emerald-700 border border-emerald-600 rounded dark:text-emerald-300">
+                            {level}
+                        </span>
+                    )}
+                </For>
+                <For each={props.entry.tags}>
+                    {(tag, _) => (
+                        <span class="px-1 text-xs border border-slate-400 rounded">
+                            {tag}
+                        </span>
+                    )}
+                </For>
+            </div>
+            <ol class="list-decimal list-inside mt-1">
+                <For each={props.entry.translations}>
+                    {(translation, _) => (
+                        <li class="py-1 font-light">{translation}</li>
+                    )}
+                </For>
+            </ol>
+        </div>
+    );
+}
+
+function CharacterEntry(props: EntryProps) {
+    return (
+        <div class="flex flex-row items-baseline space-x-3 py-2">
+            <p class="font-medium text-xl">{props.entry.simplified}</p>
+            <Pinyin entry={props.entry} colors={props.colors} />
+            <p class="font-light truncate">
+                {props.entry.translations.join("; ")}
+            </p>
+        </div>
+    );
+}
+
+export default Entry;
@@ -0,0 +1,9 @@
+/* @refresh reload */
+import { render } from "solid-js/web";
+
+import "../tooltip/index.css";
+import Entry from "./Entry";
+
+const root = document.getElementById("root");
+
+render(() => <Entry />, root!);
@@ -0,0 +1,55 @@
+import { invoke } from "@tauri-apps/api";
+import { listen } from "@tauri-apps/api/event";
+import { createEffect, createResource, createSignal } from "solid-js";
+
+type Point = { x: number; y: number };
+type Rect = { min: Point; max: Point };
+
+type BlockInfo = {
+    text: string;
+    rect: Rect;
+    color: [number, number, number];
+};
+
+type OverlayBlocks = {
+    monitor: Rect;
+    blocks: BlockInfo[];
+};
+
+function Highlight() {
+    const [overlay, setOverlay] = createSignal<OverlayBlocks | null>(null);
+    createResource(async () => {
+        setOverlay(await invoke<OverlayBlocks | null>("overlay_blocks"));
+        return await listen("blocks-changed", (event) => {
+            setOverlay(event.payload as OverlayBlocks | null);
+        });
+    });
+    let canvas!: HTMLCanvasElement;
+    createEffect(() => {
+        // Block coordinates are in physical pixels, so the canvas is too
+        canvas.width = window.innerWidth * window.devicePixelRatio;
+        canvas.height = window.innerHeight * window.devicePixelRatio;
+        const context = canvas.getContext("2d")!;
+        context.clearRect(0, 0, canvas.width, canvas.height);
+        const current = overlay();
+        if (!current) {
+            return;
+        }
+        context.lineWidth = 2;
+        for (const block of current.blocks) {
+            const [r, g, b] = block.color;
+            const x = block.rect.min.x - current.monitor.min.x;
+            const y = block.rect.min.y - current.monitor.min.y;
+            const width = block.rect.max.x - block.rect.min.x;
+            const height = block.rect.max.y - block.rect.min.y;
+            context.fillStyle = `rgba(${r}, ${g}, ${b}, 0.2)`;
+            context.strokeStyle = `rgb(${r}, ${g}, ${b})`;
+            context.fillRect(x, y, width, height);
+            context.strokeRect(x, y, width, height);
+        }
+    });
+
+    return <canvas ref={canvas} class="w-screen h-screen pointer-events-none" />;
+}
+
+export default Highlight;
@@ -0,0 +1,9 @@
+/* @refresh reload */
+import { render } from "solid-js/web";
+
+import "../tooltip/index.css";
+import Highlight from "./Highlight";
+
+const root = document.getElementById("root");
+
+render(() => <Highlight />, root!);
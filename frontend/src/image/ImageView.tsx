@@ -0,0 +1,34 @@
+import { invoke } from "@tauri-apps/api";
+import { createResource, Show } from "solid-js";
+
+function ImageView() {
+    const [image] = createResource(() =>
+        invoke<string | null>("clipboard_image")
+    );
+    const close = () => invoke("close_image");
+
+    return (
+        <div
+            class="relative w-screen h-screen outline-none"
+            tabIndex={0}
+            ref={(div) => setTimeout(() => div.focus())}
+            onKeyDown={(event) => {
+                if (event.key == "Escape") {
+                    close();
+                }
+            }}
+        >
+            <Show when={image()}>
+                {(image) => (
+                    <img
+                        class="absolute inset-0 w-full h-full select-none"
+                        src={image()}
+                        draggable={false}
+                    />
+                )}
+            </Show>
+        </div>
+    );
+}
+
+export default ImageView;
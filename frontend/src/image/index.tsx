@@ -0,0 +1,9 @@
+/* @refresh reload */
+import { render } from "solid-js/web";
+
+import "../tooltip/index.css";
+import ImageView from "./ImageView";
+
+const root = document.getElementById("root");
+
+render(() => <ImageView />, root!);
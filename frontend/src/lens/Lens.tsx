@@ -0,0 +1,76 @@
+import { invoke } from "@tauri-apps/api";
+import { listen } from "@tauri-apps/api/event";
+import { createResource, createSignal, For, Show } from "solid-js";
+
+type Point = { x: number; y: number };
+type Rect = { min: Point; max: Point };
+
+type LensChar = { text: string; rect: Rect; hovered: boolean };
+
+type LensView = {
+    image: string;
+    width: number;
+    height: number;
+    cursor: [number, number];
+    chars: LensChar[];
+};
+
+function Lens() {
+    const [view, setView] = createSignal<LensView>();
+    createResource(async () => {
+        const current = await invoke<LensView | null>("lens_view");
+        if (current) {
+            setView(current);
+        }
+        return await listen("lens-changed", (event) => {
+            setView(event.payload as LensView);
+        });
+    });
+    // Sizes from the backend are in physical pixels
+    const px = (value: number) => `${value / window.devicePixelRatio}px`;
+
+    return (
+        <Show when={view()}>
+            {(view) => (
+                <div class="relative w-screen h-screen overflow-hidden border-2 border-sky-400">
+                    <img class="absolute inset-0 w-full h-full" src={view().image} />
+                    <For each={view().chars}>
+                        {(char) => (
+                            <>
+                                <div
+                                    class="absolute border"
+                                    classList={{
+                                        "border-sky-400": !char.hovered,
+                                        "border-amber-400 border-2": char.hovered,
+                                    }}
+                                    style={{
+                                        left: px(char.rect.min.x),
+                                        top: px(char.rect.min.y),
+                                        width: px(char.rect.max.x - char.rect.min.x),
+                                        height: px(char.rect.max.y - char.rect.min.y),
+                                    }}
+                                />
+                                <p
+                                    class="absolute -translate-x-1/2 -translate-y-full px-1 rounded text-base leading-tight bg-slate-900/80"
+                                    classList={{ "text-amber-300": char.hovered }}
+                                    style={{
+                                        left: px((char.rect.min.x + char.rect.max.x) / 2),
+                                        top: px(char.rect.min.y),
+                                    }}
+                                >
+                                    {char.text}
+                                </p>
+                            </>
+                        )}
+                    </For>
+                    <div
+                        class="absolute w-2 h-2 -translate-x-1/2 -translate-y-1/2 rounded-full bg-amber-400"
+                        style={{ left: px(view().cursor[0]), top: px(view().cursor[1]) }}
+                    />
+                </div>
+            )}
+        </Show>
+    );
+}
+
+export default Lens;
@@ -0,0 +1,9 @@
+/* @refresh reload */
+import { render } from "solid-js/web";
+
+import "../tooltip/index.css";
+import Lens from "./Lens";
+
+const root = document.getElementById("root");
+
+render(() => <Lens />, root!);
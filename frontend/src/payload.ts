@@ -0,0 +1,83 @@
+// Types of the payloads sent by the backend, see `live_ocrs::payload`
+
+export type Pinyin = { tone: number; syllable: string };
+
+export type ToneColor = { light: string; dark: string };
+
+export type ToneSyllable = {
+    syllable: string;
+    tone: number;
+    color: ToneColor | null;
+};
+
+export type Section =
+    | { type: "headword"; content: string }
+    | { type: "traditional"; content: string }
+    | { type: "pinyin"; content: ToneSyllable[] }
+    | { type: "reading"; content: string }
+    | { type: "inflection"; content: string[] }
+    | { type: "definitions"; content: string[] }
+    | { type: "grammar"; content: GrammarNote[] };
+
+export type GrammarNote = { pattern: string; note: string; link: string };
+
+export type TooltipEntry = {
+    fuzzy: boolean;
+    source: string;
+    known: boolean;
+    levels: string[];
+    matched_script: "simplified" | "traditional" | null;
+    label: string;
+    sections: Section[];
+};
+
+export type DictionaryEntry = {
+    simplified: string;
+    traditional: string;
+    pinyin: Pinyin[];
+    translations: string[];
+    fuzzy: boolean;
+    reading: string | null;
+    inflections: string[];
+    tags: string[];
+    frequency: number | null;
+    source: string;
+    known: boolean;
+    rank: number | null;
+    levels: string[];
+    matched_script: "simplified" | "traditional" | null;
+};
+
+export type EntryDetails = {
+    entries: DictionaryEntry[];
+    characters: DictionaryEntry[];
+    tone_colors: (ToneColor | null)[];
+    collocates: Collocate[];
+    idiom: IdiomStory | null;
+};
+
+export type IdiomStory = {
+    story: string;
+    literal: string | null;
+    source: string | null;
+};
+
+export type Collocate = {
+    word: string;
+    count: number;
+};
+
+// Colors come from the configured palette, so they're passed in as CSS variables
+export function classForTone(color: ToneColor | null): string {
+    return color
+        ? "text-[color:var(--tone-light)] dark:text-[color:var(--tone-dark)]"
+        : "text-black dark:text-white";
+}
+
+export function styleForTone(
+    color: ToneColor | null
+): Record<string, string> {
+    return color
+        ? { "--tone-light": color.light, "--tone-dark": color.dark }
+        : {};
+}
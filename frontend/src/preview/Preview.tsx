@@ -0,0 +1,86 @@
+import { invoke } from "@tauri-apps/api";
+import { createResource, For, Show } from "solid-js";
+
+type WindowInfo = { id: number; title: string; app: string };
+
+type CapturePreview = {
+    window: WindowInfo | null;
+    snapshot: string;
+};
+
+function Preview() {
+    const [preview] = createResource(() =>
+        invoke<CapturePreview | null>("capture_preview")
+    );
+    const [windows] = createResource(() =>
+        invoke<WindowInfo[]>("capture_windows")
+    );
+    const confirm = () => invoke("confirm_capture");
+    const cancel = () => invoke("cancel_capture");
+    const adjust = (value: string) =>
+        invoke("adjust_capture", {
+            window: value == "monitor" ? null : Number(value),
+        });
+
+    return (
+        <div
+            class="relative w-screen h-screen"
+            tabIndex={0}
+            ref={(div) => setTimeout(() => div.focus())}
+            onKeyDown={(event) => {
+                if (event.key == "Enter") {
+                    confirm();
+                } else if (event.key == "Escape") {
+                    cancel();
+                }
+            }}
+        >
+            <Show when={preview()}>
+                {(preview) => (
+                    <img
+                        class="absolute inset-0 w-full h-full"
+                        src={preview().snapshot}
+                    />
+                )}
+            </Show>
+            <div class="absolute inset-0 border-4 border-sky-400 bg-black/40" />
+            <div class="absolute inset-0 flex items-center justify-center">
+                <div class="flex flex-col p-4 space-y-3 rounded bg-slate-900/90 w-96">
+                    <p class="font-medium">
+                        Capture {preview()?.window?.title ?? "this monitor"}?
+                    </p>
+                    <select
+                        class="px-2 py-1 rounded bg-slate-800"
+                        value={preview()?.window?.id.toString() ?? "monitor"}
+                        onChange={(event) => adjust(event.currentTarget.value)}
+                    >
+                        <option value="monitor">Monitor under the cursor</option>
+                        <For each={windows()}>
+                            {(window) => (
+                                <option value={window.id.toString()}>
+                                    {window.title} ({window.app})
+                                </option>
+                            )}
+                        </For>
+                    </select>
+                    <div class="flex flex-row justify-end space-x-2">
+                        <button
+                            class="px-3 py-1 rounded bg-slate-700"
+                            onClick={cancel}
+                        >
+                            Cancel
+                        </button>
+                        <button
+                            class="px-3 py-1 rounded bg-sky-600"
+                            onClick={confirm}
+                        >
+                            Capture
+                        </button>
+                    </div>
+                </div>
+            </div>
+        </div>
+    );
+}
+
+export default Preview;
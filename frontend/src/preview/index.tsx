@@ -0,0 +1,9 @@
+/* @refresh reload */
+import { render } from "solid-js/web";
+
+import "../tooltip/index.css";
+import Preview from "./Preview";
+
+const root = document.getElementById("root");
+
+render(() => <Preview />, root!);
@@ -0,0 +1,74 @@
+import { invoke } from "@tauri-apps/api";
+import { listen } from "@tauri-apps/api/event";
+import { createResource, createSignal, For, Show } from "solid-js";
+import { classForTone, styleForTone, ToneSyllable } from "../payload";
+
+type Point = { x: number; y: number };
+type Rect = { min: Point; max: Point };
+
+type RubyWord = {
+    rect: Rect;
+    pinyin: ToneSyllable[];
+    reading: string | null;
+};
+
+type RubyOverlay = {
+    monitor: Rect;
+    words: RubyWord[];
+};
+
+function Ruby() {
+    const [overlay, setOverlay] = createSignal<RubyOverlay | null>(null);
+    createResource(async () => {
+        setOverlay(await invoke<RubyOverlay | null>("ruby_overlay"));
+        return await listen("ruby-changed", (event) => {
+            setOverlay(event.payload as RubyOverlay | null);
+        });
+    });
+    // Screen space positions from the backend are in physical pixels
+    const px = (value: number) => value / window.devicePixelRatio;
+
+    return (
+        <Show when={overlay()}>
+            {(overlay) => (
+                <div class="relative w-screen h-screen pointer-events-none">
+                    <For each={overlay().words}>
+                        {(word) => {
+                            const height = word.rect.max.y - word.rect.min.y;
+                            return (
+                                <div
+                                    class="absolute flex flex-row justify-center space-x-0.5 -translate-x-1/2 -translate-y-full px-0.5 rounded bg-white/80 dark:bg-slate-900/70 leading-none whitespace-nowrap"
+                                    style={{
+                                        left: `${px((word.rect.min.x + word.rect.max.x) / 2 - overlay().monitor.min.x)}px`,
+                                        top: `${px(word.rect.min.y - overlay().monitor.min.y)}px`,
+                                        "font-size": `${Math.max(px(height * 0.45), 10)}px`,
+                                    }}
+                                >
+                                    <Show
+                                        when={word.reading}
+                                        fallback={
+                                            <For each={word.pinyin}>
+                                                {(pinyin) => (
+                                                    <span
+                                                        class={classForTone(pinyin.color)}
+                                                        style={styleForTone(pinyin.color)}
+                                                    >
+                                                        {pinyin.syllable}
+                                                    </span>
+                                                )}
+                                            </For>
+                                        }
+                                    >
+                                        {(reading) => <span>{reading()}</span>}
+                                    </Show>
+                                </div>
+                            );
+                        }}
+                    </For>
+                </div>
+            )}
+        </Show>
+    );
+}
+
+export default Ruby;
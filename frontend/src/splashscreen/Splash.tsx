@@ -0,0 +1,67 @@
+import { invoke } from "@tauri-apps/api";
+import { listen } from "@tauri-apps/api/event";
+import { createResource, createSignal, Show } from "solid-js";
+
+type LoadProgress = {
+    dictionary: string;
+    stage: "opening_cache" | "parsing" | "indexing" | "writing_cache" | "done";
+    loaded: number;
+    total: number;
+};
+
+const STAGES = {
+    opening_cache: "Opening",
+    parsing: "Reading",
+    indexing: "Indexing",
+    writing_cache: "Caching",
+    done: "",
+};
+
+function Splash() {
+    const [progress, setProgress] = createSignal<LoadProgress | null>(null);
+    invoke<LoadProgress | null>("loading_progress").then((latest) => {
+        // An event may have arrived first
+        if (!progress()) {
+            setProgress(latest);
+        }
+    });
+    createResource(
+        async () =>
+            await listen("loading-progress", (event) => {
+                setProgress(event.payload as LoadProgress);
+            })
+    );
+    const percent = () => {
+        const current = progress();
+        return current ? (100 * current.loaded) / Math.max(current.total, 1) : 0;
+    };
+    const status = () => {
+        const current = progress();
+        if (!current) {
+            return "Loading...";
+        }
+        if (current.stage == "done") {
+            return "Starting the OCR engine...";
+        }
+        return `${STAGES[current.stage]} ${current.dictionary}...`;
+    };
+
+    return (
+        <div class="flex flex-col items-center justify-center h-screen px-8 space-y-3 bg-slate-800 text-white">
+            <p>{status()}</p>
+            <div class="w-full h-2 rounded bg-slate-600">
+                <div
+                    class="h-2 rounded bg-sky-500 transition-all"
+                    style={{ width: `${percent()}%` }}
+                />
+            </div>
+            <Show when={progress()?.stage == "parsing"}>
+                <p class="text-sm text-slate-400">
+                    This might take a while on first launch...
+                </p>
+            </Show>
+        </div>
+    );
+}
+
+export default Splash;
@@ -0,0 +1,9 @@
+/* @refresh reload */
+import { render } from "solid-js/web";
+
+import "../tooltip/index.css";
+import Splash from "./Splash";
+
+const root = document.getElementById("root");
+
+render(() => <Splash />, root!);
@@ -0,0 +1,52 @@
+import { listen } from "@tauri-apps/api/event";
+import { createResource, createSignal, For, Show } from "solid-js";
+import {
+    classForTone,
+    styleForTone,
+    ToneSyllable,
+    TooltipEntry,
+} from "../payload";
+
+function Strip() {
+    const [entry, setEntry] = createSignal<TooltipEntry>();
+    createResource(
+        async () =>
+            await listen("definitions-changed", (event) => {
+                setEntry((event.payload as TooltipEntry[])[0]);
+            })
+    );
+    const section = (type: string) =>
+        entry()?.sections.find((section) => section.type == type)?.content;
+
+    return (
+        <div class="flex flex-row items-center space-x-4 px-4 h-full border-slate-700 border-2 whitespace-nowrap overflow-hidden">
+            <Show
+                when={entry()}
+                fallback={
+                    <p class="text-slate-500 dark:text-slate-400">
+                        Hover a word to look it up
+                    </p>
+                }
+            >
+                <p class="font-medium text-lg">{section("headword") as string}</p>
+                <div class="flex flex-row space-x-1">
+                    <For each={(section("pinyin") as ToneSyllable[]) ?? []}>
+                        {(pinyin, _) => (
+                            <p
+                                class={classForTone(pinyin.color)}
+                                style={styleForTone(pinyin.color)}
+                            >
+                                {pinyin.syllable}
+                            </p>
+                        )}
+                    </For>
+                </div>
+                <p class="font-light truncate">
+                    {((section("definitions") as string[]) ?? [])[0]}
+                </p>
+            </Show>
+        </div>
+    );
+}
+
+export default Strip;
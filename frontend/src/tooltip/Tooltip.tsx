@@ -1,24 +1,81 @@
 import { createElementSize } from "@solid-primitives/resize-observer";
 import { invoke } from "@tauri-apps/api";
 import { listen } from "@tauri-apps/api/event";
-import { createEffect, createResource, createSignal, For } from "solid-js";
+import { open } from "@tauri-apps/api/shell";
+import { appWindow } from "@tauri-apps/api/window";
+import {
+    createEffect,
+    createResource,
+    createSignal,
+    For,
+    Match,
+    Show,
+    Switch,
+} from "solid-js";
+import {
+    classForTone,
+    GrammarNote,
+    Section,
+    styleForTone,
+    ToneSyllable,
+    TooltipEntry,
+} from "../payload";
 
-type Definition = {
-    simplified: string;
-    traditional: string;
-    pinyin: { tone: number; syllable: string }[];
-    translations: string[];
-};
+// Matches `TOOLTIP_PAGE_SIZE` in the backend
+const PAGE_SIZE = 10;
 
 function App() {
-    const [definitions, setDefinitions] = createSignal<Definition[]>([]);
+    const [definitions, setDefinitions] = createSignal<TooltipEntry[]>([]);
+    const [hasMore, setHasMore] = createSignal(false);
+    // Bumped on every new word, so pages requested for a previous word are dropped
+    let generation = 0;
+    let loading = false;
+    const loadMore = async () => {
+        if (loading || !hasMore()) {
+            return;
+        }
+        loading = true;
+        const requested = generation;
+        const page = await invoke<TooltipEntry[]>("more_definitions", {
+            offset: definitions().length,
+        });
+        loading = false;
+        if (requested == generation) {
+            setDefinitions([...definitions(), ...page]);
+            setHasMore(page.length == PAGE_SIZE);
+        }
+    };
+    invoke<string>("tooltip_effect").then((effect) => {
+        if (effect != "none") {
+            document.body.classList.add("translucent");
+        }
+    });
+    const pinned = appWindow.label.startsWith("pin-");
+    if (pinned) {
+        invoke<TooltipEntry[]>("pinned_definitions").then(setDefinitions);
+        document.addEventListener("keydown", (event) => {
+            if (event.key == "Escape") {
+                invoke("close_pin");
+            }
+        });
+    }
     createResource(
         async () =>
             await listen("definitions-changed", (event) => {
                 //console.log(event.payload);
-                setDefinitions(event.payload as Definition[]);
+                const entries = event.payload as TooltipEntry[];
+                generation++;
+                loading = false;
+                setDefinitions(entries);
+                setHasMore(entries.length == PAGE_SIZE);
             })
     );
+    // Only label sources when there is more than one to tell apart
+    const multipleSources = () =>
+        new Set(definitions().map((entry) => entry.source)).size > 1;
+    const startsSource = (index: number) =>
+        index == 0 ||
+        definitions()[index - 1].source != definitions()[index].source;
     const size = createElementSize(document.getElementById("root")!);
     createEffect(() => {
         console.log({ width: size.width, height: size.height });
@@ -29,53 +86,168 @@ function App() {
     });
 
     return (
-        <div class="px-4 pt-2 border-slate-700 border-2">
+        <div
+            class="px-4 pt-2 border-slate-700 border-2"
+            classList={{ "select-text cursor-text": pinned }}
+            role="region"
+            aria-label="Definitions"
+            aria-live="polite"
+        >
             <For each={definitions()}>
-                {(definition, _) => <Definition definition={definition} />}
+                {(entry, i) => (
+                    <>
+                        <Show when={multipleSources() && startsSource(i())}>
+                            <p
+                                class="mb-1 text-xs font-semibold uppercase text-slate-500 dark:text-slate-400"
+                                role="heading"
+                                aria-level="2"
+                            >
+                                {entry.source}
+                            </p>
+                        </Show>
+                        <Entry entry={entry} />
+                    </>
+                )}
             </For>
+            {/* Recreated after every page, so it's observed again if still in view */}
+            <Show when={hasMore() && definitions().length} keyed>
+                {(_) => (
+                    <div
+                        class="h-2"
+                        ref={(div) =>
+                            new IntersectionObserver((observed, observer) => {
+                                if (observed.some((it) => it.isIntersecting)) {
+                                    observer.disconnect();
+                                    loadMore();
+                                }
+                            }).observe(div)
+                        }
+                    />
+                )}
+            </Show>
         </div>
     );
 }
 
-function Definition(props: { definition: Definition }) {
+function Entry(props: { entry: TooltipEntry }) {
     return (
-        <div class="mb-2">
-            <p class="font-medium text-lg">{props.definition.simplified}</p>
-            <div class="flex flex-row space-x-1">
-                <For each={props.definition.pinyin}>
-                    {(pinyin, _) => (
-                        <p class={classForTone(pinyin.tone)}>
-                            {pinyin.syllable}
-                        </p>
-                    )}
-                </For>
-            </div>
-            <div class="flex flex-col divide-y">
-                <For each={props.definition.translations}>
-                    {(translation, _) => (
-                        <p class="py-1 font-light text-nowrap">{translation}</p>
-                    )}
+        <div
+            class="mb-2"
+            classList={{ "opacity-50": props.entry.known }}
+            role="article"
+        >
+            <p class="sr-only">{props.entry.label}</p>
+            <Show when={props.entry.fuzzy}>
+                <p
+                    class="text-xs text-amber-600 dark:text-amber-400"
+                    aria-hidden="true"
+                >
+                    Possible misread
+                </p>
+            </Show>
+            <Show when={props.entry.matched_script == "traditional"}>
+                <p
+                    class="text-xs text-slate-500 dark:text-slate-400"
+                    aria-hidden="true"
+                >
+                    Matched traditional
+                </p>
+            </Show>
+            <Show when={props.entry.levels.length > 0}>
+                <div class="flex flex-row gap-1" aria-hidden="true">
+                    <For each={props.entry.levels}>
+                        {(level, _) => (
+                            <span class="px-1 text-xs text-emerald-700 border border-emerald-600 rounded dark:text-emerald-300">
+                                {level}
+                            </span>
+                        )}
+                    </For>
+                </div>
+            </Show>
+            <div aria-hidden="true">
+                <For each={props.entry.sections}>
+                    {(section, _) => <EntrySection section={section} />}
                 </For>
             </div>
         </div>
     );
 }
 
-function classForTone(tone: number): string {
-    switch (tone) {
-        case 1:
-            return "text-[#268bd2] dark:text-[#6c71c4]";
-        case 2:
-            return "text-[#b58900] dark:text-[#cb4b16]";
-        case 3:
-            return "text-[#859900] dark:text-[#2aa198]";
-        case 4:
-            return "text-[#d33682] dark:text-[#dc322f]";
-        case 5:
-            return "text-[#586e75] dark:text-[#93a1a1]";
-        default:
-            return "text-black dark:text-white";
-    }
+function EntrySection(props: { section: Section }) {
+    return (
+        <Switch>
+            <Match when={props.section.type == "headword" && props.section}>
+                {(section) => (
+                    <p class="font-medium text-lg">{section().content}</p>
+                )}
+            </Match>
+            <Match when={props.section.type == "traditional" && props.section}>
+                {(section) => (
+                    <p class="font-light text-slate-500 dark:text-slate-400">
+                        {section().content}
+                    </p>
+                )}
+            </Match>
+            <Match when={props.section.type == "pinyin" && props.section}>
+                {(section) => (
+                    <div class="flex flex-row space-x-1">
+                        <For each={section().content as ToneSyllable[]}>
+                            {(pinyin, _) => (
+                                <p
+                                    class={classForTone(pinyin.color)}
+                                    style={styleForTone(pinyin.color)}
+                                >
+                                    {pinyin.syllable}
+                                </p>
+                            )}
+                        </For>
+                    </div>
+                )}
+            </Match>
+            <Match when={props.section.type == "reading" && props.section}>
+                {(section) => <p>{section().content as string}</p>}
+            </Match>
+            <Match when={props.section.type == "inflection" && props.section}>
+                {(section) => (
+                    <p class="text-xs text-slate-500 dark:text-slate-400">
+                        {(section().content as string[]).join(" « ")}
+                    </p>
+                )}
+            </Match>
+            <Match when={props.section.type == "definitions" && props.section}>
+                {(section) => (
+                    <div class="flex flex-col divide-y">
+                        <For each={section().content as string[]}>
+                            {(translation, _) => (
+                                <p class="py-1 font-light text-nowrap">
+                                    {translation}
+                                </p>
+                            )}
+                        </For>
+                    </div>
+                )}
+            </Match>
+            <Match when={props.section.type == "grammar" && props.section}>
+                {(section) => (
+                    <div class="flex flex-col pt-1 border-t text-sm">
+                        <For each={section().content as GrammarNote[]}>
+                            {(note, _) => (
+                                <p class="py-1 font-light">
+                                    <a
+                                        class="font-medium underline cursor-pointer"
+                                        onClick={() => open(note.link)}
+                                    >
+                                        {note.pattern}
+                                    </a>{" "}
+                                    {note.note}
+                                </p>
+                            )}
+                        </For>
+                    </div>
+                )}
+            </Match>
+        </Switch>
+    );
 }
 
 export default App;
@@ -0,0 +1,108 @@
+import { invoke } from "@tauri-apps/api";
+import { appWindow } from "@tauri-apps/api/window";
+import { createSignal, onMount, Show } from "solid-js";
+
+export type UserEntry = {
+    word: string;
+    traditional: string | null;
+    pinyin: string;
+    translations: string[];
+};
+
+type UserEntryDraft = {
+    entry: UserEntry;
+    context: string;
+};
+
+function UserEntryForm() {
+    const [context, setContext] = createSignal("");
+    const [word, setWord] = createSignal("");
+    const [traditional, setTraditional] = createSignal("");
+    const [pinyin, setPinyin] = createSignal("");
+    const [translations, setTranslations] = createSignal("");
+    const [error, setError] = createSignal<string | null>(null);
+    let input: HTMLInputElement | undefined;
+    onMount(async () => {
+        const draft = await invoke<UserEntryDraft | null>("pending_user_entry");
+        if (draft) {
+            setContext(draft.context);
+            setWord(draft.entry.word);
+            setTraditional(draft.entry.traditional ?? "");
+            setPinyin(draft.entry.pinyin);
+            setTranslations(draft.entry.translations.join("\n"));
+        }
+        input?.select();
+    });
+    const submit = async (event: SubmitEvent) => {
+        event.preventDefault();
+        const entry: UserEntry = {
+            word: word().trim(),
+            traditional: traditional().trim() || null,
+            pinyin: pinyin().trim(),
+            translations: translations()
+                .split("\n")
+                .map((it) => it.trim())
+                .filter((it) => it),
+        };
+        try {
+            await invoke("save_user_entry", { entry });
+            await appWindow.close();
+        } catch (err) {
+            setError(err as string);
+        }
+    };
+    const inputClass =
+        "w-full px-2 py-1 border border-slate-300 rounded dark:bg-slate-800";
+
+    return (
+        <form
+            class="p-4 space-y-2"
+            onSubmit={submit}
+            onKeyDown={(event) => {
+                if (event.key == "Escape") {
+                    appWindow.close();
+                }
+            }}
+        >
+            <p class="text-sm text-slate-500 dark:text-slate-400">{context()}</p>
+            <input
+                ref={input}
+                class={`${inputClass} text-lg`}
+                placeholder="Word"
+                value={word()}
+                onInput={(event) => setWord(event.currentTarget.value)}
+                autofocus
+            />
+            <input
+                class={inputClass}
+                placeholder="Traditional (if different)"
+                value={traditional()}
+                onInput={(event) => setTraditional(event.currentTarget.value)}
+            />
+            <input
+                class={inputClass}
+                placeholder="Pinyin, e.g. xue2 sheng5"
+                value={pinyin()}
+                onInput={(event) => setPinyin(event.currentTarget.value)}
+            />
+            <textarea
+                class={inputClass}
+                placeholder="Definitions, one per line"
+                rows={3}
+                value={translations()}
+                onInput={(event) => setTranslations(event.currentTarget.value)}
+            />
+            <Show when={error()}>
+                <p class="text-sm text-red-600">{error()}</p>
+            </Show>
+            <button
+                type="submit"
+                class="px-2 py-1 border border-slate-300 rounded"
+            >
+                Save
+            </button>
+        </form>
+    );
+}
+
+export default UserEntryForm;
@@ -0,0 +1,9 @@
+/* @refresh reload */
+import { render } from "solid-js/web";
+
+import "../tooltip/index.css";
+import UserEntryForm from "./UserEntryForm";
+
+const root = document.getElementById("root");
+
+render(() => <UserEntryForm />, root!);
@@ -37,7 +37,17 @@ export default defineConfig({
         rollupOptions: {
             input: {
                 main: resolve(__dirname, "index.html"),
+                splashscreen: resolve(__dirname, "splashscreen.html"),
                 tooltip: resolve(__dirname, "tooltip.html"),
+                strip: resolve(__dirname, "strip.html"),
+                entry: resolve(__dirname, "entry.html"),
+                correct: resolve(__dirname, "correct.html"),
+                "user-entry": resolve(__dirname, "user-entry.html"),
+                preview: resolve(__dirname, "preview.html"),
+                lens: resolve(__dirname, "lens.html"),
+                image: resolve(__dirname, "image.html"),
+                ruby: resolve(__dirname, "ruby.html"),
+                highlight: resolve(__dirname, "highlight.html"),
             },
         },
     },
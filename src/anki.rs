@@ -0,0 +1,184 @@
+//! Export of the hovered word to Anki through the [AnkiConnect](https://foosoft.net/projects/anki-connect/)
+//! add-on, which has to be installed and Anki running.
+
+use std::{collections::HashMap, io::Cursor, time::Duration};
+
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use image::{imageops, ImageFormat, RgbaImage};
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::{history, hooks::SaveEvent, hover::find_block, speech, Error, LiveOcr, Result};
+
+/// How long to wait for AnkiConnect before giving up
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Where and how notes are created. Field names have to match the note type. Several kinds of
+/// content can go into the same field, they're separated by line breaks.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct AnkiConfig {
+    /// AnkiConnect endpoint
+    pub url: String,
+    pub deck: String,
+    pub note_type: String,
+    pub word_field: String,
+    pub reading_field: String,
+    pub definitions_field: String,
+    /// Field for the full line the word was found in
+    pub sentence_field: String,
+    /// Field for a screenshot of the line. `None` skips the screenshot.
+    pub screenshot_field: Option<String>,
+    /// Field for the word read by text to speech. `None` skips the audio.
+    pub audio_field: Option<String>,
+    pub tags: Vec<String>,
+}
+
+impl Default for AnkiConfig {
+    fn default() -> Self {
+        Self {
+            url: "http://127.0.0.1:8765".to_string(),
+            deck: "Default".to_string(),
+            note_type: "Basic".to_string(),
+            word_field: "Front".to_string(),
+            reading_field: "Back".to_string(),
+            definitions_field: "Back".to_string(),
+            sentence_field: "Back".to_string(),
+            screenshot_field: Some("Back".to_string()),
+            audio_field: Some("Front".to_string()),
+            tags: vec!["live-ocrs".to_string()],
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct Response {
+    result: Value,
+    error: Option<String>,
+}
+
+/// Adds a note for the hovered word, its first dictionary entry, the line it's in and a
+/// recording of the word. Returns the exported word, or `None` if nothing is hovered or Anki
+/// export isn't configured.
+pub fn export_hovered(state: &RwLock<LiveOcr>) -> Result<Option<String>> {
+    // Don't hold the lock while capturing audio and waiting for Anki, hovering needs it
+    let (config, word, entry, headword, line, hooks) = {
+        let state = state.read();
+        let (Some(config), Some(word), Some(entry)) = (
+            &state.config.anki,
+            state.hover.word(),
+            state.definitions.definitions.first(),
+        ) else {
+            return Ok(None);
+        };
+        let line = config
+            .screenshot_field
+            .as_ref()
+            .and_then(|_| line_image(&state));
+        (
+            config.clone(),
+            word.text.clone(),
+            entry.clone(),
+            entry.headword(state.config.headword_script),
+            line,
+            state.config.hooks.clone(),
+        )
+    };
+
+    let mut fields = HashMap::<String, Vec<String>>::new();
+    let mut add = |field: &str, content: String| {
+        if !content.is_empty() {
+            fields.entry(field.to_string()).or_default().push(content);
+        }
+    };
+    add(&config.word_field, headword);
+    add(&config.reading_field, entry.pronunciation());
+    add(&config.definitions_field, entry.translations.join("; "));
+    add(&config.sentence_field, word);
+    let fields = fields
+        .into_iter()
+        .map(|(field, content)| (field, content.join("<br>")))
+        .collect::<HashMap<_, _>>();
+
+    let mut note = json!({
+        "deckName": config.deck,
+        "modelName": config.note_type,
+        "fields": fields,
+        "tags": config.tags,
+    });
+    // Anki keeps one file per name, so earlier cards for the same word keep their media
+    let media_name = format!("live-ocrs-{}-{}", entry.simplified, history::now());
+    let screenshot = config
+        .screenshot_field
+        .as_ref()
+        .zip(line)
+        .and_then(|(field, line)| Some((field, encode_png(&line)?)));
+    if let Some((field, png)) = screenshot {
+        note["picture"] = json!([{
+            "data": STANDARD.encode(png),
+            "filename": format!("{media_name}.png"),
+            "fields": [field],
+        }]);
+    }
+
+    if let Some(field) = &config.audio_field {
+        match speech::synthesize(&entry.simplified) {
+            Ok(wav) => {
+                note["audio"] = json!([{
+                    "data": STANDARD.encode(wav),
+                    "filename": format!("{media_name}.wav"),
+                    "fields": [field],
+                }]);
+            }
+            Err(err) => log::warn!("Failed to generate audio for {}: {err}", entry.simplified),
+        }
+    }
+
+    request(&config.url, "addNote", json!({ "note": note }))?;
+    log::info!("Added {} to Anki", entry.simplified);
+    hooks.word_saved(&entry, SaveEvent::Anki);
+    Ok(Some(entry.simplified))
+}
+
+/// Crop of the hovered line from the frame OCR ran on. A fresh capture could include the
+/// tooltip.
+fn line_image(state: &LiveOcr) -> Option<RgbaImage> {
+    let word = state.hover.word()?;
+    let index = find_block(&state.definitions.ocr_strings, word)?;
+    let bounds = state.definitions.ocr_strings[index].bounds;
+    let frame = state.last_frame.as_ref()?;
+    let (frame, origin) = (&frame.image, frame.origin);
+
+    let margin = bounds.height() / 2.0;
+    let min_x = (bounds.min().x - origin.0 as f32 - margin).max(0.0) as u32;
+    let min_y = (bounds.min().y - origin.1 as f32 - margin).max(0.0) as u32;
+    let max_x = ((bounds.max().x - origin.0 as f32 + margin) as u32).min(frame.width());
+    let max_y = ((bounds.max().y - origin.1 as f32 + margin) as u32).min(frame.height());
+    if max_x <= min_x || max_y <= min_y {
+        return None;
+    }
+    Some(imageops::crop_imm(frame, min_x, min_y, max_x - min_x, max_y - min_y).to_image())
+}
+
+fn encode_png(image: &RgbaImage) -> Option<Vec<u8>> {
+    let mut png = Vec::new();
+    if let Err(err) = image.write_to(&mut Cursor::new(&mut png), ImageFormat::Png) {
+        log::warn!("Failed to encode screenshot: {err}");
+        return None;
+    }
+    Some(png)
+}
+
+fn request(url: &str, action: &str, params: Value) -> Result<Value> {
+    let body = json!({ "action": action, "version": 6, "params": params });
+    let response: Response = ureq::post(url)
+        .timeout(TIMEOUT)
+        .send_json(body)
+        .map_err(|err| Error::Anki(err.to_string()))?
+        .into_json()?;
+    match response.error {
+        Some(err) => Err(Error::Anki(err)),
+        None => Ok(response.result),
+    }
+}
@@ -0,0 +1,305 @@
+//! Command line access to the OCR pipeline, without the GUI.
+
+use std::{
+    env,
+    error::Error,
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+    sync::Arc,
+};
+
+use geo::Rect;
+use live_ocrs::{
+    capture::{CaptureOptions, CaptureState, ImageSource},
+    character::CharBox,
+    dict::{self, DictionaryEntry, DictionaryGroup},
+    segment::segment,
+};
+use rapidocr::RapidOCRBuilder;
+use serde::Serialize;
+use tracing_subscriber::EnvFilter;
+
+const USAGE: &str = "Usage:
+    live-ocrs-cli video <FILE> [--interval <SECONDS>] [--format srt|json] [--models <DIR>]
+    live-ocrs-cli annotate <IMAGE> [--dict <FILE>] [--models <DIR>]
+
+Commands:
+    video       Extract on-screen text from a video file with timestamps. Requires ffmpeg on
+                PATH.
+    annotate    Recognize the text in an image and look up every word. Prints JSON.
+
+Options:
+    --interval <SECONDS>    Time between sampled frames [default: 1]
+    --format <FORMAT>       Output format, srt or json [default: srt]
+    --dict <FILE>           CC-CEDICT dictionary, JSON or .u8 text [default: data/cedict.json]
+    --models <DIR>          Directory containing the OCR models [default: models]";
+
+#[derive(Clone, Copy, PartialEq)]
+enum Format {
+    Srt,
+    Json,
+}
+
+struct VideoArgs {
+    input: PathBuf,
+    interval: f32,
+    format: Format,
+    models: PathBuf,
+}
+
+struct AnnotateArgs {
+    input: PathBuf,
+    dict: PathBuf,
+    models: PathBuf,
+}
+
+/// A recognized line with the words it's made of
+#[derive(Serialize)]
+struct AnnotatedBlock {
+    text: String,
+    bounds: Rect<f32>,
+    chars: Vec<CharBox>,
+    confidence: f32,
+    words: Vec<AnnotatedWord>,
+}
+
+#[derive(Serialize)]
+struct AnnotatedWord {
+    text: String,
+    /// Character index of the word within the line
+    start: usize,
+    definitions: Vec<DictionaryEntry>,
+}
+
+/// A span of time during which the same text was on screen
+#[derive(Serialize)]
+struct Cue {
+    start: f32,
+    end: f32,
+    lines: Vec<String>,
+}
+
+fn main() {
+    tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::from_default_env())
+        .with_writer(std::io::stderr)
+        .init();
+
+    let mut args = env::args().skip(1);
+    let result = match args.next().as_deref() {
+        Some("video") => parse_video_args(args).and_then(|args| video(&args)),
+        Some("annotate") => parse_annotate_args(args).and_then(|args| annotate(&args)),
+        _ => Err(USAGE.into()),
+    };
+    if let Err(err) = result {
+        eprintln!("{err}");
+        std::process::exit(1);
+    }
+}
+
+fn parse_video_args(mut args: impl Iterator<Item = String>) -> Result<VideoArgs, Box<dyn Error>> {
+    let mut input = None;
+    let mut interval = 1.0;
+    let mut format = Format::Srt;
+    let mut models = PathBuf::from("models");
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--interval" => interval = args.next().ok_or(USAGE)?.parse()?,
+            "--format" => {
+                format = match args.next().as_deref() {
+                    Some("srt") => Format::Srt,
+                    Some("json") => Format::Json,
+                    _ => return Err(USAGE.into()),
+                }
+            }
+            "--models" => models = args.next().ok_or(USAGE)?.into(),
+            _ if input.is_none() => input = Some(PathBuf::from(arg)),
+            _ => return Err(USAGE.into()),
+        }
+    }
+    Ok(VideoArgs {
+        input: input.ok_or(USAGE)?,
+        interval,
+        format,
+        models,
+    })
+}
+
+fn parse_annotate_args(
+    mut args: impl Iterator<Item = String>,
+) -> Result<AnnotateArgs, Box<dyn Error>> {
+    let mut input = None;
+    let mut dict = PathBuf::from("data/cedict.json");
+    let mut models = PathBuf::from("models");
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--dict" => dict = args.next().ok_or(USAGE)?.into(),
+            "--models" => models = args.next().ok_or(USAGE)?.into(),
+            _ if input.is_none() => input = Some(PathBuf::from(arg)),
+            _ => return Err(USAGE.into()),
+        }
+    }
+    Ok(AnnotateArgs {
+        input: input.ok_or(USAGE)?,
+        dict,
+        models,
+    })
+}
+
+fn load_ocr(models: &Path) -> Result<Arc<CaptureState>, Box<dyn Error>> {
+    let ocr = RapidOCRBuilder::new()
+        .max_side_len(2048)
+        .det_model(models.join("ch_PP-OCRv4_det_infer/ch_PP-OCRv4_det_infer.onnx"))
+        .rec_model(
+            models.join("ch_PP-OCRv4_rec_infer/ch_PP-OCRv4_rec_infer.onnx"),
+            models.join("ppocr_keys_v1.txt"),
+        )
+        .build()?;
+    Ok(Arc::new(CaptureState { ocr }))
+}
+
+fn video(args: &VideoArgs) -> Result<(), Box<dyn Error>> {
+    let capture = load_ocr(&args.models)?;
+    let frames_dir = env::temp_dir().join(format!("live-ocrs-frames-{}", std::process::id()));
+    fs::create_dir_all(&frames_dir)?;
+    let result = extract_frames(&args.input, args.interval, &frames_dir)
+        .and_then(|frames| ocr_frames(&capture, &frames, args.interval));
+    fs::remove_dir_all(&frames_dir)?;
+    let cues = result?;
+
+    match args.format {
+        Format::Srt => print!("{}", to_srt(&cues)),
+        Format::Json => println!("{}", serde_json::to_string_pretty(&cues)?),
+    }
+    Ok(())
+}
+
+fn annotate(args: &AnnotateArgs) -> Result<(), Box<dyn Error>> {
+    let capture = load_ocr(&args.models)?;
+    let dict = dict::load(&args.dict, env::temp_dir().join("live-ocrs-cli-dict"))?;
+    let source = ImageSource {
+        image: image::open(&args.input)?.to_rgba8(),
+        origin: (0, 0),
+    };
+    let blocks = capture
+        .capture(&source, &CaptureOptions::default())?
+        .into_iter()
+        .map(|block| AnnotatedBlock {
+            words: annotate_words(&dict, &block.text),
+            text: block.text,
+            bounds: block.bounds,
+            chars: block.chars,
+            confidence: block.confidence,
+        })
+        .collect::<Vec<_>>();
+    println!("{}", serde_json::to_string_pretty(&blocks)?);
+    Ok(())
+}
+
+/// Splits a line into words and looks each of them up. Characters that aren't part of any
+/// known word are left out.
+fn annotate_words(dict: &DictionaryGroup, text: &str) -> Vec<AnnotatedWord> {
+    let chars = text.chars().collect::<Vec<_>>();
+    segment(dict, text)
+        .into_iter()
+        .filter_map(|span| {
+            let word = chars[span.clone()].iter().collect::<String>();
+            let definitions = dict
+                .matches(&word)
+                .into_iter()
+                .filter(|entry| entry.simplified == word || entry.traditional == word)
+                .collect::<Vec<_>>();
+            (!definitions.is_empty()).then_some(AnnotatedWord {
+                text: word,
+                start: span.start,
+                definitions,
+            })
+        })
+        .collect()
+}
+
+/// Samples one frame every `interval` seconds into `dir`, returning the frame paths in order.
+fn extract_frames(input: &Path, interval: f32, dir: &Path) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let status = Command::new("ffmpeg")
+        .arg("-loglevel")
+        .arg("error")
+        .arg("-i")
+        .arg(input)
+        .arg("-vf")
+        .arg(format!("fps=1/{interval}"))
+        .arg(dir.join("frame_%06d.png"))
+        .status()?;
+    if !status.success() {
+        return Err(format!("ffmpeg failed with {status}").into());
+    }
+
+    let mut frames = fs::read_dir(dir)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<Vec<_>, _>>()?;
+    frames.sort();
+    Ok(frames)
+}
+
+fn ocr_frames(
+    capture: &Arc<CaptureState>,
+    frames: &[PathBuf],
+    interval: f32,
+) -> Result<Vec<Cue>, Box<dyn Error>> {
+    let mut cues: Vec<Cue> = Vec::new();
+    // Lines of the previous frame, a cue only continues if they didn't change in between
+    let mut last_lines = Vec::new();
+    for (i, frame) in frames.iter().enumerate() {
+        log::info!("OCR frame {}/{}", i + 1, frames.len());
+        let source = ImageSource {
+            image: image::open(frame)?.to_rgba8(),
+            origin: (0, 0),
+        };
+        let lines = capture
+            .clone()
+            .capture(&source, &CaptureOptions::default())?
+            .into_iter()
+            .map(|block| block.text)
+            .collect::<Vec<_>>();
+
+        let start = i as f32 * interval;
+        let end = start + interval;
+        match cues.last_mut() {
+            Some(cue) if lines == last_lines && cue.lines == lines => cue.end = end,
+            _ if lines.is_empty() => {}
+            _ => cues.push(Cue {
+                start,
+                end,
+                lines: lines.clone(),
+            }),
+        }
+        last_lines = lines;
+    }
+    Ok(cues)
+}
+
+fn to_srt(cues: &[Cue]) -> String {
+    cues.iter()
+        .enumerate()
+        .map(|(i, cue)| {
+            format!(
+                "{}\n{} --> {}\n{}\n\n",
+                i + 1,
+                srt_timestamp(cue.start),
+                srt_timestamp(cue.end),
+                cue.lines.join("\n")
+            )
+        })
+        .collect()
+}
+
+fn srt_timestamp(seconds: f32) -> String {
+    let millis = (seconds * 1000.0).round() as u64;
+    format!(
+        "{:02}:{:02}:{:02},{:03}",
+        millis / 3_600_000,
+        millis / 60_000 % 60,
+        millis / 1000 % 60,
+        millis % 1000
+    )
+}
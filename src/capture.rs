@@ -1,23 +1,180 @@
-use std::sync::Arc;
+use std::{io::Cursor, sync::Arc};
 
-use geo::{BoundingRect, Rect};
-use image::DynamicImage;
+use arboard::Clipboard;
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use geo::{coord, BoundingRect, Coord, Rect, Translate as _};
+use image::{
+    imageops::{self, FilterType},
+    DynamicImage, ImageFormat, RgbaImage,
+};
 use rapidocr::{DetectionOptions, RapidOCR};
-use xcap::Monitor;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use xcap::{Monitor, Window};
 
-use crate::character::detect_char_boxes;
+use crate::{
+    character::{detect_char_boxes, Block, CharBox},
+    color::contrasting_color,
+    config::{Config, SmallText, SmallTextAction},
+    environment::cursor_position,
+};
+
+/// Scale factor for re-running OCR on small text
+const UPSCALE_FACTOR: u32 = 3;
+/// Share of the darkest and brightest pixels ignored when measuring a frame's range, so a few
+/// outliers like a cursor or a pure black border don't hide a washed-out frame
+const RANGE_CLIP: f32 = 0.005;
+/// Frames whose luminance already spans at least this range are left as they are
+const FULL_RANGE: u8 = 224;
+/// Width capture preview snapshots are downscaled to
+const PREVIEW_WIDTH: u32 = 960;
+/// Largest share of the monitor an image is shown at, larger ones are scaled down
+const IMAGE_MAX_SCALE: f32 = 0.9;
+
+#[derive(Debug, Error)]
+pub enum CaptureError {
+    #[error("Failed to capture screen: {0}")]
+    Capture(String),
+    #[error(
+        "Captured a blank frame. The window under the cursor is probably protected or hardware \
+         accelerated (e.g. a video player). Try disabling hardware acceleration in that \
+         application."
+    )]
+    BlankFrame,
+    #[error("The clipboard doesn't contain an image")]
+    NoClipboardImage,
+    #[error("The cursor position can't be read in this session")]
+    NoCursor,
+    #[error("No open window matches {0:?}")]
+    WindowNotFound(String),
+    #[error("Text recognition failed: {0}")]
+    Ocr(String),
+}
+
+/// Per-capture overrides for the OCR detection options.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct CaptureOptions {
+    /// What to capture instead of the configured `target_window`
+    pub target: Option<CaptureTarget>,
+    pub max_side_len: Option<u32>,
+    /// Minimum score of a detected text box, lower finds fainter text
+    pub box_threshold: Option<f32>,
+    /// How far detected text boxes are expanded, raise it if characters at the edges get cut off
+    pub unclip_ratio: Option<f32>,
+    /// Lines recognized with a lower confidence are dropped
+    pub min_confidence: Option<f32>,
+}
+
+/// What a single capture runs OCR on.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CaptureTarget {
+    /// The monitor under the cursor
+    Monitor,
+    /// The window with this id or title, see [`find_window`]
+    Window(String),
+}
+
+impl CaptureOptions {
+    /// Fills in options that aren't overridden for this capture from the config.
+    pub fn with_config(&self, config: &Config) -> Self {
+        let target = match &config.target_window {
+            Some(window) => CaptureTarget::Window(window.clone()),
+            None => CaptureTarget::Monitor,
+        };
+        Self {
+            target: self.target.clone().or(Some(target)),
+            min_confidence: self.min_confidence.or(Some(config.min_confidence)),
+            ..self.clone()
+        }
+    }
+
+    pub fn detection_options(&self) -> DetectionOptions {
+        let mut options = DetectionOptions {
+            max_side_len: 2048,
+            ..Default::default()
+        };
+        if let Some(max_side_len) = self.max_side_len {
+            options.max_side_len = max_side_len as _;
+        }
+        if let Some(box_threshold) = self.box_threshold {
+            options.box_threshold = box_threshold;
+        }
+        if let Some(unclip_ratio) = self.unclip_ratio {
+            options.unclip_ratio = unclip_ratio;
+        }
+        options
+    }
+}
+
+/// Something that provides frames to run OCR on, e.g. a monitor or a recorded frame.
+pub trait FrameSource {
+    fn capture_frame(&self) -> Result<RgbaImage, CaptureError>;
+    /// Screen space position of the frame's top left corner. Character boxes are translated by
+    /// this so they line up with the cursor.
+    fn origin(&self) -> (i32, i32);
+}
+
+impl FrameSource for Monitor {
+    fn capture_frame(&self) -> Result<RgbaImage, CaptureError> {
+        capture_monitor(self)
+    }
+
+    fn origin(&self) -> (i32, i32) {
+        (self.x(), self.y())
+    }
+}
+
+/// Captures a single application window, even if it's partially covered by other windows.
+impl FrameSource for Window {
+    fn capture_frame(&self) -> Result<RgbaImage, CaptureError> {
+        let image = self
+            .capture_image()
+            .map_err(|err| CaptureError::Capture(err.to_string()))?;
+        if is_solid(&image) {
+            Err(CaptureError::BlankFrame)
+        } else {
+            Ok(image)
+        }
+    }
+
+    fn origin(&self) -> (i32, i32) {
+        (self.x(), self.y())
+    }
+}
+
+/// A still frame that was captured elsewhere, e.g. a screenshot or a frame of a recording.
+pub struct ImageSource {
+    pub image: RgbaImage,
+    pub origin: (i32, i32),
+}
+
+impl FrameSource for ImageSource {
+    fn capture_frame(&self) -> Result<RgbaImage, CaptureError> {
+        Ok(self.image.clone())
+    }
+
+    fn origin(&self) -> (i32, i32) {
+        self.origin
+    }
+}
 
 pub struct CaptureState {
     pub ocr: RapidOCR,
 }
 
 impl CaptureState {
-    pub fn capture(self: Arc<Self>, monitor: &Monitor) -> Vec<(String, Vec<(usize, Rect<f32>)>)> {
-        let image = monitor.capture_image().unwrap();
+    pub fn capture(
+        self: Arc<Self>,
+        source: &dyn FrameSource,
+        options: &CaptureOptions,
+    ) -> Result<Vec<Block>, CaptureError> {
+        let image = source.capture_frame()?;
         #[cfg(feature = "debug")]
         image.save("screen.png").unwrap();
         let image = image.into();
-        let boxes = do_ocr(&self.ocr, &image, monitor);
+        let boxes = do_ocr(&self.ocr, &image, source.origin(), options)?;
         #[cfg(feature = "debug")]
         {
             use crate::draw_outline_geo;
@@ -25,26 +182,384 @@ impl CaptureState {
 
             image.to_luma8().save("screen_gray.png").unwrap();
             let mut image = image.to_rgb8();
-            for (_, contour) in boxes.iter().flat_map(|it| &it.1) {
-                draw_outline_geo(&mut image, *contour, Rgb([255, 0, 0]))
+            for block in &boxes {
+                for contour in &block.chars {
+                    draw_outline_geo(&mut image, contour.rect, Rgb(block.color))
+                }
             }
             image.save("boundaries.png").unwrap();
         }
 
-        boxes
+        Ok(boxes)
+    }
+
+    /// Applies the small text rule to blocks captured from `frame`, dropping lines below the
+    /// minimum height or recognizing them again from an upscaled crop.
+    pub fn process_small_text(
+        &self,
+        frame: &ImageSource,
+        blocks: Vec<Block>,
+        small_text: Option<SmallText>,
+        options: &CaptureOptions,
+    ) -> Vec<Block> {
+        let Some(small_text) = small_text else {
+            return blocks;
+        };
+        blocks
+            .into_iter()
+            .flat_map(|block| {
+                let bounds = block.bounds;
+                if bounds.height() >= small_text.min_height {
+                    return vec![block];
+                }
+                log::info!("Small text {} ({}px)", block.text, bounds.height());
+                match small_text.action {
+                    SmallTextAction::Drop => Vec::new(),
+                    SmallTextAction::Upscale => self.ocr_upscaled(frame, bounds, options),
+                }
+            })
+            .collect()
+    }
+
+    fn ocr_upscaled(
+        &self,
+        frame: &ImageSource,
+        bounds: Rect<f32>,
+        options: &CaptureOptions,
+    ) -> Vec<Block> {
+        // Leave some background around the line, detection misses text touching the border
+        let margin = bounds.height() / 2.0;
+        let (origin_x, origin_y) = (frame.origin.0 as f32, frame.origin.1 as f32);
+        let min_x = (bounds.min().x - origin_x - margin).max(0.0) as u32;
+        let min_y = (bounds.min().y - origin_y - margin).max(0.0) as u32;
+        let max_x = ((bounds.max().x - origin_x + margin) as u32).min(frame.image.width());
+        let max_y = ((bounds.max().y - origin_y + margin) as u32).min(frame.image.height());
+        if max_x <= min_x || max_y <= min_y {
+            return Vec::new();
+        }
+        let crop = imageops::crop_imm(&frame.image, min_x, min_y, max_x - min_x, max_y - min_y);
+        let (width, height) = (
+            (max_x - min_x) * UPSCALE_FACTOR,
+            (max_y - min_y) * UPSCALE_FACTOR,
+        );
+        let upscaled = imageops::resize(&crop.to_image(), width, height, FilterType::CatmullRom);
+        // Detection would shrink a long line back down, undoing the upscale
+        let options = CaptureOptions {
+            max_side_len: Some(width.max(height)),
+            ..options.clone()
+        };
+        let blocks = match do_ocr(&self.ocr, &upscaled.into(), (0, 0), &options) {
+            Ok(blocks) => blocks,
+            Err(err) => {
+                log::error!("{err}");
+                return Vec::new();
+            }
+        };
+
+        let to_screen = |point: Coord<f32>| {
+            coord![
+                x: point.x / UPSCALE_FACTOR as f32 + min_x as f32 + origin_x,
+                y: point.y / UPSCALE_FACTOR as f32 + min_y as f32 + origin_y,
+            ]
+        };
+        blocks
+            .into_iter()
+            .map(|block| {
+                let chars = block
+                    .chars
+                    .into_iter()
+                    .map(|it| CharBox {
+                        rect: Rect::new(to_screen(it.rect.min()), to_screen(it.rect.max())),
+                        ..it
+                    })
+                    .collect();
+                Block {
+                    color: block.color,
+                    ..Block::new(block.text, chars, block.confidence)
+                }
+            })
+            .collect()
+    }
+}
+
+/// Captures the monitor, falling back to capturing the window under the cursor if the monitor
+/// capture comes back as a single solid color. Protected and hardware accelerated surfaces
+/// (mostly video players) often show up black in a monitor capture, but window capture goes
+/// through a different API on Windows (`PrintWindow` instead of a `BitBlt` of the screen) and
+/// can sometimes still read them.
+fn capture_monitor(monitor: &Monitor) -> Result<RgbaImage, CaptureError> {
+    let mut image = monitor
+        .capture_image()
+        .map_err(|err| CaptureError::Capture(err.to_string()))?;
+    if !is_solid(&image) {
+        return Ok(image);
+    }
+
+    // Only the window being read is worth retrying, any other one would just be unrelated text
+    let window = cursor_position()
+        .and_then(|(x, y)| window_at(x, y))
+        .filter(|window| window.current_monitor().id() == monitor.id());
+    let Some(window) = window else {
+        return Err(CaptureError::BlankFrame);
+    };
+    log::warn!(
+        "Monitor capture is a solid color, retrying with the window under the cursor: {}",
+        window.title()
+    );
+    let window_image = window
+        .capture_image()
+        .map_err(|err| CaptureError::Capture(err.to_string()))?;
+    imageops::overlay(
+        &mut image,
+        &window_image,
+        (window.x() - monitor.x()) as i64,
+        (window.y() - monitor.y()) as i64,
+    );
+
+    if is_solid(&image) {
+        Err(CaptureError::BlankFrame)
+    } else {
+        Ok(image)
     }
 }
 
+/// Finds the topmost visible window containing the point.
+pub fn window_at(x: i32, y: i32) -> Option<Window> {
+    Window::all().ok()?.into_iter().find(|window| {
+        !window.is_minimized()
+            && (window.x()..window.x() + window.width() as i32).contains(&x)
+            && (window.y()..window.y() + window.height() as i32).contains(&y)
+    })
+}
+
+/// Finds a window by its numeric id, or else the topmost window whose title contains
+/// `id_or_title`, ignoring case. Minimized windows can't be captured and are skipped.
+pub fn find_window(id_or_title: &str) -> Option<Window> {
+    let mut windows = Window::all()
+        .ok()?
+        .into_iter()
+        .filter(|window| !window.is_minimized());
+    match id_or_title.parse::<u32>() {
+        Ok(id) => windows.find(|window| window.id() == id),
+        Err(_) => {
+            let title = id_or_title.to_lowercase();
+            windows.find(|window| window.title().to_lowercase().contains(&title))
+        }
+    }
+}
+
+/// A window that can be captured, for picking a capture target.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct WindowInfo {
+    pub id: u32,
+    pub title: String,
+    pub app: String,
+}
+
+impl WindowInfo {
+    fn new(window: &Window) -> Self {
+        Self {
+            id: window.id(),
+            title: window.title().to_string(),
+            app: window.app_name().to_string(),
+        }
+    }
+}
+
+/// Windows that can be captured, topmost first. Minimized and untitled windows are skipped.
+pub fn list_windows() -> Vec<WindowInfo> {
+    Window::all()
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|window| !window.is_minimized() && !window.title().is_empty())
+        .map(|window| WindowInfo::new(&window))
+        .collect()
+}
+
+/// A quick look at what capturing a source would contain, to confirm before running OCR.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct CapturePreview {
+    /// The previewed window, `None` for the monitor under the cursor
+    pub window: Option<WindowInfo>,
+    /// Screen space bounds of the source
+    pub bounds: Rect<f32>,
+    /// Downscaled snapshot of the source as a PNG data URL
+    pub snapshot: String,
+}
+
+/// Previews capturing the window matching `target`, see [`find_window`], or the monitor under
+/// the cursor if there is no target.
+pub fn preview(target: Option<&str>) -> Result<CapturePreview, CaptureError> {
+    match target {
+        Some(target) => {
+            let window = find_window(target)
+                .ok_or_else(|| CaptureError::WindowNotFound(target.to_string()))?;
+            let (bounds, snapshot) = snapshot(&window)?;
+            Ok(CapturePreview {
+                window: Some(WindowInfo::new(&window)),
+                bounds,
+                snapshot,
+            })
+        }
+        None => {
+            let (x, y) = cursor_position().ok_or(CaptureError::NoCursor)?;
+            let monitor =
+                Monitor::from_point(x, y).map_err(|err| CaptureError::Capture(err.to_string()))?;
+            let (bounds, snapshot) = snapshot(&monitor)?;
+            Ok(CapturePreview {
+                window: None,
+                bounds,
+                snapshot,
+            })
+        }
+    }
+}
+
+/// Screen space bounds and a downscaled PNG data URL of a frame of `source`.
+fn snapshot(source: &dyn FrameSource) -> Result<(Rect<f32>, String), CaptureError> {
+    let frame = source.capture_frame()?;
+    let (x, y) = source.origin();
+    let (width, height) = frame.dimensions();
+    let bounds = Rect::new(
+        coord![x: x as f32, y: y as f32],
+        coord![x: (x + width as i32) as f32, y: (y + height as i32) as f32],
+    );
+    let scale = (PREVIEW_WIDTH as f32 / width as f32).min(1.0);
+    let snapshot = imageops::thumbnail(
+        &frame,
+        (width as f32 * scale) as u32,
+        (height as f32 * scale) as u32,
+    );
+    Ok((bounds, data_url(&snapshot)?))
+}
+
+/// Encodes an image as a PNG data URL for display in the frontend.
+pub fn data_url(image: &RgbaImage) -> Result<String, CaptureError> {
+    let mut png = Vec::new();
+    image
+        .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+        .map_err(|err| CaptureError::Capture(err.to_string()))?;
+    Ok(format!("data:image/png;base64,{}", STANDARD.encode(png)))
+}
+
+/// Scales `image` down to fit the monitor under the cursor and centers it there, to show it in
+/// a window and recognize it where it's displayed, see [`crate::recognize_image`].
+pub fn center_on_monitor(image: RgbaImage) -> Result<ImageSource, CaptureError> {
+    let (x, y) = cursor_position().ok_or(CaptureError::NoCursor)?;
+    let monitor =
+        Monitor::from_point(x, y).map_err(|err| CaptureError::Capture(err.to_string()))?;
+    let (width, height) = image.dimensions();
+    let scale = (monitor.width() as f32 * IMAGE_MAX_SCALE / width as f32)
+        .min(monitor.height() as f32 * IMAGE_MAX_SCALE / height as f32)
+        .min(1.0);
+    let image = if scale < 1.0 {
+        imageops::resize(
+            &image,
+            (width as f32 * scale) as u32,
+            (height as f32 * scale) as u32,
+            FilterType::Triangle,
+        )
+    } else {
+        image
+    };
+    let origin = (
+        monitor.x() + (monitor.width() as i32 - image.width() as i32) / 2,
+        monitor.y() + (monitor.height() as i32 - image.height() as i32) / 2,
+    );
+    Ok(ImageSource { image, origin })
+}
+
+/// The image currently on the clipboard, e.g. a screenshot taken with a snipping tool.
+pub fn clipboard_image() -> Result<RgbaImage, CaptureError> {
+    let image = Clipboard::new()
+        .and_then(|mut clipboard| clipboard.get_image())
+        .map_err(|err| match err {
+            arboard::Error::ContentNotAvailable => CaptureError::NoClipboardImage,
+            err => CaptureError::Capture(err.to_string()),
+        })?;
+    RgbaImage::from_raw(
+        image.width as u32,
+        image.height as u32,
+        image.bytes.into_owned(),
+    )
+    .ok_or_else(|| CaptureError::Capture("Invalid clipboard image".to_string()))
+}
+
+/// Stretch of the luminance range to the full 0-255 range. Captures of HDR and 10-bit
+/// monitors come through as SDR with raised blacks and dimmed whites, which lowers the contrast
+/// detection and recognition see. Only used for the monitors listed in
+/// [`Config::hdr_monitors`], since ordinary low contrast content would be stretched too. The
+/// range is measured once per OCR pass, so live mode frames all get the same transform.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RangeMap {
+    low: f32,
+    high: f32,
+}
+
+impl RangeMap {
+    /// Measures the range of a frame. `None` if it already uses the full range.
+    pub fn measure(image: &RgbaImage) -> Option<Self> {
+        let mut histogram = [0usize; 256];
+        for pixel in image.pixels() {
+            histogram[luminance(pixel.0) as usize] += 1;
+        }
+        let clip = ((image.width() * image.height()) as f32 * RANGE_CLIP) as usize;
+        let low = first_level_past(&histogram, clip, 0..256);
+        let high = first_level_past(&histogram, clip, (0..256).rev());
+        if high - low >= FULL_RANGE as f32 || high <= low {
+            return None;
+        }
+        log::debug!("Normalizing frame range {low}-{high}");
+        Some(Self { low, high })
+    }
+
+    pub fn apply(&self, mut image: RgbaImage) -> RgbaImage {
+        let scale = 255.0 / (self.high - self.low);
+        for pixel in image.pixels_mut() {
+            for channel in &mut pixel.0[..3] {
+                *channel = ((*channel as f32 - self.low) * scale).clamp(0.0, 255.0) as u8;
+            }
+        }
+        image
+    }
+}
+
+/// First level, in the order of `levels`, at which more than `count` pixels were seen.
+fn first_level_past(
+    histogram: &[usize; 256],
+    count: usize,
+    mut levels: impl Iterator<Item = usize>,
+) -> f32 {
+    let mut seen = 0;
+    levels
+        .find(|level| {
+            seen += histogram[*level];
+            seen > count
+        })
+        .unwrap_or(0) as f32
+}
+
+/// Rec. 709 luma, the same weights `image` uses for grayscale conversion
+fn luminance([r, g, b, _]: [u8; 4]) -> u8 {
+    (0.2126 * r as f32 + 0.7152 * g as f32 + 0.0722 * b as f32) as u8
+}
+
+fn is_solid(image: &RgbaImage) -> bool {
+    let Some(first) = image.pixels().next() else {
+        return true;
+    };
+    image.pixels().all(|pixel| pixel.0[..3] == first.0[..3])
+}
+
 pub fn do_ocr(
     ocr: &RapidOCR,
     image: &DynamicImage,
-    monitor: &Monitor,
-) -> Vec<(String, Vec<(usize, Rect<f32>)>)> {
-    let options = DetectionOptions {
-        max_side_len: 2048,
-        ..Default::default()
-    };
-    let detection_result = ocr.detect(&image, options).unwrap();
+    origin: (i32, i32),
+    options: &CaptureOptions,
+) -> Result<Vec<Block>, CaptureError> {
+    let detection_result = ocr
+        .detect(&image, options.detection_options())
+        .map_err(|err| CaptureError::Ocr(err.to_string()))?;
     for result in &detection_result {
         log::debug!(
             "[Text: {}, Bounds: {:?}]",
@@ -52,6 +567,11 @@ pub fn do_ocr(
             result.bounds.rect.bounding_rect().unwrap()
         );
     }
-    let char_boxes = detect_char_boxes(&image, &detection_result, monitor);
-    char_boxes
+    let min_confidence = options.min_confidence.unwrap_or(0.0);
+    let mut blocks = detect_char_boxes(&image, &detection_result, origin, min_confidence);
+    for block in &mut blocks {
+        let rect = block.bounds.translate(-origin.0 as f32, -origin.1 as f32);
+        block.color = contrasting_color(image, rect);
+    }
+    Ok(blocks)
 }
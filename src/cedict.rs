@@ -0,0 +1,105 @@
+//! Loader for the official CC-CEDICT text format
+//! (<https://www.mdbg.net/chinese/dictionary?page=cc-cedict>), so the upstream `cedict_ts.u8`
+//! can be used as is. Each line reads `Traditional Simplified [pin1 yin1] /gloss/gloss/`, lines
+//! starting with `#` are comments.
+
+use crate::{dict::DictionaryEntry, pinyin::parse_pinyin};
+
+/// Whether `data` is the text format rather than the preconverted JSON of the bundled
+/// `cedict.json`.
+pub fn is_native(data: &str) -> bool {
+    !data
+        .trim_start_matches('\u{feff}')
+        .trim_start()
+        .starts_with('[')
+}
+
+/// Converts every line into an entry. Malformed lines are skipped.
+pub fn parse(data: &str) -> Vec<DictionaryEntry> {
+    let mut skipped = 0;
+    let entries = data
+        .lines()
+        .map(|line| line.trim_start_matches('\u{feff}').trim())
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| {
+            let entry = parse_line(line);
+            if entry.is_none() {
+                skipped += 1;
+            }
+            entry
+        })
+        .collect::<Vec<_>>();
+    if skipped > 0 {
+        log::warn!("Skipped {skipped} malformed CC-CEDICT lines");
+    }
+    entries
+}
+
+fn parse_line(line: &str) -> Option<DictionaryEntry> {
+    let (traditional, rest) = line.split_once(' ')?;
+    let (simplified, rest) = rest.split_once(' ')?;
+    let (pinyin, rest) = rest.strip_prefix('[')?.split_once(']')?;
+    let translations = rest
+        .trim()
+        .strip_prefix('/')?
+        .split('/')
+        .filter(|gloss| !gloss.is_empty())
+        .map(str::to_string)
+        .collect::<Vec<_>>();
+    if translations.is_empty() {
+        return None;
+    }
+    Some(DictionaryEntry {
+        simplified: simplified.to_string(),
+        traditional: traditional.to_string(),
+        pinyin: parse_pinyin(pinyin),
+        translations,
+        fuzzy: false,
+        reading: None,
+        word_classes: Vec::new(),
+        inflections: Vec::new(),
+        tags: Vec::new(),
+        frequency: None,
+        source: String::new(),
+        known: false,
+        rank: None,
+        levels: Vec::new(),
+        matched_script: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_lines() {
+        let entries = parse("# CC-CEDICT\n中國 中国 [Zhong1 guo2] /China/Middle Kingdom/\n");
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].traditional, "中國");
+        assert_eq!(entries[0].simplified, "中国");
+        assert_eq!(entries[0].pinyin.len(), 2);
+        assert_eq!(entries[0].translations, ["China", "Middle Kingdom"]);
+    }
+
+    #[test]
+    fn skips_malformed_lines() {
+        let data = "中国\n\
+                    中國 中国\n\
+                    中國 中国 Zhong1 guo2 /China/\n\
+                    中國 中国 [Zhong1 guo2\n\
+                    中國 中国 [Zhong1 guo2]\n\
+                    中國 中国 [Zhong1 guo2] //\n\
+                    你好 你好 [ni3 hao3] /hello/";
+        let entries = parse(data);
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].simplified, "你好");
+    }
+
+    #[test]
+    fn tells_text_from_json() {
+        assert!(is_native("\u{feff}# CC-CEDICT\n"));
+        assert!(!is_native("[{\"simplified\": \"中国\"}]"));
+        assert!(!is_native("\u{feff}\n  ["));
+    }
+}
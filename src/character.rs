@@ -9,23 +9,72 @@ use imageproc::{
 };
 use ordered_float::OrderedFloat;
 use rapidocr::OcrResult;
+use serde::{Deserialize, Serialize};
 use unicode_blocks::{
     find_unicode_block, is_cjk, CJK_SYMBOLS_AND_PUNCTUATION, HALFWIDTH_AND_FULLWIDTH_FORMS,
 };
-use xcap::Monitor;
 
 #[cfg(feature = "debug")]
 use crate::draw_outline_geo;
-use crate::to_geo_poly;
+use crate::{color::DEFAULT_OUTLINE, filter::block_bounds, to_geo_poly};
 
-pub type Character = (usize, Rect<f32>);
-pub type Characters = Vec<Character>;
-pub type Block = (String, Characters);
+/// The box of a single character, in screen space.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct CharBox {
+    /// Character index within the block's text
+    pub index: usize,
+    pub rect: Rect<f32>,
+}
+
+/// A line of text recognized by OCR.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Block {
+    pub text: String,
+    pub chars: Vec<CharBox>,
+    /// Bounding box of all characters
+    pub bounds: Rect<f32>,
+    /// Recognition score of the line, from 0 to 1
+    pub confidence: f32,
+    /// Outline color that stands out against the pixels under the line, see
+    /// [`crate::color::contrasting_color`]
+    #[serde(default = "default_outline")]
+    pub color: [u8; 3],
+}
+
+fn default_outline() -> [u8; 3] {
+    DEFAULT_OUTLINE
+}
+
+impl Block {
+    pub fn new(text: String, chars: Vec<CharBox>, confidence: f32) -> Self {
+        let bounds = block_bounds(&chars)
+            .unwrap_or(Rect::new(coord![x: 0.0, y: 0.0], coord![x: 0.0, y: 0.0]));
+        Self {
+            text,
+            chars,
+            bounds,
+            confidence,
+            color: DEFAULT_OUTLINE,
+        }
+    }
+}
+
+/// Numbers character boxes in text order.
+fn char_boxes(rects: impl IntoIterator<Item = Rect<f32>>) -> Vec<CharBox> {
+    rects
+        .into_iter()
+        .enumerate()
+        .map(|(index, rect)| CharBox { index, rect })
+        .collect()
+}
 
+/// Splits detected lines into character boxes. Lines recognized with a confidence below
+/// `min_confidence` are dropped.
 pub fn detect_char_boxes(
     image: &DynamicImage,
     detection_results: &[OcrResult],
-    monitor: &Monitor,
+    origin: (i32, i32),
+    min_confidence: f32,
 ) -> Vec<Block> {
     detection_results
         .iter()
@@ -34,17 +83,27 @@ pub fn detect_char_boxes(
             let text = line.text.text.trim();
             text.chars().count() > 0 && text.chars().all(is_cjk)
         })
-        .filter_map(|(i, line)| {
+        .filter(|(_, line)| {
+            let confident = line.text.score >= min_confidence;
+            if !confident {
+                log::info!(
+                    "Dropped {} with confidence {}",
+                    line.text.text,
+                    line.text.score
+                );
+            }
+            confident
+        })
+        .flat_map(|(i, line)| {
             let text = strip_punctuation(&line.text.text);
             log::info!("Stripped string: {text}");
             let text_len = text.chars().count();
             let removed = line.text.text.chars().count() - text_len;
             log::debug!("{} is CJK: {}", text, text.trim().chars().all(is_cjk));
             if text_len <= 1 {
-                return Some((
-                    text,
-                    vec![(0usize, line.bounds.rect.bounding_rect().unwrap())],
-                ));
+                let rect = line.bounds.rect.bounding_rect().unwrap();
+                let rect = rect.translate(origin.0 as f32, origin.1 as f32);
+                return vec![Block::new(text, char_boxes([rect]), line.text.score)];
             }
             log::info!("Contouring {i}");
             let rect = line.bounds.rect.bounding_rect().unwrap();
@@ -54,7 +113,14 @@ pub fn detect_char_boxes(
                 rect.width() as u32,
                 rect.height() as u32,
             );
-            let image_height = image.height();
+            // Vertical lines are split top to bottom. Contours are transposed so the rest of
+            // the segmentation can treat every line as horizontal.
+            let vertical = rect.height() > rect.width();
+            let image_height = if vertical {
+                image.width()
+            } else {
+                image.height()
+            };
 
             let mut gray_image = threshold(&image.to_luma8(), 128, ThresholdType::Binary);
             if gray_image.get_pixel(0, 0).0 == [255] {
@@ -71,11 +137,12 @@ pub fn detect_char_boxes(
                             .offset(0.5, JoinType::Square, EndType::ClosedPolygon, 1.0);
                     poly.bounding_rect()
                 })
+                .map(|it| if vertical { transpose(it) } else { it })
                 .collect::<Vec<_>>();
 
             if bounds.len() < 2 {
                 log::info!("bounds too small");
-                return None;
+                return Vec::new();
             }
 
             bounds.sort_by_cached_key(|it| OrderedFloat(it.min().x));
@@ -90,58 +157,181 @@ pub fn detect_char_boxes(
             {
                 let mut image = DynamicImage::ImageLuma8(gray_image).to_rgb8();
                 for contour in bounds.iter() {
-                    draw_outline_geo(&mut image, *contour, Rgb([255, 0, 0]))
+                    let contour = if vertical {
+                        transpose(*contour)
+                    } else {
+                        *contour
+                    };
+                    draw_outline_geo(&mut image, contour, Rgb([255, 0, 0]))
                 }
                 image.save(format!("part_images/subimage{i}.png")).unwrap();
             }
 
-            let mut character_width = find_character_width(&bounds);
+            let character_width = find_character_width(&bounds);
             if character_width == 0.0 {
                 log::info!("No contours found for {}", line.text.text);
-                return None;
+                return Vec::new();
             }
             log::info!("Character width: {character_width}");
+            let to_screen = |char_rect: Rect<f32>| {
+                let char_rect = if vertical {
+                    transpose(char_rect)
+                } else {
+                    char_rect
+                };
+                char_rect
+                    .translate(rect.min().x, rect.min().y)
+                    .translate(origin.0 as f32, origin.1 as f32)
+            };
+
+            if let Some(segments) = split_at_gaps(&bounds, character_width, text_len) {
+                log::info!("Splitting {text} into {} blocks at gaps", segments.len());
+                let mut chars = text.chars();
+                return segments
+                    .into_iter()
+                    .map(|(segment, len)| {
+                        let line_rect = find_line_bounds(&segment, character_width);
+                        let rects = layout_chars(line_rect, character_width, len)
+                            .into_iter()
+                            .map(to_screen);
+                        Block::new(
+                            chars.by_ref().take(len).collect(),
+                            char_boxes(rects),
+                            line.text.score,
+                        )
+                    })
+                    .collect();
+            }
+
             let line_rect = find_line_bounds(&bounds, character_width);
             log::info!("Detected line height: {}", line_rect.height());
-            if character_width * text_len as f32 > line_rect.width() {
-                let new_width = line_rect.width() / text_len as f32;
-                log::warn!(
-                    "Incorrect boxes: character boxes exceed line. Correcting by {}",
-                    new_width / character_width
-                );
-                character_width = new_width;
-            }
+            let rects = layout_chars(line_rect, character_width, text_len)
+                .into_iter()
+                .map(to_screen);
+            vec![Block::new(
+                line.text.text.clone(),
+                char_boxes(rects),
+                line.text.score,
+            )]
+        })
+        .collect()
+}
+
+/// Replaces the text of a block, spreading the new characters evenly over the old line.
+pub fn replace_text(block: &Block, text: String) -> Block {
+    if block.chars.is_empty() {
+        return block.clone();
+    }
+    let bounds = block.bounds;
+    let vertical = bounds.height() > bounds.width();
+    let line = if vertical { transpose(bounds) } else { bounds };
+    let len = text.chars().count().max(1);
+    let char_width = line.width() / len as f32;
+    let rects = (0..len).map(|i| {
+        let min_x = line.min().x + i as f32 * char_width;
+        let rect = Rect::new(
+            coord![x: min_x, y: line.min().y],
+            coord![x: min_x + char_width, y: line.max().y],
+        );
+        if vertical {
+            transpose(rect)
+        } else {
+            rect
+        }
+    });
+    Block {
+        color: block.color,
+        ..Block::new(text, char_boxes(rects), 1.0)
+    }
+}
+
+/// Gaps between contours wider than this many character widths separate unrelated text, e.g.
+/// two UI labels detected as one line
+const SPLIT_GAP_FACTOR: f32 = 2.5;
 
-            let letter_spacing =
-                (line_rect.width() - character_width * text_len as f32) / (text_len - 1) as f32;
+/// Spreads `text_len` evenly spaced character boxes across the line.
+fn layout_chars(line_rect: Rect<f32>, character_width: f32, text_len: usize) -> Vec<Rect<f32>> {
+    let mut character_width = character_width;
+    if character_width * text_len as f32 > line_rect.width() {
+        let new_width = line_rect.width() / text_len as f32;
+        log::warn!(
+            "Incorrect boxes: character boxes exceed line. Correcting by {}",
+            new_width / character_width
+        );
+        character_width = new_width;
+    }
 
-            //let letter_spacing = find_letter_spacing(&bounds, character_width, line_rect);
-            log::info!("Detected character spacing: {letter_spacing}");
+    let letter_spacing =
+        (line_rect.width() - character_width * text_len as f32) / (text_len.max(2) - 1) as f32;
 
-            Some((
-                line.text.text.clone(),
-                text.chars()
-                    .enumerate()
-                    .map(|(i, _)| {
-                        let min_x =
-                            line_rect.min().x + i as f32 * (letter_spacing + character_width);
-                        let max_x = min_x + character_width;
-                        (
-                            i,
-                            Rect::new(
-                                coord![x: min_x, y: line_rect.min().y],
-                                coord![x: max_x, y: line_rect.max().y],
-                            )
-                            .translate(rect.min().x, rect.min().y)
-                            .translate(monitor.x() as f32, monitor.y() as f32),
-                        )
-                    })
-                    .collect(),
-            ))
+    //let letter_spacing = find_letter_spacing(&bounds, character_width, line_rect);
+    log::info!("Detected character spacing: {letter_spacing}");
+
+    (0..text_len)
+        .map(|i| {
+            let min_x = line_rect.min().x + i as f32 * (letter_spacing + character_width);
+            let max_x = min_x + character_width;
+            Rect::new(
+                coord![x: min_x, y: line_rect.min().y],
+                coord![x: max_x, y: line_rect.max().y],
+            )
         })
         .collect()
 }
 
+/// Splits contours sorted by x into runs separated by abnormally large gaps, with the number of
+/// characters in each run. Returns `None` if there are no such gaps, or if the character counts
+/// can't be matched up with the text.
+fn split_at_gaps(
+    bounds: &[Rect<f32>],
+    char_width: f32,
+    text_len: usize,
+) -> Option<Vec<(Vec<Rect<f32>>, usize)>> {
+    let mut segments = vec![vec![bounds[0]]];
+    let mut end = bounds[0].max().x;
+    for bound in &bounds[1..] {
+        if bound.min().x - end > char_width * SPLIT_GAP_FACTOR {
+            segments.push(vec![*bound]);
+        } else {
+            segments.last_mut().unwrap().push(*bound);
+        }
+        end = end.max(bound.max().x);
+    }
+    if segments.len() < 2 || segments.len() > text_len {
+        return None;
+    }
+
+    // Letter spacing that fits all characters into the segments, ignoring the gaps
+    let widths = segments
+        .iter()
+        .map(|segment| find_line_bounds(segment, char_width).width())
+        .collect::<Vec<_>>();
+    let spacing = (widths.iter().sum::<f32>() - char_width * text_len as f32)
+        / (text_len - segments.len()).max(1) as f32;
+    let spacing = spacing.max(0.0);
+    let lens = widths
+        .iter()
+        .map(|width| {
+            ((width + spacing) / (char_width + spacing))
+                .round()
+                .max(1.0) as usize
+        })
+        .collect::<Vec<_>>();
+    if lens.iter().sum::<usize>() != text_len {
+        log::debug!("Found gaps, but segment lengths {lens:?} don't add up to {text_len}");
+        return None;
+    }
+    Some(segments.into_iter().zip(lens).collect())
+}
+
+/// Swaps the x and y axes.
+fn transpose(rect: Rect<f32>) -> Rect<f32> {
+    Rect::new(
+        coord![x: rect.min().y, y: rect.min().x],
+        coord![x: rect.max().y, y: rect.max().x],
+    )
+}
+
 fn find_line_bounds(bounds: &[Rect<f32>], char_width: f32) -> Rect<f32> {
     let min_y = *bounds
         .iter()
@@ -0,0 +1,184 @@
+//! Words that appear together in the same sentences, collected from everything recognized
+//! across sessions. The entry window lists the most frequent ones as collocation hints.
+
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap, HashSet, VecDeque},
+    fs::OpenOptions,
+    hash::{Hash, Hasher},
+    io::{self, Write as _},
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{dict::DictionaryGroup, segment::segment, LiveOcr};
+
+/// Characters that end a sentence
+pub(crate) const SENTENCE_ENDS: &[char] = &['。', '！', '？', '；', '!', '?', ';'];
+/// Pairs seen fewer times than this are mostly chance and aren't shown
+const MIN_COUNT: u32 = 2;
+
+/// The words of one counted sentence, as appended to the collocations file.
+#[derive(Serialize, Deserialize)]
+struct CountedSentence {
+    hash: u64,
+    words: Vec<String>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct Collocate {
+    pub word: String,
+    /// Number of sentences both words appeared in
+    pub count: u32,
+}
+
+/// Co-occurrence counts persisted across runs. Persisted as JSON lines of the words in each
+/// counted sentence so recording only appends, and the counts are rebuilt on load.
+pub struct Collocations {
+    path: PathBuf,
+    /// How often two words appeared in the same sentence, stored under both words
+    counts: HashMap<String, HashMap<String, u32>>,
+    /// Hashes of the most recently counted sentences, so text that stays on screen or shows
+    /// up again soon after is only counted once. Only the most recent `limit` are kept, see
+    /// [`crate::memory::MemoryLimits`].
+    counted: HashSet<u64>,
+    counted_order: VecDeque<u64>,
+    limit: usize,
+}
+
+impl Collocations {
+    /// Counts the word pairs in the sentences of `lines` that weren't counted recently. Only
+    /// words of two or more characters are counted, single characters are mostly particles.
+    /// Words are counted under their simplified form, so traditional text adds to the same
+    /// counts the entry window looks up.
+    pub fn record<'a>(&mut self, dict: &DictionaryGroup, lines: impl Iterator<Item = &'a str>) {
+        for sentence in lines.flat_map(|line| line.split(SENTENCE_ENDS)) {
+            let sentence = sentence.trim();
+            let hash = hash(sentence);
+            if sentence.is_empty() || !self.remember(hash) {
+                continue;
+            }
+            let chars = sentence.chars().collect::<Vec<_>>();
+            let mut words = segment(dict, sentence)
+                .into_iter()
+                .filter(|span| span.len() > 1)
+                .map(|span| headword(dict, chars[span].iter().collect()))
+                .collect::<Vec<_>>();
+            words.sort();
+            words.dedup();
+            let sentence = CountedSentence { hash, words };
+            // A sentence without pairs doesn't add anything to the file
+            if sentence.words.len() > 1 {
+                if let Err(err) = self.append(&sentence) {
+                    log::error!("Failed to save collocations to {:?}: {err}", self.path);
+                }
+            }
+            self.count(&sentence.words);
+        }
+    }
+
+    /// The words most often seen together with `word`, most frequent first.
+    pub fn top(&self, word: &str, limit: usize) -> Vec<Collocate> {
+        let Some(counts) = self.counts.get(word) else {
+            return Vec::new();
+        };
+        let mut collocates = counts
+            .iter()
+            .filter(|(_, count)| **count >= MIN_COUNT)
+            .map(|(word, count)| Collocate {
+                word: word.clone(),
+                count: *count,
+            })
+            .collect::<Vec<_>>();
+        collocates.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.word.cmp(&b.word)));
+        collocates.truncate(limit);
+        collocates
+    }
+
+    /// Remembers a sentence as counted, evicting the oldest beyond the limit. `false` if it
+    /// was already counted recently.
+    fn remember(&mut self, hash: u64) -> bool {
+        if !self.counted.insert(hash) {
+            return false;
+        }
+        self.counted_order.push_back(hash);
+        while self.counted_order.len() > self.limit {
+            if let Some(oldest) = self.counted_order.pop_front() {
+                self.counted.remove(&oldest);
+            }
+        }
+        true
+    }
+
+    fn count(&mut self, words: &[String]) {
+        for (i, a) in words.iter().enumerate() {
+            for b in &words[i + 1..] {
+                self.increment(a, b);
+                self.increment(b, a);
+            }
+        }
+    }
+
+    fn increment(&mut self, word: &str, other: &str) {
+        *self
+            .counts
+            .entry(word.to_string())
+            .or_default()
+            .entry(other.to_string())
+            .or_default() += 1;
+    }
+
+    fn append(&self, sentence: &CountedSentence) -> io::Result<()> {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        writeln!(file, "{}", serde_json::to_string(sentence)?)
+    }
+}
+
+/// Rebuilds the counts from the file, remembering the most recent `limit` sentences as
+/// counted.
+pub fn load(path: impl AsRef<Path>, limit: usize) -> Collocations {
+    let path = path.as_ref();
+    let mut collocations = Collocations {
+        path: path.to_owned(),
+        counts: HashMap::new(),
+        counted: HashSet::new(),
+        counted_order: VecDeque::new(),
+        limit,
+    };
+    std::fs::read_to_string(path)
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|line| serde_json::from_str::<CountedSentence>(line).ok())
+        .for_each(|sentence| {
+            collocations.remember(sentence.hash);
+            collocations.count(&sentence.words);
+        });
+    collocations
+}
+
+/// Counts the current OCR results, see [`Collocations::record`].
+pub(crate) fn record_ocr(state: &mut LiveOcr) {
+    let lines = state
+        .definitions
+        .ocr_strings
+        .iter()
+        .map(|block| block.text.as_str());
+    state.collocations.record(&state.definitions.dict, lines);
+}
+
+/// The simplified form of `word` if it's a dictionary headword in either script.
+fn headword(dict: &DictionaryGroup, word: String) -> String {
+    dict.matches(&word)
+        .into_iter()
+        .find(|entry| entry.simplified == word || entry.traditional == word)
+        .map_or(word, |entry| entry.simplified)
+}
+
+fn hash(sentence: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    sentence.hash(&mut hasher);
+    hasher.finish()
+}
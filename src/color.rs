@@ -0,0 +1,67 @@
+//! Picks outline colors that stay visible on top of the captured text. A fixed color
+//! disappears on UIs that happen to use it, e.g. red outlines on a red game HUD.
+
+use geo::Rect;
+use image::{DynamicImage, GenericImageView as _};
+
+/// Colors outlines are picked from
+const CANDIDATES: [[u8; 3]; 7] = [
+    [255, 48, 48],
+    [48, 255, 96],
+    [0, 200, 255],
+    [255, 224, 0],
+    [255, 0, 255],
+    [255, 255, 255],
+    [0, 0, 0],
+];
+/// Pixels sampled along each side of a block
+const SAMPLES_PER_SIDE: u32 = 16;
+
+/// Outline color used when there's nothing to sample
+pub const DEFAULT_OUTLINE: [u8; 3] = CANDIDATES[0];
+
+/// The candidate color furthest from the average color of the pixels under `rect`, in image
+/// space.
+pub fn contrasting_color(image: &DynamicImage, rect: Rect<f32>) -> [u8; 3] {
+    let Some(average) = average_color(image, rect) else {
+        return DEFAULT_OUTLINE;
+    };
+    CANDIDATES
+        .into_iter()
+        .max_by_key(|candidate| distance(*candidate, average))
+        .unwrap_or(DEFAULT_OUTLINE)
+}
+
+fn average_color(image: &DynamicImage, rect: Rect<f32>) -> Option<[u32; 3]> {
+    let (width, height) = image.dimensions();
+    let min_x = (rect.min().x.max(0.0) as u32).min(width);
+    let min_y = (rect.min().y.max(0.0) as u32).min(height);
+    let max_x = (rect.max().x.max(0.0) as u32).min(width);
+    let max_y = (rect.max().y.max(0.0) as u32).min(height);
+    if max_x <= min_x || max_y <= min_y {
+        return None;
+    }
+    let step_x = ((max_x - min_x) / SAMPLES_PER_SIDE).max(1);
+    let step_y = ((max_y - min_y) / SAMPLES_PER_SIDE).max(1);
+
+    let mut sum = [0u32; 3];
+    let mut count = 0;
+    for y in (min_y..max_y).step_by(step_y as usize) {
+        for x in (min_x..max_x).step_by(step_x as usize) {
+            let pixel = image.get_pixel(x, y).0;
+            for (sum, channel) in sum.iter_mut().zip(pixel) {
+                *sum += channel as u32;
+            }
+            count += 1;
+        }
+    }
+    Some(sum.map(|channel| channel / count))
+}
+
+fn distance(color: [u8; 3], average: [u32; 3]) -> u32 {
+    color
+        .into_iter()
+        .zip(average)
+        .map(|(a, b)| (a as i32 - b as i32).pow(2) as u32)
+        .sum()
+}
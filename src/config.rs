@@ -0,0 +1,224 @@
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    anki::AnkiConfig,
+    dict::{DictionarySource, Script},
+    filter::BlockFilter,
+    hooks::Hooks,
+    lens::LensConfig,
+    memory::MemoryLimits,
+    payload::{TonePalette, TooltipSection},
+    Error, Result,
+};
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ScreenEdge {
+    Top,
+    Bottom,
+}
+
+/// Side of the screen the details panel is docked to.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PanelSide {
+    Left,
+    Right,
+}
+
+/// Compositor effect for the tooltip background. Effects other than `None` make the window
+/// translucent.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum WindowEffect {
+    #[default]
+    None,
+    Acrylic,
+    Mica,
+    Blur,
+}
+
+/// How lookups of words the user marked as known are shown.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum KnownWordDisplay {
+    Show,
+    /// Show them faded out
+    #[default]
+    Dim,
+    /// Leave them out of the tooltip
+    Skip,
+}
+
+/// What to do with lines shorter than `min_height` pixels, which OCR tends to misread.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct SmallText {
+    pub min_height: f32,
+    pub action: SmallTextAction,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SmallTextAction {
+    Drop,
+    /// Re-run OCR on an upscaled crop of the line
+    Upscale,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct Config {
+    /// How long the tooltip stays open after the cursor leaves the hovered word.
+    pub auto_hide_delay_ms: u64,
+    /// How close the cursor has to be to a character to look it up, in screen pixels.
+    pub hover_distance: f32,
+    /// How long the cursor has to rest on a word before it's looked up. Zero looks up
+    /// immediately.
+    pub hover_dwell_ms: u64,
+    /// Which sections to show for each tooltip entry, in display order.
+    pub tooltip_sections: Vec<TooltipSection>,
+    /// Colors for pinyin tones.
+    pub tone_palette: TonePalette,
+    /// Script of the headword in tooltips and exports.
+    pub headword_script: Script,
+    /// Retry lookups with characters OCR commonly confuses substituted, see
+    /// [`crate::Definitions::update`].
+    pub fuzzy_lookup: bool,
+    /// Translucent tooltip background, where the platform supports it.
+    pub tooltip_effect: WindowEffect,
+    /// Show lookups in a strip docked to this edge of the screen instead of a tooltip.
+    pub reading_strip: Option<ScreenEdge>,
+    /// Show full definitions in a panel docked to this side of the screen, and only the
+    /// headword, pronunciation and first definition in the tooltip.
+    pub details_panel: Option<PanelSide>,
+    /// Outline every recognized line after OCR, to show what can be hovered.
+    pub highlight_blocks: bool,
+    /// Only capture the window with this id or title, instead of the monitor under the cursor.
+    pub target_window: Option<String>,
+    /// Names of monitors whose captures come through washed out, e.g. HDR or 10-bit monitors.
+    /// Their captures are stretched to the full range, see [`crate::capture::RangeMap`].
+    pub hdr_monitors: Vec<String>,
+    /// Drops noise like clocks or usernames from OCR results.
+    pub block_filter: BlockFilter,
+    /// While enabled, recapture this often and re-run OCR on the parts of the screen that
+    /// changed. `None` only runs OCR when toggling.
+    pub live_interval_ms: Option<u64>,
+    /// Serves the local HTTP API on this port, see [`crate::server`].
+    pub server_port: Option<u16>,
+    /// Streams hover events over a WebSocket on this port, see [`crate::websocket`].
+    pub websocket_port: Option<u16>,
+    /// Web page origins allowed to connect to the WebSocket, e.g. `http://localhost:5000`.
+    /// Connections from any other page are refused.
+    pub websocket_origins: Vec<String>,
+    /// Drops lines too short for OCR to read reliably, or recognizes them again upscaled.
+    pub small_text: Option<SmallText>,
+    /// OCR lines recognized with a lower confidence (0 to 1) are ignored.
+    pub min_confidence: f32,
+    /// CC-CEDICT file to use instead of the bundled one, e.g. the upstream `cedict_ts.u8`.
+    pub cedict_path: Option<PathBuf>,
+    /// JMdict file in the jmdict-simplified JSON format to use instead of the bundled CC-CEDICT.
+    pub jmdict_path: Option<PathBuf>,
+    /// Additional dictionaries looked up alongside the base one, e.g. imported Yomitan
+    /// dictionaries.
+    pub dictionaries: Vec<DictionarySource>,
+    /// Word frequency list used to rank matches, see [`crate::dict::load_frequency_list`].
+    pub frequency_list: Option<PathBuf>,
+    /// Word lists of proficiency tests like HSK or TOCFL, see
+    /// [`crate::dict::load_level_list`].
+    pub level_lists: Vec<PathBuf>,
+    /// Idiom origin stories shown in the entry window, see [`crate::dict::load_idiom_pack`].
+    pub idiom_packs: Vec<PathBuf>,
+    /// Shell commands run on events, see [`crate::hooks`].
+    pub hooks: Hooks,
+    /// Enables the magnifier while its key is held.
+    pub lens: Option<LensConfig>,
+    /// Enables exporting the hovered word to Anki with Alt + A.
+    pub anki: Option<AnkiConfig>,
+    /// How words marked as known are shown in tooltips, see [`crate::known`].
+    pub known_words: KnownWordDisplay,
+    /// Show what will be captured and wait for confirmation before running OCR.
+    pub capture_preview: bool,
+    /// Lookups further apart than this start a new reading session, see [`crate::reading`].
+    pub reading_session_gap_minutes: u64,
+    /// Caps on session data kept in memory, see [`crate::memory`].
+    pub memory_limits: MemoryLimits,
+    /// Target time for a full OCR pass. The detection size is adjusted to stay within it.
+    pub latency_budget_ms: Option<u64>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            auto_hide_delay_ms: 0,
+            hover_distance: 5.0,
+            hover_dwell_ms: 0,
+            tooltip_sections: TooltipSection::defaults(),
+            tone_palette: TonePalette::default(),
+            headword_script: Script::default(),
+            fuzzy_lookup: false,
+            tooltip_effect: WindowEffect::None,
+            reading_strip: None,
+            details_panel: None,
+            highlight_blocks: false,
+            target_window: None,
+            hdr_monitors: Vec::new(),
+            block_filter: BlockFilter::default(),
+            live_interval_ms: None,
+            server_port: None,
+            websocket_port: None,
+            websocket_origins: Vec::new(),
+            small_text: None,
+            min_confidence: 0.5,
+            cedict_path: None,
+            jmdict_path: None,
+            dictionaries: Vec::new(),
+            frequency_list: None,
+            level_lists: Vec::new(),
+            idiom_packs: Vec::new(),
+            hooks: Hooks::default(),
+            lens: None,
+            anki: None,
+            known_words: KnownWordDisplay::default(),
+            capture_preview: false,
+            reading_session_gap_minutes: 30,
+            memory_limits: MemoryLimits::default(),
+            latency_budget_ms: None,
+        }
+    }
+}
+
+impl Config {
+    pub fn auto_hide_delay(&self) -> Duration {
+        Duration::from_millis(self.auto_hide_delay_ms)
+    }
+
+    pub fn hover_dwell(&self) -> Duration {
+        Duration::from_millis(self.hover_dwell_ms)
+    }
+
+    pub fn reading_session_gap_secs(&self) -> u64 {
+        self.reading_session_gap_minutes * 60
+    }
+}
+
+/// Loads the config file, writing the default config if none exists yet so users have
+/// something to edit.
+pub fn load(path: impl AsRef<Path>) -> Result<Config> {
+    let path = path.as_ref();
+    if !path.exists() {
+        let config = Config::default();
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, serde_json::to_string_pretty(&config)?)?;
+        return Ok(config);
+    }
+
+    let data = std::fs::read_to_string(path)?;
+    serde_json::from_str(&data).map_err(Error::Config)
+}
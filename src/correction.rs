@@ -0,0 +1,150 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    character::{replace_text, Block},
+    collocation,
+    hover::{find_block, reset_hover, update_hover, HoverEvent, HoveredWord},
+    LiveOcr,
+};
+
+/// Corrections for one application.
+#[derive(Serialize, Deserialize, Default, Debug)]
+struct AppCorrections {
+    /// Whole block corrections, keyed by the misread text
+    #[serde(default)]
+    texts: HashMap<String, String>,
+    /// Text the OCR model reliably misreads in this application's font, e.g. `"未" -> "末"`.
+    /// Replaced anywhere it appears.
+    #[serde(default)]
+    substitutions: BTreeMap<String, String>,
+}
+
+impl AppCorrections {
+    fn apply(&self, block: &mut Block) {
+        if let Some(corrected) = self.texts.get(&block.text) {
+            log::info!("Correcting {} to {corrected}", block.text);
+            *block = replace_text(block, corrected.clone());
+            return;
+        }
+        let substituted = self
+            .substitutions
+            .iter()
+            .fold(block.text.clone(), |text, (from, to)| {
+                text.replace(from, to)
+            });
+        if substituted == block.text {
+            return;
+        }
+        log::info!("Substituting {} with {substituted}", block.text);
+        if substituted.chars().count() == block.text.chars().count() {
+            // Character boxes still line up, keep the detected ones
+            block.text = substituted;
+        } else {
+            *block = replace_text(block, substituted);
+        }
+    }
+}
+
+/// Corrections the user made to OCR results, keyed by application. Later captures in the same
+/// application get the same corrections applied.
+pub struct Corrections {
+    path: PathBuf,
+    data: HashMap<String, AppCorrections>,
+}
+
+impl Corrections {
+    pub fn record(&mut self, app: &str, original: &str, corrected: &str) {
+        self.data
+            .entry(app.to_string())
+            .or_default()
+            .texts
+            .insert(original.to_string(), corrected.to_string());
+        self.save();
+    }
+
+    /// Remembers the parts of `original` that were changed in `corrected` as substitutions, so
+    /// they're also fixed in text that wasn't corrected yet. If the length changed, the whole
+    /// text is substituted.
+    pub fn record_substitutions(&mut self, app: &str, original: &str, corrected: &str) {
+        let substitutions = &mut self.data.entry(app.to_string()).or_default().substitutions;
+        if original.chars().count() == corrected.chars().count() {
+            for (from, to) in original.chars().zip(corrected.chars()) {
+                if from != to {
+                    substitutions.insert(from.to_string(), to.to_string());
+                }
+            }
+        } else {
+            substitutions.insert(original.to_string(), corrected.to_string());
+        }
+        self.save();
+    }
+
+    pub fn apply(&self, app: &str, blocks: &mut [Block]) {
+        let Some(corrections) = self.data.get(app) else {
+            return;
+        };
+        for block in blocks {
+            corrections.apply(block);
+        }
+    }
+
+    fn save(&self) {
+        let data = serde_json::to_string(&self.data).unwrap();
+        if let Err(err) = std::fs::write(&self.path, data) {
+            log::error!("Failed to save corrections to {:?}: {err}", self.path);
+        }
+    }
+}
+
+pub fn load(path: impl AsRef<Path>) -> Corrections {
+    let path = path.as_ref();
+    let data = std::fs::read_to_string(path)
+        .ok()
+        .and_then(|data| serde_json::from_str(&data).ok())
+        .unwrap_or_default();
+    Corrections {
+        path: path.to_path_buf(),
+        data,
+    }
+}
+
+/// Replaces the text of the block containing `word` with the user's correction, remembers the
+/// correction for the current application and looks the word up again. With `always`, the
+/// changed characters are remembered as substitutions and fixed in every other block too.
+pub fn correct_block(
+    state: &mut LiveOcr,
+    word: &HoveredWord,
+    text: String,
+    always: bool,
+) -> Option<HoverEvent> {
+    let index = find_block(&state.definitions.ocr_strings, word)?;
+    let block = &state.definitions.ocr_strings[index];
+    if let Some(app) = &state.app {
+        if always {
+            state
+                .corrections
+                .record_substitutions(app, &block.text, &text);
+        } else {
+            state.corrections.record(app, &block.text, &text);
+        }
+    }
+    state.definitions.ocr_strings[index] = replace_text(block, text);
+    if let Some(app) = state.app.as_ref().filter(|_| always) {
+        // Fix the same misreads in the other blocks on screen
+        state
+            .corrections
+            .apply(app, &mut state.definitions.ocr_strings);
+    }
+    state.definitions.reindex();
+    collocation::record_ocr(state);
+
+    // The cursor is likely on the correction window by now, look up at the corrected word
+    reset_hover(state);
+    let center = word.rect.center();
+    update_hover(state, (center.x as i32, center.y as i32))
+}
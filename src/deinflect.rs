@@ -0,0 +1,238 @@
+//! Rule based deinflection of conjugated Japanese verbs and adjectives, based on the approach
+//! used by Yomichan. A conjugated form is reduced to candidate dictionary forms, which are only
+//! valid if the dictionary entry has a matching word class.
+
+use std::sync::OnceLock;
+
+/// Word class bit flags. A class of 0 means any, and is used for the original text.
+pub const ICHIDAN: u16 = 1 << 0;
+pub const GODAN: u16 = 1 << 1;
+pub const SURU: u16 = 1 << 2;
+pub const KURU: u16 = 1 << 3;
+pub const ADJ_I: u16 = 1 << 4;
+/// Nouns that take する, e.g. 勉強
+pub const SURU_NOUN: u16 = 1 << 5;
+/// て-form, which can be followed by auxiliaries like いる
+const TE: u16 = 1 << 6;
+
+/// Stems of each godan ending: (dictionary, い-row, あ-row, え-row, お-row, て-form, た-form)
+const GODAN_ROWS: &[[&str; 7]] = &[
+    ["く", "き", "か", "け", "こ", "いて", "いた"],
+    ["ぐ", "ぎ", "が", "げ", "ご", "いで", "いだ"],
+    ["す", "し", "さ", "せ", "そ", "して", "した"],
+    ["つ", "ち", "た", "て", "と", "って", "った"],
+    ["ぬ", "に", "な", "ね", "の", "んで", "んだ"],
+    ["ぶ", "び", "ば", "べ", "ぼ", "んで", "んだ"],
+    ["む", "み", "ま", "め", "も", "んで", "んだ"],
+    ["る", "り", "ら", "れ", "ろ", "って", "った"],
+    ["う", "い", "わ", "え", "お", "って", "った"],
+];
+
+struct Rule {
+    from: String,
+    to: String,
+    /// Classes the inflected form can have
+    classes_in: u16,
+    /// Class of the deinflected form
+    class_out: u16,
+    reason: &'static str,
+}
+
+/// A candidate dictionary form of the text.
+#[derive(Clone, Debug)]
+pub struct Deinflection {
+    pub term: String,
+    /// Word classes the dictionary entry needs to have, 0 for any
+    pub classes: u16,
+    /// Applied inflections, outermost last
+    pub reasons: Vec<&'static str>,
+}
+
+/// All candidate dictionary forms of `text`, including `text` itself.
+pub fn deinflect(text: &str) -> Vec<Deinflection> {
+    let mut results = vec![Deinflection {
+        term: text.to_string(),
+        classes: 0,
+        reasons: Vec::new(),
+    }];
+    let mut i = 0;
+    while i < results.len() {
+        let current = results[i].clone();
+        for rule in rules() {
+            if current.classes != 0 && current.classes & rule.classes_in == 0 {
+                continue;
+            }
+            let Some(stem) = current.term.strip_suffix(rule.from.as_str()) else {
+                continue;
+            };
+            if stem.is_empty() && rule.to.is_empty() {
+                continue;
+            }
+            let mut reasons = vec![rule.reason];
+            reasons.extend(&current.reasons);
+            results.push(Deinflection {
+                term: format!("{stem}{}", rule.to),
+                classes: rule.class_out,
+                reasons,
+            });
+        }
+        i += 1;
+    }
+    results
+}
+
+/// Whether an entry with the given JMdict part of speech tags has one of the word classes.
+pub fn has_class(classes: u16, part_of_speech: &[String]) -> bool {
+    classes == 0
+        || part_of_speech.iter().any(|pos| {
+            let class = match pos.as_str() {
+                "v1" | "v1-s" => ICHIDAN,
+                "vs-i" | "vs-s" => SURU,
+                "vs" => SURU_NOUN,
+                "vk" => KURU,
+                "adj-i" | "adj-ix" => ADJ_I,
+                pos if pos.starts_with("v5") => GODAN,
+                _ => 0,
+            };
+            classes & class != 0
+        })
+}
+
+fn rules() -> &'static [Rule] {
+    static RULES: OnceLock<Vec<Rule>> = OnceLock::new();
+    RULES.get_or_init(build_rules)
+}
+
+fn build_rules() -> Vec<Rule> {
+    let mut rules = Vec::new();
+    let mut add = |from: &str, to: &str, classes_in: u16, class_out: u16, reason| {
+        rules.push(Rule {
+            from: from.to_string(),
+            to: to.to_string(),
+            classes_in,
+            class_out,
+            reason,
+        })
+    };
+
+    // Ichidan
+    for (from, classes_in, reason) in [
+        ("た", 0, "past"),
+        ("て", TE, "te"),
+        ("ない", ADJ_I, "negative"),
+        ("ず", 0, "negative"),
+        ("ます", 0, "polite"),
+        ("ました", 0, "polite past"),
+        ("ません", 0, "polite negative"),
+        ("たい", ADJ_I, "want"),
+        ("られる", ICHIDAN, "passive/potential"),
+        ("させる", ICHIDAN, "causative"),
+        ("よう", 0, "volitional"),
+        ("れば", 0, "conditional"),
+        ("たら", 0, "conditional"),
+        ("ろ", 0, "imperative"),
+    ] {
+        add(from, "る", classes_in, ICHIDAN, reason);
+    }
+
+    // Godan
+    for [dict, i, a, e, o, te, ta] in GODAN_ROWS {
+        add(ta, dict, 0, GODAN, "past");
+        add(te, dict, TE, GODAN, "te");
+        add(&format!("{ta}ら"), dict, 0, GODAN, "conditional");
+        add(&format!("{a}ない"), dict, ADJ_I, GODAN, "negative");
+        add(&format!("{a}ず"), dict, 0, GODAN, "negative");
+        add(&format!("{i}ます"), dict, 0, GODAN, "polite");
+        add(&format!("{i}ました"), dict, 0, GODAN, "polite past");
+        add(&format!("{i}ません"), dict, 0, GODAN, "polite negative");
+        add(&format!("{i}たい"), dict, ADJ_I, GODAN, "want");
+        add(&format!("{a}れる"), dict, ICHIDAN, GODAN, "passive");
+        add(&format!("{a}せる"), dict, ICHIDAN, GODAN, "causative");
+        add(&format!("{e}る"), dict, ICHIDAN, GODAN, "potential");
+        add(&format!("{e}ば"), dict, 0, GODAN, "conditional");
+        add(e, dict, 0, GODAN, "imperative");
+        add(&format!("{o}う"), dict, 0, GODAN, "volitional");
+    }
+    // 行く has an irregular て-form and past
+    for stem in ["行", "い"] {
+        add(
+            &format!("{stem}った"),
+            &format!("{stem}く"),
+            0,
+            GODAN,
+            "past",
+        );
+        add(
+            &format!("{stem}って"),
+            &format!("{stem}く"),
+            TE,
+            GODAN,
+            "te",
+        );
+    }
+
+    // する
+    for (from, classes_in, reason) in [
+        ("した", 0, "past"),
+        ("して", TE, "te"),
+        ("しない", ADJ_I, "negative"),
+        ("せず", 0, "negative"),
+        ("します", 0, "polite"),
+        ("しました", 0, "polite past"),
+        ("しません", 0, "polite negative"),
+        ("したい", ADJ_I, "want"),
+        ("される", ICHIDAN, "passive"),
+        ("させる", ICHIDAN, "causative"),
+        ("できる", ICHIDAN, "potential"),
+        ("しよう", 0, "volitional"),
+        ("すれば", 0, "conditional"),
+        ("しろ", 0, "imperative"),
+    ] {
+        add(from, "する", classes_in, SURU, reason);
+    }
+    add("する", "", SURU, SURU_NOUN, "suru");
+
+    // 来る, written with the kanji or in kana
+    for (kanji_from, kana_from, classes_in, reason) in [
+        ("来た", "きた", 0, "past"),
+        ("来て", "きて", TE, "te"),
+        ("来ない", "こない", ADJ_I, "negative"),
+        ("来ます", "きます", 0, "polite"),
+        ("来ました", "きました", 0, "polite past"),
+        ("来られる", "こられる", ICHIDAN, "passive/potential"),
+        ("来させる", "こさせる", ICHIDAN, "causative"),
+        ("来よう", "こよう", 0, "volitional"),
+        ("来れば", "くれば", 0, "conditional"),
+    ] {
+        add(kanji_from, "来る", classes_in, KURU, reason);
+        add(kana_from, "くる", classes_in, KURU, reason);
+    }
+
+    // い-adjectives
+    for (from, classes_in, reason) in [
+        ("かった", 0, "past"),
+        ("くない", ADJ_I, "negative"),
+        ("くて", 0, "te"),
+        ("く", 0, "adverb"),
+        ("ければ", 0, "conditional"),
+        ("さ", 0, "noun"),
+        ("そう", 0, "seems"),
+    ] {
+        add(from, "い", classes_in, ADJ_I, reason);
+    }
+
+    // Auxiliaries following the て-form
+    for (from, to, classes_in, reason) in [
+        ("ている", "て", ICHIDAN, "progressive"),
+        ("てる", "て", ICHIDAN, "progressive"),
+        ("でいる", "で", ICHIDAN, "progressive"),
+        ("でる", "で", ICHIDAN, "progressive"),
+        ("てしまう", "て", GODAN, "completion"),
+        ("でしまう", "で", GODAN, "completion"),
+        ("てください", "て", 0, "request"),
+        ("でください", "で", 0, "request"),
+    ] {
+        add(from, to, classes_in, TE, reason);
+    }
+    rules
+}
@@ -1,14 +1,33 @@
-use std::path::Path;
+use std::{
+    cmp::{Ordering, Reverse},
+    collections::{hash_map::DefaultHasher, HashMap},
+    fs::File,
+    hash::{Hash, Hasher},
+    path::{Path, PathBuf},
+    thread::JoinHandle,
+};
 
 use bitcode::{Decode, Encode};
+use fst::{raw::Output, Map, MapBuilder};
 use itertools::Itertools;
+use memmap2::Mmap;
 use serde::{Deserialize, Deserializer, Serialize};
-use serde_repr::{Deserialize_repr, Serialize_repr};
-use trie_rs::map::Trie;
 use type_hash::TypeHash;
 
+pub use crate::pinyin::{Pinyin, Tone};
+use crate::{
+    cedict,
+    deinflect::{deinflect, has_class},
+    jmdict,
+    pinyin::parse_pinyin,
+    stardict, yomitan, Error, Result,
+};
+
 type CacheData = Vec<(String, Vec<DictionaryEntry>)>;
 
+/// Bumped when the cache format changes in ways the entry type hash doesn't catch
+const CACHE_VERSION: u32 = 5;
+
 #[derive(Serialize, Deserialize, Clone, Debug, Encode, Decode, TypeHash)]
 pub struct DictionaryEntry {
     pub simplified: String,
@@ -16,195 +35,878 @@ pub struct DictionaryEntry {
     #[serde(deserialize_with = "pinyin_deserialize")]
     pub pinyin: Vec<Pinyin>,
     pub translations: Vec<String>,
+    /// Whether this entry was found by substituting characters OCR commonly confuses
+    #[serde(default)]
+    pub fuzzy: bool,
+    /// Kana reading of Japanese entries
+    #[serde(default)]
+    pub reading: Option<String>,
+    /// JMdict part of speech tags, used to validate deinflections
+    #[serde(default)]
+    pub word_classes: Vec<String>,
+    /// Inflections that were undone to find this entry, e.g. `["te", "progressive"]`
+    #[serde(default)]
+    pub inflections: Vec<String>,
+    /// Descriptive tags, e.g. part of speech or usage notes
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Frequency from the dictionary's frequency data. Whether lower or higher is more common
+    /// depends on the dictionary.
+    #[serde(default)]
+    pub frequency: Option<u32>,
+    /// Name of the dictionary the entry was found in
+    #[serde(default)]
+    pub source: String,
+    /// Whether the user marked the word as known, see [`crate::known`]
+    #[serde(default)]
+    pub known: bool,
+    /// Frequency rank, 1 being the most common word. `None` if the word isn't in the
+    /// frequency data.
+    #[serde(default)]
+    pub rank: Option<u32>,
+    /// Proficiency levels the word is part of, e.g. `HSK 3` or `TOCFL 2`
+    #[serde(default)]
+    pub levels: Vec<String>,
+    /// Which form the looked up text matched. `None` if both forms are the same.
+    #[serde(default)]
+    pub matched_script: Option<Script>,
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug, Encode, Decode, TypeHash)]
-pub struct Pinyin {
-    pub tone: Tone,
-    pub syllable: String,
+/// Which of the two Chinese scripts is shown as the headword.
+#[derive(
+    Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Default, Encode, Decode, TypeHash,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum Script {
+    #[default]
+    Simplified,
+    /// For learners in Taiwan or Hong Kong
+    Traditional,
 }
 
-#[derive(Serialize_repr, Deserialize_repr, Encode, Decode, Clone, Debug, TypeHash, Copy)]
-#[repr(u8)]
-pub enum Tone {
-    First = 1,
-    Second = 2,
-    Third = 3,
-    Fourth = 4,
-    Fifth = 5,
-    None = 0,
-}
+impl DictionaryEntry {
+    /// The headword to show for `script`. Traditional headwords are followed by the simplified
+    /// form in parentheses if it differs, e.g. `學生 (学生)`.
+    pub fn headword(&self, script: Script) -> String {
+        match script {
+            Script::Traditional if self.traditional != self.simplified => {
+                format!("{} ({})", self.traditional, self.simplified)
+            }
+            Script::Traditional => self.traditional.clone(),
+            Script::Simplified => self.simplified.clone(),
+        }
+    }
 
-impl Tone {
-    pub fn from_u8(tone: u8) -> Self {
-        match tone {
-            1 => Self::First,
-            2 => Self::Second,
-            3 => Self::Third,
-            4 => Self::Fourth,
-            5 => Self::Fifth,
-            _ => panic!("Invalid tone number"),
+    /// Kana reading for Japanese entries, space separated pinyin otherwise.
+    pub fn pronunciation(&self) -> String {
+        match &self.reading {
+            Some(reading) => reading.clone(),
+            None => self
+                .pinyin
+                .iter()
+                .map(|it| it.syllable.as_str())
+                .collect::<Vec<_>>()
+                .join(" "),
         }
     }
+}
+
+/// Groups of characters that OCR commonly confuses with each other.
+const CONFUSABLES: &[&[char]] = &[
+    &['日', '目', '曰'],
+    &['天', '夭'],
+    &['未', '末'],
+    &['己', '已', '巳'],
+    &['土', '士'],
+    &['人', '入', '八'],
+    &['千', '干', '于'],
+    &['大', '太', '犬'],
+    &['口', '囗'],
+    &['戊', '戌', '戍'],
+    &['贝', '见'],
+    &['刀', '力'],
+    &['免', '兔'],
+    &['折', '拆'],
+    &['侯', '候'],
+    &['子', '孑', '孓'],
+    &['王', '玉'],
+    &['木', '本'],
+    &['乌', '鸟'],
+    &['问', '间'],
+    &['史', '吏'],
+    &['拨', '拔'],
+    &['持', '待'],
+    &['晴', '睛'],
+    &['辨', '辩', '辫'],
+    &['陪', '倍', '培'],
+    &['衷', '哀', '衰'],
+    // Kanji and katakana that look the same
+    &['一', 'ー'],
+    &['口', 'ロ'],
+    &['力', 'カ'],
+    &['二', 'ニ'],
+    &['八', 'ハ'],
+    &['工', 'エ'],
+    &['夕', 'タ'],
+];
+
+/// Name the user dictionary's entries are shown under
+pub const USER_DICTIONARY: &str = "User dictionary";
+
+/// Longest text deinflection is attempted on, in characters
+const MAX_DEINFLECT_LEN: usize = 12;
+
+/// Bytes of a cache file, memory-mapped when loaded from disk so the OS can page it in lazily
+/// and share it between processes.
+enum Bytes {
+    Mapped(Mmap),
+    Owned(Vec<u8>),
+}
 
-    pub fn apply(&self, tone_ch: char) -> char {
+impl AsRef<[u8]> for Bytes {
+    fn as_ref(&self) -> &[u8] {
         match self {
-            Tone::First => match tone_ch {
-                'ü' => 'ǖ',
-                'a' => 'ā',
-                'e' => 'ē',
-                'i' => 'ī',
-                'o' => 'ō',
-                'u' => 'ū',
-                _ => tone_ch,
-            },
-            Tone::Second => match tone_ch {
-                'ü' => 'ǘ',
-                'a' => 'á',
-                'e' => 'é',
-                'i' => 'í',
-                'o' => 'ó',
-                'm' => 'ḿ',
-                'u' => 'ú',
-                _ => tone_ch,
-            },
-            Tone::Third => match tone_ch {
-                'ü' => 'ǚ',
-                'a' => 'ǎ',
-                'e' => 'ě',
-                'i' => 'ǐ',
-                'o' => 'ǒ',
-                'u' => 'ǔ',
-                _ => tone_ch,
-            },
-            Tone::Fourth => match tone_ch {
-                'ü' => 'ǜ',
-                'm' => 'm',
-                'a' => 'à',
-                'e' => 'è',
-                'i' => 'ì',
-                'o' => 'ò',
-                'u' => 'ù',
-                _ => tone_ch,
-            },
-            _ => tone_ch,
+            Bytes::Mapped(map) => map,
+            Bytes::Owned(data) => data,
         }
     }
 }
 
+impl Bytes {
+    fn map(path: &Path) -> Result<Self> {
+        let file = File::open(path)?;
+        // SAFETY: cache files are never modified in place. They're only replaced by renaming a
+        // complete new file over them, or removed, both of which leave existing maps intact.
+        // See `write_atomically`.
+        Ok(Bytes::Mapped(unsafe { Mmap::map(&file)? }))
+    }
+}
+
+/// Headwords in an fst map to offsets into an arena of entry groups. Each group is stored as
+/// its length as a little endian `u32`, followed by the bitcode encoded entries, and is only
+/// decoded when looked up. Both are written to the cache once and memory-mapped afterwards, so
+/// loading a cached dictionary doesn't read or decode anything up front.
 pub struct Dictionary {
-    data: Trie<u8, Vec<DictionaryEntry>>,
+    index: Map<Bytes>,
+    entries: Bytes,
 }
 
 impl Dictionary {
+    pub fn build(entries: Vec<DictionaryEntry>) -> Self {
+        let (index, entries) = build_index(entries).expect("headwords are sorted and unique");
+        Self {
+            index: Map::new(Bytes::Owned(index)).expect("index was just built"),
+            entries: Bytes::Owned(entries),
+        }
+    }
+
+    /// Memory-maps a dictionary written with [`Dictionary::write`]. Fails if the arena doesn't
+    /// end where the index expects it to, i.e. the two files don't belong together.
+    fn open(index: &Path, entries: &Path) -> Result<Self> {
+        let dict = Self {
+            index: Map::new(Bytes::map(index)?)?,
+            entries: Bytes::map(entries)?,
+        };
+        if dict.arena_len() != Some(dict.entries.as_ref().len()) {
+            return Err(Error::CacheMismatch);
+        }
+        Ok(dict)
+    }
+
+    /// Writes the entries first and the index last, so an index only ever exists next to its
+    /// complete entries.
+    fn write(&self, index: &Path, entries: &Path) -> Result<()> {
+        write_atomically(entries, self.entries.as_ref())?;
+        write_atomically(index, self.index.as_fst().as_bytes())?;
+        Ok(())
+    }
+
+    /// Length the arena must have according to the index. Groups are stored in headword order,
+    /// so the last headword's group ends the arena. Only walks the path to the last headword.
+    fn arena_len(&self) -> Option<usize> {
+        let fst = self.index.as_fst();
+        let mut node = fst.root();
+        let mut output = Output::zero();
+        while node.len() > 0 {
+            let transition = node.transition(node.len() - 1);
+            output = output.cat(transition.out);
+            node = fst.node(transition.addr);
+        }
+        if !node.is_final() {
+            // No headwords at all
+            return Some(0);
+        }
+        let offset = output.cat(node.final_output()).value() as usize;
+        let len = self.entries.as_ref().get(offset..offset + 4)?;
+        Some(offset + 4 + u32::from_le_bytes(len.try_into().unwrap()) as usize)
+    }
+
+    /// The entry group stored at `offset` in the arena.
+    fn entries_at(&self, offset: u64) -> Vec<DictionaryEntry> {
+        let arena = self.entries.as_ref();
+        let start = offset as usize + 4;
+        let group = arena
+            .get(offset as usize..start)
+            .map(|len| u32::from_le_bytes(len.try_into().unwrap()) as usize)
+            .and_then(|len| arena.get(start..start + len));
+        match group.map(bitcode::decode) {
+            Some(Ok(entries)) => entries,
+            _ => {
+                log::error!("Corrupted dictionary entries at offset {offset}");
+                Vec::new()
+            }
+        }
+    }
+
+    /// Byte lengths and arena offsets of the headwords `text` starts with, shortest first.
+    fn common_prefixes(&self, text: &str) -> Vec<(usize, u64)> {
+        let fst = self.index.as_fst();
+        let mut node = fst.root();
+        let mut output = Output::zero();
+        let mut prefixes = Vec::new();
+        for (i, byte) in text.bytes().enumerate() {
+            let Some(transition) = node.find_input(byte) else {
+                break;
+            };
+            let transition = node.transition(transition);
+            output = output.cat(transition.out);
+            node = fst.node(transition.addr);
+            if node.is_final() {
+                prefixes.push((i + 1, output.cat(node.final_output()).value()));
+            }
+        }
+        prefixes
+    }
+
     pub fn matches(&self, text: &str) -> Vec<DictionaryEntry> {
         let mut matches = self
-            .data
-            .common_prefix_search(text)
-            .flat_map(|(_, value): (Vec<u8>, &Vec<DictionaryEntry>)| value.clone())
+            .surface_matches(text)
+            .into_iter()
+            .map(|(_, entry)| entry)
             .collect::<Vec<_>>();
         matches.sort_by_cached_key(|entry| entry.simplified.chars().count());
         matches.reverse();
         matches
     }
-}
 
-pub fn load(path: impl AsRef<Path>, cache_dir: impl AsRef<Path>) -> Dictionary {
-    log::info!("Loading data");
-    let path = path.as_ref();
-    let cache_dir = cache_dir.as_ref();
+    /// Length in characters of the longest match at the start of `text`, zero if nothing matches.
+    pub fn longest_match(&self, text: &str) -> usize {
+        self.surface_matches(text)
+            .into_iter()
+            .map(|(len, _)| len)
+            .max()
+            .unwrap_or(0)
+    }
 
-    if !cache_dir.exists() {
-        std::fs::create_dir_all(&cache_dir).unwrap();
+    /// Entries matching the start of `text`, along with how many characters of `text` each one
+    /// covers. Deinflected entries cover the conjugated form, not their headword.
+    fn surface_matches(&self, text: &str) -> Vec<(usize, DictionaryEntry)> {
+        let mut matches = self
+            .common_prefixes(text)
+            .into_iter()
+            .flat_map(|(len, offset)| {
+                let key = &text[..len];
+                let chars = key.chars().count();
+                self.entries_at(offset).into_iter().map(move |entry| {
+                    let entry = DictionaryEntry {
+                        matched_script: matched_script(&entry, key.as_bytes()),
+                        ..entry
+                    };
+                    (chars, entry)
+                })
+            })
+            .collect::<Vec<_>>();
+        if text.chars().any(is_kana) {
+            matches.extend(self.deinflected_matches(text));
+        }
+        matches
     }
-    let cache = cache_dir.join(format!("cedict.{:x}.bin", CacheData::type_hash()));
 
-    let data = if !cache.exists() {
-        std::fs::remove_dir_all(&cache_dir).unwrap();
+    /// Lengths in characters of the headwords `text` starts with.
+    pub fn prefix_lengths<'a>(&'a self, text: &'a str) -> impl Iterator<Item = usize> + 'a {
+        self.common_prefixes(text)
+            .into_iter()
+            .map(|(len, _)| text[..len].chars().count())
+    }
 
-        let data = std::fs::read_to_string(path).unwrap();
-        let data: Vec<DictionaryEntry> = serde_json::from_str(&data).unwrap();
-        let data = treeify(data);
+    /// Retries the lookup with one character at a time replaced by a visually similar one.
+    /// Only matches that include the replaced character and cover more than `longer_than`
+    /// characters are returned, flagged as `fuzzy`.
+    pub fn fuzzy_matches(&self, text: &str, longer_than: usize) -> Vec<DictionaryEntry> {
+        let chars = &text.chars().collect::<Vec<_>>();
+        let mut matches = chars
+            .iter()
+            .enumerate()
+            .flat_map(|(i, ch)| {
+                confusables(*ch).map(move |replacement| {
+                    let mut candidate = chars.clone();
+                    candidate[i] = replacement;
+                    (i, candidate.into_iter().collect::<String>())
+                })
+            })
+            .flat_map(|(i, candidate)| {
+                self.surface_matches(&candidate)
+                    .into_iter()
+                    .filter(move |(len, _)| *len > i.max(longer_than))
+            })
+            .map(|(_, entry)| DictionaryEntry {
+                fuzzy: true,
+                ..entry
+            })
+            .collect::<Vec<_>>();
+        matches.sort_by_cached_key(|entry| entry.simplified.chars().count());
+        matches.reverse();
+        matches
+    }
+
+    /// Looks up the dictionary forms of conjugated Japanese words at the start of `text`.
+    fn deinflected_matches(&self, text: &str) -> Vec<(usize, DictionaryEntry)> {
+        text.char_indices()
+            .take(MAX_DEINFLECT_LEN)
+            .enumerate()
+            .flat_map(|(len, (i, ch))| {
+                deinflect(&text[..i + ch.len_utf8()])
+                    .into_iter()
+                    .map(move |candidate| (len + 1, candidate))
+            })
+            .filter(|(_, candidate)| !candidate.reasons.is_empty())
+            .flat_map(|(len, candidate)| {
+                let classes = candidate.classes;
+                let inflections = candidate
+                    .reasons
+                    .iter()
+                    .map(|it| it.to_string())
+                    .collect::<Vec<_>>();
+                let entries = self
+                    .index
+                    .get(&candidate.term)
+                    .map(|offset| self.entries_at(offset));
+                entries
+                    .unwrap_or_default()
+                    .into_iter()
+                    .filter(move |entry| has_class(classes, &entry.word_classes))
+                    .map(move |entry| {
+                        let entry = DictionaryEntry {
+                            inflections: inflections.clone(),
+                            ..entry
+                        };
+                        (len, entry)
+                    })
+            })
+            .collect()
+    }
+}
 
-        // Write cached copy
-        let bitcoded = bitcode::encode(&data);
-        std::fs::create_dir_all(&cache_dir).unwrap();
-        std::fs::write(cache, bitcoded).unwrap();
+/// Builds the fst and entry arena of a [`Dictionary`].
+fn build_index(entries: Vec<DictionaryEntry>) -> Result<(Vec<u8>, Vec<u8>)> {
+    let mut index = MapBuilder::memory();
+    let mut arena = Vec::new();
+    for (key, entries) in treeify(entries) {
+        let encoded = bitcode::encode(&entries);
+        index.insert(key, arena.len() as u64)?;
+        arena.extend((encoded.len() as u32).to_le_bytes());
+        arena.extend(encoded);
+    }
+    Ok((index.into_inner()?, arena))
+}
 
-        data
+/// Which form of `entry` the headword `key` is.
+fn matched_script(entry: &DictionaryEntry, key: &[u8]) -> Option<Script> {
+    if entry.traditional == entry.simplified {
+        None
+    } else if entry.traditional.as_bytes() == key {
+        Some(Script::Traditional)
     } else {
-        let data = std::fs::read(cache).unwrap();
-        bitcode::decode(&data).unwrap()
-    };
-    log::info!("Data loaded. Building tree");
-    Dictionary {
-        data: Trie::from_iter(data),
+        Some(Script::Simplified)
     }
 }
 
-fn parse_pinyin(pinyin: &str) -> Vec<Pinyin> {
-    let syllables = pinyin.trim().split(' ');
-    syllables
-        .map(|it| {
-            if !it.ends_with(['1', '2', '3', '4', '5']) {
-                Pinyin {
-                    syllable: it.to_string(),
-                    tone: Tone::None,
+fn is_kana(ch: char) -> bool {
+    ('\u{3040}'..='\u{30ff}').contains(&ch)
+}
+
+fn confusables(ch: char) -> impl Iterator<Item = char> {
+    CONFUSABLES
+        .iter()
+        .filter(move |group| group.contains(&ch))
+        .flat_map(|group| group.iter().copied())
+        .filter(move |it| *it != ch)
+}
+
+/// Format of a dictionary file.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DictionaryFormat {
+    /// CC-CEDICT, either the official text format, see [`crate::cedict`], or converted to JSON
+    /// like the bundled `cedict.json`
+    Cedict,
+    /// JMdict in the jmdict-simplified JSON format, see [`crate::jmdict`]
+    Jmdict,
+    /// Yomitan/Yomichan dictionary zip, see [`crate::yomitan`]
+    Yomitan,
+    /// StarDict dictionary, given the path of its `.ifo` file, see [`crate::stardict`]
+    #[serde(rename = "stardict")]
+    StarDict,
+}
+
+/// A dictionary file and how to show it alongside other dictionaries.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct DictionarySource {
+    pub format: DictionaryFormat,
+    pub path: PathBuf,
+    /// Name shown above the dictionary's entries in the tooltip. Defaults to the file name.
+    #[serde(default)]
+    pub name: Option<String>,
+    /// Entries of dictionaries with a higher priority are listed first
+    #[serde(default)]
+    pub priority: i32,
+}
+
+impl DictionarySource {
+    pub fn new(format: DictionaryFormat, path: impl Into<PathBuf>) -> Self {
+        Self {
+            format,
+            path: path.into(),
+            name: None,
+            priority: 0,
+        }
+    }
+
+    pub fn with_name(self, name: impl Into<String>) -> Self {
+        Self {
+            name: Some(name.into()),
+            ..self
+        }
+    }
+
+    fn stem(&self) -> String {
+        self.path
+            .file_stem()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    fn display_name(&self) -> String {
+        self.name.clone().unwrap_or_else(|| self.stem())
+    }
+
+    /// Name of the cache file, unique per source. Includes a hash of the canonical path, since
+    /// dictionaries in different folders can have the same file name.
+    fn cache_name(&self) -> String {
+        let format = match self.format {
+            DictionaryFormat::Cedict => "cedict",
+            DictionaryFormat::Jmdict => "jmdict",
+            DictionaryFormat::Yomitan => "yomitan",
+            DictionaryFormat::StarDict => "stardict",
+        };
+        let path = std::fs::canonicalize(&self.path).unwrap_or_else(|_| self.path.clone());
+        let mut hasher = DefaultHasher::new();
+        path.hash(&mut hasher);
+        format!("{format}-{}-{:x}", self.stem(), hasher.finish())
+    }
+
+    /// Files the dictionary is read from. StarDict dictionaries are spread over several.
+    fn files(&self) -> Vec<PathBuf> {
+        match self.format {
+            DictionaryFormat::StarDict => stardict::files(&self.path),
+            _ => vec![self.path.clone()],
+        }
+    }
+
+    fn parse(&self) -> Result<Vec<DictionaryEntry>> {
+        let path = &self.path;
+        match self.format {
+            DictionaryFormat::Cedict => {
+                let data = std::fs::read_to_string(path)?;
+                if cedict::is_native(&data) {
+                    Ok(cedict::parse(&data))
+                } else {
+                    Ok(serde_json::from_str(&data)?)
                 }
-            } else {
-                let tone = Tone::from_u8(it.chars().last().unwrap().to_string().parse().unwrap());
-                let syllable = normalize_syllable(&it);
-                let syllable = apply_tone(&syllable, tone);
-                Pinyin { syllable, tone }
             }
-        })
-        .collect()
+            DictionaryFormat::Jmdict => Ok(jmdict::parse(&std::fs::read_to_string(path)?)?),
+            DictionaryFormat::Yomitan => yomitan::parse(path),
+            DictionaryFormat::StarDict => stardict::parse(path),
+        }
+    }
 }
 
-fn normalize_syllable(syllable: &str) -> String {
-    syllable
-        .to_lowercase()
-        .replacen("u:", "ü", 1)
-        .replacen("v", "ü", 1)
-        .replacen(char::is_numeric, "", 1)
+/// Several dictionaries looked up together. Results are grouped per dictionary, highest
+/// priority first.
+pub struct DictionaryGroup {
+    dictionaries: Vec<(String, Dictionary)>,
+    /// Frequency rank by word, 1 being the most common
+    ranks: HashMap<String, u32>,
+    /// Proficiency levels by word
+    levels: HashMap<String, Vec<String>>,
+    /// Idiom origin stories by headword
+    idioms: HashMap<String, IdiomStory>,
 }
 
-fn apply_tone(syllable: &str, tone: Tone) -> String {
-    let vowels = find_vowels(syllable);
-    let (tonal_letter_index, tonal_letter) = if vowels.is_empty() {
-        syllable.char_indices().next().unwrap()
-    } else if vowels.len() == 1 {
-        *vowels.first().unwrap()
-    } else {
-        const PREFERENTIAL_VOWELS: &[char] = &['a', 'e', 'o'];
-        if let Some(character) = vowels
+impl DictionaryGroup {
+    /// Replaces the user's own entries, which are looked up before every other dictionary. See
+    /// [`crate::user_dict`].
+    pub fn set_user_entries(&mut self, entries: Vec<DictionaryEntry>) {
+        self.dictionaries
+            .retain(|(name, _)| name != USER_DICTIONARY);
+        if !entries.is_empty() {
+            self.dictionaries
+                .insert(0, (USER_DICTIONARY.to_string(), Dictionary::build(entries)));
+        }
+    }
+
+    /// Ranks matches by a frequency list, see [`load_frequency_list`].
+    pub fn set_frequency_ranks(&mut self, ranks: HashMap<String, u32>) {
+        self.ranks = ranks;
+    }
+
+    /// Tags matches with proficiency levels, see [`load_level_list`].
+    pub fn add_levels(&mut self, levels: HashMap<String, Vec<String>>) {
+        for (word, levels) in levels {
+            self.levels.entry(word).or_default().extend(levels);
+        }
+    }
+
+    /// Adds origin stories for idioms, see [`load_idiom_pack`].
+    pub fn add_idioms(&mut self, idioms: HashMap<String, IdiomStory>) {
+        self.idioms.extend(idioms);
+    }
+
+    /// The origin story of an idiom, looked up by either form.
+    pub fn idiom_story(&self, entry: &DictionaryEntry) -> Option<&IdiomStory> {
+        self.idioms
+            .get(&entry.simplified)
+            .or_else(|| self.idioms.get(&entry.traditional))
+    }
+
+    pub fn matches(&self, text: &str) -> Vec<DictionaryEntry> {
+        self.collect(|dict| dict.matches(text))
+    }
+
+    /// Lengths of the headwords `text` starts with, across all dictionaries. Unsorted and may
+    /// contain duplicates.
+    pub fn prefix_lengths(&self, text: &str) -> Vec<usize> {
+        self.dictionaries
+            .iter()
+            .flat_map(|(_, dict)| dict.prefix_lengths(text))
+            .collect()
+    }
+
+    /// See [`Dictionary::longest_match`]
+    pub fn longest_match(&self, text: &str) -> usize {
+        self.dictionaries
             .iter()
-            .find(|(_, ch)| PREFERENTIAL_VOWELS.contains(ch))
+            .map(|(_, dict)| dict.longest_match(text))
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// See [`Dictionary::fuzzy_matches`]
+    pub fn fuzzy_matches(&self, text: &str, longer_than: usize) -> Vec<DictionaryEntry> {
+        self.collect(|dict| dict.fuzzy_matches(text, longer_than))
+    }
+
+    /// Proficiency levels of `entry` under either form. Lists like TOCFL use traditional
+    /// characters.
+    fn levels_of(&self, entry: &DictionaryEntry) -> Vec<String> {
+        let mut levels = Vec::<String>::new();
+        let forms = [&entry.simplified, &entry.traditional];
+        for level in forms
+            .into_iter()
+            .filter_map(|form| self.levels.get(form))
+            .flatten()
         {
-            *character
-        } else {
-            vowels.into_iter().nth(1).unwrap()
+            if !levels.contains(level) {
+                levels.push(level.clone());
+            }
         }
+        levels
+    }
+
+    fn collect(
+        &self,
+        lookup: impl Fn(&Dictionary) -> Vec<DictionaryEntry>,
+    ) -> Vec<DictionaryEntry> {
+        self.dictionaries
+            .iter()
+            .flat_map(|(name, dict)| {
+                let mut matches = lookup(dict)
+                    .into_iter()
+                    .map(|entry| DictionaryEntry {
+                        source: name.clone(),
+                        // Ranks from the dictionary's own frequency data, see `yomitan::parse`
+                        rank: self.ranks.get(&entry.simplified).copied().or(entry.rank),
+                        levels: self.levels_of(&entry),
+                        ..entry
+                    })
+                    .collect::<Vec<_>>();
+                // More common words go first, so obscure archaic terms don't outrank them.
+                // Words missing from the frequency data are mostly rare, so they go last, and
+                // the longest match breaks ties.
+                matches.sort_by_cached_key(|entry| {
+                    (
+                        entry.rank.is_none(),
+                        entry.rank,
+                        Reverse(entry.simplified.chars().count()),
+                    )
+                });
+                matches
+            })
+            .collect()
+    }
+}
+
+/// Loads a word list for a proficiency test. Each line is a word and its level separated by a
+/// tab, e.g. `学生\tHSK 1`. A word can be listed more than once, e.g. in different tests.
+pub fn load_level_list(path: impl AsRef<Path>) -> Result<HashMap<String, Vec<String>>> {
+    let data = std::fs::read_to_string(path)?;
+    let mut levels = HashMap::<String, Vec<String>>::new();
+    for line in data.lines() {
+        let Some((word, level)) = line.split_once('\t') else {
+            continue;
+        };
+        levels
+            .entry(word.trim().to_string())
+            .or_default()
+            .push(level.trim().to_string());
+    }
+    log::info!("Loaded proficiency levels for {} words", levels.len());
+    Ok(levels)
+}
+
+/// Explanation of where an idiom comes from.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct IdiomStory {
+    /// The story or etymology behind the idiom
+    pub story: String,
+    /// Literal meaning of the characters, which often differs from the figurative one
+    #[serde(default)]
+    pub literal: Option<String>,
+    /// The classical text the idiom comes from, e.g. `《战国策》`
+    #[serde(default)]
+    pub source: Option<String>,
+}
+
+/// Loads an idiom data pack, a JSON object of origin stories keyed by headword, e.g.
+/// `{"画蛇添足": {"story": "...", "source": "《战国策》"}}`.
+pub fn load_idiom_pack(path: impl AsRef<Path>) -> Result<HashMap<String, IdiomStory>> {
+    let idioms: HashMap<String, IdiomStory> =
+        serde_json::from_str(&std::fs::read_to_string(path)?)?;
+    log::info!("Loaded origin stories for {} idioms", idioms.len());
+    Ok(idioms)
+}
+
+/// Loads a word frequency list as ranks, e.g. SUBTLEX-CH converted to UTF-8. Each line starts
+/// with a word, optionally followed by its count. Words are ranked by count if there is one,
+/// otherwise the list is assumed to be sorted most common first.
+pub fn load_frequency_list(path: impl AsRef<Path>) -> Result<HashMap<String, u32>> {
+    let data = std::fs::read_to_string(path)?;
+    let mut words = data
+        .lines()
+        .filter_map(|line| {
+            let mut columns = line.split_whitespace();
+            let word = columns.next()?;
+            let count = columns.next().and_then(|it| it.parse::<f64>().ok());
+            Some((word, count))
+        })
+        .collect::<Vec<_>>();
+    if words.iter().all(|(_, count)| count.is_some()) {
+        words.sort_by(|(_, a), (_, b)| b.partial_cmp(a).unwrap_or(Ordering::Equal));
+    }
+    let mut ranks = HashMap::new();
+    for (rank, (word, _)) in words.into_iter().enumerate() {
+        ranks.entry(word.to_string()).or_insert(rank as u32 + 1);
+    }
+    log::info!("Loaded {} word frequencies", ranks.len());
+    Ok(ranks)
+}
+
+pub fn load(path: impl AsRef<Path>, cache_dir: impl AsRef<Path>) -> Result<DictionaryGroup> {
+    let source = DictionarySource::new(DictionaryFormat::Cedict, path.as_ref());
+    load_group(&[source], cache_dir)
+}
+
+/// What loading a dictionary group is busy with, see [`load_in_background`].
+#[derive(Serialize, Clone, Debug)]
+pub struct LoadProgress {
+    /// Name of the dictionary being loaded
+    pub dictionary: String,
+    pub stage: LoadStage,
+    /// Dictionaries finished so far
+    pub loaded: usize,
+    pub total: usize,
+}
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum LoadStage {
+    OpeningCache,
+    /// Reading the source file, only when there's no cache for it yet
+    Parsing,
+    /// Grouping entries by headword and building the index
+    Indexing,
+    WritingCache,
+    /// Every dictionary is loaded
+    Done,
+}
+
+/// A dictionary group loading on a background thread.
+pub struct DictionaryLoader {
+    thread: JoinHandle<Result<DictionaryGroup>>,
+}
+
+impl DictionaryLoader {
+    /// Blocks until loading finishes.
+    pub fn wait(self) -> Result<DictionaryGroup> {
+        self.thread
+            .join()
+            .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+    }
+}
+
+/// Starts loading `sources` like [`load_group`] and returns immediately. `progress` is called
+/// on the loading thread whenever a dictionary moves to the next stage.
+pub fn load_in_background(
+    sources: Vec<DictionarySource>,
+    cache_dir: PathBuf,
+    mut progress: impl FnMut(LoadProgress) + Send + 'static,
+) -> DictionaryLoader {
+    let thread =
+        std::thread::spawn(move || load_group_with_progress(&sources, cache_dir, &mut progress));
+    DictionaryLoader { thread }
+}
+
+/// Loads each source into its own dictionary, ordered by priority. Sources with the same
+/// priority keep their order. Sources that fail to load are skipped, unless none of them load.
+pub fn load_group(
+    sources: &[DictionarySource],
+    cache_dir: impl AsRef<Path>,
+) -> Result<DictionaryGroup> {
+    load_group_with_progress(sources, cache_dir, &mut |_| {})
+}
+
+fn load_group_with_progress(
+    sources: &[DictionarySource],
+    cache_dir: impl AsRef<Path>,
+    progress: &mut dyn FnMut(LoadProgress),
+) -> Result<DictionaryGroup> {
+    let cache_dir = cache_dir.as_ref();
+    let total = sources.len();
+    let mut dictionaries = Vec::new();
+    for (loaded, source) in sources.iter().enumerate() {
+        let mut report = |stage: LoadStage| {
+            progress(LoadProgress {
+                dictionary: source.display_name(),
+                stage,
+                loaded,
+                total,
+            })
+        };
+        match load_cached(source, cache_dir, &mut report) {
+            Ok(dict) => dictionaries.push((source.priority, source.display_name(), dict)),
+            Err(err) => log::error!("Failed to load dictionary {:?}: {err}", source.path),
+        }
+    }
+    progress(LoadProgress {
+        dictionary: String::new(),
+        stage: LoadStage::Done,
+        loaded: total,
+        total,
+    });
+    if dictionaries.is_empty() {
+        return Err(Error::NoDictionary);
+    }
+    dictionaries.sort_by_key(|(priority, _, _)| Reverse(*priority));
+    Ok(DictionaryGroup {
+        dictionaries: dictionaries
+            .into_iter()
+            .map(|(_, name, dict)| (name, dict))
+            .collect(),
+        ranks: HashMap::new(),
+        levels: HashMap::new(),
+        idioms: HashMap::new(),
+    })
+}
+
+fn load_cached(
+    source: &DictionarySource,
+    cache_dir: &Path,
+    report: &mut dyn FnMut(LoadStage),
+) -> Result<Dictionary> {
+    let name = source.cache_name();
+    log::info!("Loading {name}");
+
+    if !cache_dir.exists() {
+        std::fs::create_dir_all(&cache_dir)?;
+    }
+    let key = cache_key(&source.files());
+    let index = cache_dir.join(format!("{name}.{key:x}.fst"));
+    let entries = cache_dir.join(format!("{name}.{key:x}.entries"));
+
+    if index.exists() && entries.exists() {
+        report(LoadStage::OpeningCache);
+        match Dictionary::open(&index, &entries) {
+            Ok(dict) => return Ok(dict),
+            Err(err) => log::warn!("Rebuilding corrupted cache of {name}: {err}"),
+        }
+    }
+
+    remove_stale_caches(&name, cache_dir);
+    report(LoadStage::Parsing);
+    let parsed = source.parse()?;
+    log::info!("Data loaded. Building index");
+    report(LoadStage::Indexing);
+    let dict = Dictionary::build(parsed);
+    report(LoadStage::WritingCache);
+    // Another running instance may still have the old cache mapped, which can keep it from
+    // being replaced on Windows. The dictionary works without a cache either way.
+    if let Err(err) = dict.write(&index, &entries) {
+        log::warn!("Failed to write the cache of {name}: {err}");
+    }
+    Ok(dict)
+}
+
+/// Writes `data` to a temporary file next to `path` and renames it into place, so other
+/// processes never see, or have mapped, a partially written file.
+fn write_atomically(path: &Path, data: &[u8]) -> Result<()> {
+    let mut temp = path.as_os_str().to_owned();
+    temp.push(format!(".{}.tmp", std::process::id()));
+    let temp = PathBuf::from(temp);
+    std::fs::write(&temp, data)?;
+    if let Err(err) = std::fs::rename(&temp, path) {
+        let _ = std::fs::remove_file(&temp);
+        return Err(err.into());
+    }
+    Ok(())
+}
+
+/// Changes whenever the cached data layout or any of the source files change.
+fn cache_key(files: &[PathBuf]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    CacheData::type_hash().hash(&mut hasher);
+    CACHE_VERSION.hash(&mut hasher);
+    for path in files {
+        path.hash(&mut hasher);
+        if let Ok(metadata) = std::fs::metadata(path) {
+            metadata.len().hash(&mut hasher);
+            metadata.modified().ok().hash(&mut hasher);
+        }
+    }
+    hasher.finish()
+}
+
+/// Removes caches of `name` written for an older version of the source or data layout.
+fn remove_stale_caches(name: &str, cache_dir: &Path) {
+    let prefix = format!("{name}.");
+    let Ok(entries) = std::fs::read_dir(cache_dir) else {
+        return;
     };
-    let replacement = tone.apply(tonal_letter);
-    let mut syllable = syllable.to_owned();
-    syllable.replace_range(
-        syllable
-            .char_indices()
-            .nth(tonal_letter_index)
-            .map(|(pos, ch)| (pos..pos + ch.len_utf8()))
-            .unwrap(),
-        &replacement.to_string(),
-    );
-    syllable
-}
-
-fn find_vowels(syllable: &str) -> Vec<(usize, char)> {
-    const VOWELS: &[char] = &['a', 'e', 'i', 'o', 'u', 'ü'];
-    syllable
-        .char_indices()
-        .filter(|(_, ch)| VOWELS.contains(ch))
-        .collect()
+    for entry in entries.flatten() {
+        if entry.file_name().to_string_lossy().starts_with(&prefix) {
+            let _ = std::fs::remove_file(entry.path());
+        }
+    }
 }
 
 fn pinyin_deserialize<'de, D>(deserializer: D) -> Result<Vec<Pinyin>, D::Error>
@@ -215,13 +917,121 @@ where
     Ok(parse_pinyin(string))
 }
 
-fn treeify(mut data: Vec<DictionaryEntry>) -> CacheData {
-    data.sort_by_cached_key(|entry| entry.simplified.to_string());
-    let grouped = data
+/// Groups entries by headword. Entries with a different traditional form are listed under
+/// both forms, so text in either script finds them.
+fn treeify(data: Vec<DictionaryEntry>) -> CacheData {
+    let mut keyed = data
         .into_iter()
-        .chunk_by(|entry| entry.simplified.to_string());
+        .flat_map(|entry| {
+            let traditional = (!entry.traditional.is_empty()
+                && entry.traditional != entry.simplified)
+                .then(|| (entry.traditional.clone(), entry.clone()));
+            [(entry.simplified.clone(), entry)]
+                .into_iter()
+                .chain(traditional)
+        })
+        .collect::<Vec<_>>();
+    keyed.sort_by(|(a, _), (b, _)| a.cmp(b));
+    let grouped = keyed.into_iter().chunk_by(|(key, _)| key.clone());
     grouped
         .into_iter()
-        .map(|(key, entries)| (key, entries.collect()))
+        .map(|(key, entries)| (key, entries.map(|(_, entry)| entry).collect()))
         .collect()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(simplified: &str, traditional: &str, translation: &str) -> DictionaryEntry {
+        DictionaryEntry {
+            simplified: simplified.to_string(),
+            traditional: traditional.to_string(),
+            pinyin: Vec::new(),
+            translations: vec![translation.to_string()],
+            fuzzy: false,
+            reading: None,
+            word_classes: Vec::new(),
+            inflections: Vec::new(),
+            tags: Vec::new(),
+            frequency: None,
+            source: String::new(),
+            known: false,
+            rank: None,
+            levels: Vec::new(),
+            matched_script: None,
+        }
+    }
+
+    /// A fresh directory for the files of one test.
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("live-ocrs-dict-{}-{name}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn test_dictionary() -> Dictionary {
+        Dictionary::build(vec![
+            entry("中国", "中國", "China"),
+            entry("中", "中", "middle"),
+            entry("你好", "你好", "hello"),
+        ])
+    }
+
+    #[test]
+    fn round_trips_through_the_cache() {
+        let dir = temp_dir("round-trip");
+        let (index, entries) = (dir.join("test.fst"), dir.join("test.entries"));
+        test_dictionary().write(&index, &entries).unwrap();
+        let dict = Dictionary::open(&index, &entries).unwrap();
+
+        let matches = dict.matches("中國人");
+        assert_eq!(matches.len(), 2);
+        assert_eq!(matches[0].simplified, "中国");
+        assert_eq!(matches[0].translations, ["China"]);
+        assert_eq!(matches[0].matched_script, Some(Script::Traditional));
+        assert_eq!(matches[1].translations, ["middle"]);
+        assert_eq!(dict.longest_match("你好吗"), 2);
+        assert!(dict.matches("吗").is_empty());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn rejects_mismatched_cache_files() {
+        let dir = temp_dir("mismatch");
+        let (index, entries) = (dir.join("test.fst"), dir.join("test.entries"));
+        test_dictionary().write(&index, &entries).unwrap();
+        let arena = std::fs::read(&entries).unwrap();
+        std::fs::write(&entries, &arena[..arena.len() - 1]).unwrap();
+        assert!(matches!(
+            Dictionary::open(&index, &entries),
+            Err(Error::CacheMismatch)
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn cache_key_changes_with_the_source() {
+        let path = temp_dir("key").join("cedict.txt");
+        std::fs::write(&path, "中國 中国 [Zhong1 guo2] /China/\n").unwrap();
+        let files = DictionarySource::new(DictionaryFormat::Cedict, &path).files();
+        let key = cache_key(&files);
+        assert_eq!(cache_key(&files), key);
+        std::fs::write(&path, "中國 中国 [Zhong1 guo2] /China/Middle Kingdom/\n").unwrap();
+        assert_ne!(cache_key(&files), key);
+    }
+
+    #[test]
+    fn stardict_cache_key_tracks_every_file() {
+        let ifo = temp_dir("stardict-key").join("test.ifo");
+        std::fs::write(&ifo, "StarDict's dict ifo file\nsametypesequence=m\n").unwrap();
+        std::fs::write(ifo.with_extension("idx"), b"hello\0\0\0\0\0\0\0\0\x05").unwrap();
+        std::fs::write(ifo.with_extension("dict"), "hello").unwrap();
+        let source = DictionarySource::new(DictionaryFormat::StarDict, &ifo);
+        let key = cache_key(&source.files());
+        std::fs::write(ifo.with_extension("dict"), "hello, world").unwrap();
+        assert_ne!(cache_key(&source.files()), key);
+    }
+}
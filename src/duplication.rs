@@ -0,0 +1,237 @@
+//! Monitor capture through DXGI Desktop Duplication on Windows. Keeps a duplication session
+//! open between captures, which is much faster than taking a screenshot every time, reports
+//! which parts of the screen were redrawn, and can tell when nothing was redrawn at all.
+
+use std::{mem::size_of, slice};
+
+use geo::{coord, Rect};
+use image::RgbaImage;
+use windows::{
+    core::Interface as _,
+    Win32::{
+        Foundation::{E_FAIL, HMODULE, RECT},
+        Graphics::{
+            Direct3D::D3D_DRIVER_TYPE_UNKNOWN,
+            Direct3D11::{
+                D3D11CreateDevice, ID3D11Device, ID3D11DeviceContext, ID3D11Texture2D,
+                D3D11_CPU_ACCESS_READ, D3D11_CREATE_DEVICE_BGRA_SUPPORT, D3D11_MAPPED_SUBRESOURCE,
+                D3D11_MAP_READ, D3D11_SDK_VERSION, D3D11_TEXTURE2D_DESC, D3D11_USAGE_STAGING,
+            },
+            Dxgi::{
+                Common::DXGI_MODE_ROTATION_IDENTITY, CreateDXGIFactory1, IDXGIAdapter1,
+                IDXGIFactory1, IDXGIOutput, IDXGIOutput1, IDXGIOutputDuplication, IDXGIResource,
+                DXGI_ERROR_WAIT_TIMEOUT, DXGI_OUTDUPL_FRAME_INFO,
+            },
+        },
+    },
+};
+
+use crate::capture::CaptureError;
+
+/// A frame from desktop duplication.
+pub struct DuplicatedFrame {
+    pub image: RgbaImage,
+    /// Screen space position of the frame's top left corner
+    pub origin: (i32, i32),
+    /// Regions redrawn since the previous frame, in frame pixels
+    pub dirty: Vec<Rect<f32>>,
+}
+
+/// A desktop duplication session for one monitor.
+pub struct Duplication {
+    context: ID3D11DeviceContext,
+    device: ID3D11Device,
+    duplication: IDXGIOutputDuplication,
+    /// CPU readable copy of the desktop texture, recreated when its size changes
+    staging: Option<(ID3D11Texture2D, (u32, u32))>,
+    origin: (i32, i32),
+}
+
+// The device is free threaded, and the immediate context and duplication are only used through
+// `&mut self`, so never from two threads at once.
+unsafe impl Send for Duplication {}
+
+impl Duplication {
+    /// Starts duplicating the monitor whose top left corner is at `origin` in screen space.
+    /// Fails for rotated monitors, which come through unrotated, and on systems without a
+    /// hardware adapter, e.g. some remote desktop sessions.
+    pub fn new(origin: (i32, i32)) -> Result<Self, CaptureError> {
+        let (adapter, output) = unsafe { find_output(origin) }.map_err(to_capture_error)?;
+        let mut device = None;
+        let mut context = None;
+        unsafe {
+            D3D11CreateDevice(
+                &adapter,
+                D3D_DRIVER_TYPE_UNKNOWN,
+                HMODULE::default(),
+                D3D11_CREATE_DEVICE_BGRA_SUPPORT,
+                None,
+                D3D11_SDK_VERSION,
+                Some(&mut device),
+                None,
+                Some(&mut context),
+            )
+        }
+        .map_err(to_capture_error)?;
+        let (Some(device), Some(context)) = (device, context) else {
+            return Err(CaptureError::Capture(
+                "Failed to create a Direct3D device".to_string(),
+            ));
+        };
+        let duplication = output
+            .cast::<IDXGIOutput1>()
+            .and_then(|output| unsafe { output.DuplicateOutput(&device) })
+            .map_err(to_capture_error)?;
+        Ok(Self {
+            context,
+            device,
+            duplication,
+            staging: None,
+            origin,
+        })
+    }
+
+    pub fn origin(&self) -> (i32, i32) {
+        self.origin
+    }
+
+    /// The current desktop image, or `None` if nothing was redrawn since the last call. Fails
+    /// if the session was lost, e.g. after a display mode change or while the secure desktop
+    /// is shown, after which a new one has to be started.
+    pub fn next_frame(&mut self) -> Result<Option<DuplicatedFrame>, CaptureError> {
+        let mut info = DXGI_OUTDUPL_FRAME_INFO::default();
+        let mut resource: Option<IDXGIResource> = None;
+        match unsafe {
+            self.duplication
+                .AcquireNextFrame(0, &mut info, &mut resource)
+        } {
+            Ok(()) => {}
+            Err(err) if err.code() == DXGI_ERROR_WAIT_TIMEOUT => return Ok(None),
+            Err(err) => return Err(to_capture_error(err)),
+        }
+        let frame = self.read_frame(&info, resource);
+        unsafe { self.duplication.ReleaseFrame() }.map_err(to_capture_error)?;
+        frame
+    }
+
+    fn read_frame(
+        &mut self,
+        info: &DXGI_OUTDUPL_FRAME_INFO,
+        resource: Option<IDXGIResource>,
+    ) -> Result<Option<DuplicatedFrame>, CaptureError> {
+        // Only the cursor moved
+        if info.LastPresentTime == 0 {
+            return Ok(None);
+        }
+        let Some(resource) = resource else {
+            return Ok(None);
+        };
+        let texture = resource
+            .cast::<ID3D11Texture2D>()
+            .map_err(to_capture_error)?;
+        let dirty = unsafe { self.dirty_rects(info.TotalMetadataBufferSize) }?;
+        let image = unsafe { self.copy_to_image(&texture) }.map_err(to_capture_error)?;
+        Ok(Some(DuplicatedFrame {
+            image,
+            origin: self.origin,
+            dirty,
+        }))
+    }
+
+    unsafe fn dirty_rects(&self, buffer_size: u32) -> Result<Vec<Rect<f32>>, CaptureError> {
+        let mut rects = vec![RECT::default(); buffer_size as usize / size_of::<RECT>()];
+        let mut used = 0;
+        if !rects.is_empty() {
+            self.duplication
+                .GetFrameDirtyRects(
+                    (rects.len() * size_of::<RECT>()) as u32,
+                    rects.as_mut_ptr(),
+                    &mut used,
+                )
+                .map_err(to_capture_error)?;
+        }
+        rects.truncate(used as usize / size_of::<RECT>());
+        Ok(rects
+            .into_iter()
+            .map(|rect| {
+                Rect::new(
+                    coord![x: rect.left as f32, y: rect.top as f32],
+                    coord![x: rect.right as f32, y: rect.bottom as f32],
+                )
+            })
+            .collect())
+    }
+
+    /// Copies the GPU desktop texture to memory, converting it from BGRA to RGBA.
+    unsafe fn copy_to_image(
+        &mut self,
+        texture: &ID3D11Texture2D,
+    ) -> windows::core::Result<RgbaImage> {
+        let mut desc = D3D11_TEXTURE2D_DESC::default();
+        texture.GetDesc(&mut desc);
+        let size = (desc.Width, desc.Height);
+        let staging = match &self.staging {
+            Some((staging, staging_size)) if *staging_size == size => staging.clone(),
+            _ => {
+                let staging_desc = D3D11_TEXTURE2D_DESC {
+                    Usage: D3D11_USAGE_STAGING,
+                    BindFlags: 0,
+                    CPUAccessFlags: D3D11_CPU_ACCESS_READ.0 as u32,
+                    MiscFlags: 0,
+                    MipLevels: 1,
+                    ArraySize: 1,
+                    ..desc
+                };
+                let mut staging = None;
+                self.device
+                    .CreateTexture2D(&staging_desc, None, Some(&mut staging))?;
+                let staging = staging.ok_or_else(windows::core::Error::empty)?;
+                self.staging = Some((staging.clone(), size));
+                staging
+            }
+        };
+
+        self.context.CopyResource(&staging, texture);
+        let mut mapped = D3D11_MAPPED_SUBRESOURCE::default();
+        self.context
+            .Map(&staging, 0, D3D11_MAP_READ, 0, Some(&mut mapped))?;
+        let (width, height) = size;
+        let data = slice::from_raw_parts(
+            mapped.pData as *const u8,
+            mapped.RowPitch as usize * height as usize,
+        );
+        let mut pixels = Vec::with_capacity(width as usize * height as usize * 4);
+        for row in data.chunks_exact(mapped.RowPitch as usize) {
+            for bgra in row[..width as usize * 4].chunks_exact(4) {
+                pixels.extend_from_slice(&[bgra[2], bgra[1], bgra[0], 255]);
+            }
+        }
+        self.context.Unmap(&staging, 0);
+        RgbaImage::from_raw(width, height, pixels).ok_or_else(windows::core::Error::empty)
+    }
+}
+
+/// The adapter and output showing the monitor at `origin`.
+unsafe fn find_output(origin: (i32, i32)) -> windows::core::Result<(IDXGIAdapter1, IDXGIOutput)> {
+    let factory = CreateDXGIFactory1::<IDXGIFactory1>()?;
+    for adapter in (0..).map_while(|i| factory.EnumAdapters1(i).ok()) {
+        for output in (0..).map_while(|i| adapter.EnumOutputs(i).ok()) {
+            let desc = output.GetDesc()?;
+            let rect = desc.DesktopCoordinates;
+            if desc.AttachedToDesktop.as_bool()
+                && desc.Rotation == DXGI_MODE_ROTATION_IDENTITY
+                && (rect.left, rect.top) == origin
+            {
+                return Ok((adapter, output));
+            }
+        }
+    }
+    Err(windows::core::Error::new(
+        E_FAIL,
+        "No unrotated display output at the monitor's position",
+    ))
+}
+
+fn to_capture_error(err: windows::core::Error) -> CaptureError {
+    CaptureError::Capture(err.to_string())
+}
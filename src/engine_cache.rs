@@ -0,0 +1,82 @@
+//! Management of the TensorRT engine cache. Engines are compiled for a specific model, GPU and
+//! driver, so stale entries have to be cleared when any of those change.
+
+use std::{
+    collections::hash_map::DefaultHasher,
+    fs,
+    hash::{Hash, Hasher},
+    io,
+    path::Path,
+    time::UNIX_EPOCH,
+};
+
+use serde::{Deserialize, Serialize};
+
+const FINGERPRINT_FILE: &str = "fingerprint";
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct EngineCacheEntry {
+    pub name: String,
+    pub size: u64,
+}
+
+pub fn list(cache_dir: impl AsRef<Path>) -> Vec<EngineCacheEntry> {
+    let Ok(entries) = fs::read_dir(cache_dir) else {
+        return Vec::new();
+    };
+    entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_name() != FINGERPRINT_FILE)
+        .filter_map(|entry| {
+            let metadata = entry.metadata().ok()?;
+            metadata.is_file().then(|| EngineCacheEntry {
+                name: entry.file_name().to_string_lossy().into_owned(),
+                size: metadata.len(),
+            })
+        })
+        .collect()
+}
+
+pub fn clear(cache_dir: impl AsRef<Path>) -> io::Result<()> {
+    let cache_dir = cache_dir.as_ref();
+    if cache_dir.exists() {
+        fs::remove_dir_all(cache_dir)?;
+    }
+    fs::create_dir_all(cache_dir)
+}
+
+/// Clears the cache if any of `inputs` changed since the engines were built. Inputs are
+/// fingerprinted by name, size and modification time.
+pub fn invalidate_if_stale(
+    cache_dir: impl AsRef<Path>,
+    inputs: &[impl AsRef<Path>],
+) -> io::Result<()> {
+    let cache_dir = cache_dir.as_ref();
+    let fingerprint = format!("{:x}", fingerprint(inputs));
+    let fingerprint_path = cache_dir.join(FINGERPRINT_FILE);
+    let previous = fs::read_to_string(&fingerprint_path).unwrap_or_default();
+    if previous != fingerprint {
+        log::info!("Engine cache inputs changed, clearing {cache_dir:?}");
+        clear(cache_dir)?;
+        fs::write(fingerprint_path, fingerprint)?;
+    }
+    Ok(())
+}
+
+fn fingerprint(inputs: &[impl AsRef<Path>]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    env!("CARGO_PKG_VERSION").hash(&mut hasher);
+    for input in inputs {
+        let input = input.as_ref();
+        input.file_name().hash(&mut hasher);
+        if let Ok(metadata) = fs::metadata(input) {
+            metadata.len().hash(&mut hasher);
+            metadata
+                .modified()
+                .ok()
+                .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+                .hash(&mut hasher);
+        }
+    }
+    hasher.finish()
+}
@@ -0,0 +1,110 @@
+//! Detection of remote desktop and headless sessions, where screen capture and global input
+//! hooks behave differently or don't work at all, so features that can't work are disabled up
+//! front instead of failing silently.
+
+use std::env;
+
+use device_query::{DeviceQuery as _, DeviceState};
+use serde::Serialize;
+use xcap::Monitor;
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SessionKind {
+    Local,
+    /// RDP, xrdp or a forwarded X11 session. Capture works, but usually through slower paths,
+    /// and the client may keep some hotkeys for itself.
+    RemoteDesktop,
+    /// No display server to capture from or track the cursor on
+    Headless,
+}
+
+/// What the session supports, with the problems found so far.
+#[derive(Serialize, Clone, Debug)]
+pub struct SessionStatus {
+    pub kind: SessionKind,
+    pub monitors: usize,
+    /// Features that were disabled or failed to start, to show to the user
+    pub issues: Vec<String>,
+}
+
+impl SessionStatus {
+    pub fn detect() -> Self {
+        let kind = session_kind();
+        let monitors = match Monitor::all() {
+            Ok(monitors) => monitors.len(),
+            Err(err) => {
+                log::warn!("Failed to list monitors: {err}");
+                0
+            }
+        };
+        let mut status = Self {
+            kind,
+            monitors,
+            issues: Vec::new(),
+        };
+        match kind {
+            SessionKind::Local => {}
+            SessionKind::RemoteDesktop => status.report(
+                "Running in a remote desktop session. Capture is slower, and hotkeys taken by \
+                 the remote desktop client won't reach LiveOCR.",
+            ),
+            SessionKind::Headless => {
+                status.report("No display found. Screen capture and cursor tracking are disabled.")
+            }
+        }
+        if monitors == 0 && kind != SessionKind::Headless {
+            status.report("No monitors found. Screen capture is disabled.");
+        }
+        status
+    }
+
+    /// Whether global mouse and keyboard state can be read. Input hooks need a display server
+    /// to attach to and panic without one.
+    pub fn can_track_input(&self) -> bool {
+        self.kind != SessionKind::Headless
+    }
+
+    pub fn can_capture(&self) -> bool {
+        self.monitors > 0
+    }
+
+    /// Records a feature that doesn't work in this session.
+    pub fn report(&mut self, issue: impl Into<String>) {
+        let issue = issue.into();
+        log::warn!("{issue}");
+        self.issues.push(issue);
+    }
+}
+
+/// The cursor position, or `None` in sessions where it can't be read, see
+/// [`SessionStatus::can_track_input`].
+pub fn cursor_position() -> Option<(i32, i32)> {
+    (session_kind() != SessionKind::Headless).then(|| DeviceState::new().get_mouse().coords)
+}
+
+#[cfg(windows)]
+fn session_kind() -> SessionKind {
+    // `Console` locally and `RDP-Tcp#<n>` over remote desktop
+    match env::var("SESSIONNAME") {
+        Ok(name) if name.starts_with("RDP-") => SessionKind::RemoteDesktop,
+        _ => SessionKind::Local,
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn session_kind() -> SessionKind {
+    let has_var = |name| env::var_os(name).is_some_and(|value| !value.is_empty());
+    if !has_var("DISPLAY") && !has_var("WAYLAND_DISPLAY") {
+        SessionKind::Headless
+    } else if has_var("XRDP_SESSION") || has_var("SSH_CONNECTION") {
+        SessionKind::RemoteDesktop
+    } else {
+        SessionKind::Local
+    }
+}
+
+#[cfg(not(any(windows, target_os = "linux")))]
+fn session_kind() -> SessionKind {
+    SessionKind::Local
+}
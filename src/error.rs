@@ -0,0 +1,38 @@
+use thiserror::Error;
+
+use crate::capture::CaptureError;
+
+/// Errors returned by the core library, meant to be shown to the user by frontends.
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error(transparent)]
+    Capture(#[from] CaptureError),
+    #[error("No text was recognized")]
+    NoText,
+    #[error("None of the configured dictionaries could be loaded")]
+    NoDictionary,
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Invalid dictionary data: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("Invalid dictionary archive: {0}")]
+    Zip(#[from] zip::result::ZipError),
+    #[error("Invalid StarDict dictionary: {0}")]
+    StarDict(String),
+    #[error("Corrupted dictionary cache: {0}")]
+    Cache(#[from] bitcode::Error),
+    #[error("Dictionary cache entries don't match its index")]
+    CacheMismatch,
+    #[error("Corrupted dictionary index: {0}")]
+    Index(#[from] fst::Error),
+    #[error("Invalid config file: {0}")]
+    Config(serde_json::Error),
+    #[error("The monitor the session was captured on is no longer connected")]
+    MonitorDisconnected,
+    #[error("Anki export failed: {0}")]
+    Anki(String),
+    #[error("Speech synthesis failed: {0}")]
+    Speech(String),
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
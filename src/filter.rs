@@ -0,0 +1,83 @@
+use geo::{coord, Contains, Rect};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::character::{Block, CharBox};
+
+/// A screen region in physical pixels.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct Region {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl Region {
+    fn rect(&self) -> Rect<f32> {
+        Rect::new(
+            coord![x: self.x, y: self.y],
+            coord![x: self.x + self.width, y: self.y + self.height],
+        )
+    }
+}
+
+/// Rules for dropping OCR blocks that are known noise, like clocks, usernames or watermarks.
+/// A block is dropped if any rule matches it.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct BlockFilter {
+    /// Regexes matched against the recognized text
+    pub exclude_patterns: Vec<String>,
+    /// Line height limits in pixels
+    pub min_height: Option<f32>,
+    pub max_height: Option<f32>,
+    /// Blocks entirely inside one of these regions are dropped
+    pub exclude_regions: Vec<Region>,
+}
+
+impl BlockFilter {
+    pub fn apply(&self, blocks: Vec<Block>) -> Vec<Block> {
+        let patterns = self
+            .exclude_patterns
+            .iter()
+            .filter_map(|pattern| match Regex::new(pattern) {
+                Ok(regex) => Some(regex),
+                Err(err) => {
+                    log::error!("Ignoring invalid filter pattern {pattern:?}: {err}");
+                    None
+                }
+            })
+            .collect::<Vec<_>>();
+        let regions = self
+            .exclude_regions
+            .iter()
+            .map(Region::rect)
+            .collect::<Vec<_>>();
+
+        blocks
+            .into_iter()
+            .filter(|block| {
+                let bounds = block.bounds;
+                let excluded = patterns.iter().any(|regex| regex.is_match(&block.text))
+                    || self.min_height.is_some_and(|min| bounds.height() < min)
+                    || self.max_height.is_some_and(|max| bounds.height() > max)
+                    || regions.iter().any(|region| region.contains(&bounds));
+                if excluded {
+                    log::info!("Filtered out block {}", block.text);
+                }
+                !excluded
+            })
+            .collect()
+    }
+}
+
+/// Bounding box of all characters in a block.
+pub fn block_bounds(chars: &[CharBox]) -> Option<Rect<f32>> {
+    chars.iter().map(|it| it.rect).reduce(|a, b| {
+        Rect::new(
+            coord![x: a.min().x.min(b.min().x), y: a.min().y.min(b.min().y)],
+            coord![x: a.max().x.max(b.max().x), y: a.max().y.max(b.max().y)],
+        )
+    })
+}
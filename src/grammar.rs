@@ -0,0 +1,126 @@
+//! Rule based detection of common Chinese grammar patterns in the hovered sentence, shown as
+//! short notes in the tooltip. The rules only look at the words the sentence is segmented
+//! into, so they're hints rather than a parse.
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    collocation::SENTENCE_ENDS, dict::DictionaryGroup, hover::HoveredWord, is_meaningful,
+    segment::segment,
+};
+
+/// Results of complement verbs, e.g. `完` in `看完`
+const RESULT_COMPLEMENTS: &[&str] = &["完", "懂", "见", "到", "错", "清楚", "明白", "干净"];
+/// Adverbs that come before the same characters without forming a complement, e.g. `很` in
+/// `很清楚`
+const DEGREE_ADVERBS: &[char] = &[
+    '很', '太', '真', '最', '挺', '更', '不', '没', '也', '都', '还',
+];
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct GrammarNote {
+    /// The pattern, e.g. `是…的`
+    pub pattern: String,
+    pub note: String,
+    /// Chinese Grammar Wiki search for the pattern, for the full explanation
+    pub link: String,
+}
+
+impl GrammarNote {
+    fn new(pattern: &str, note: &str, search: &str) -> Self {
+        Self {
+            pattern: pattern.to_string(),
+            note: note.to_string(),
+            link: format!(
+                "https://resources.allsetlearning.com/chinese/grammar/Special:Search?search={}",
+                search.replace(' ', "+")
+            ),
+        }
+    }
+}
+
+/// Notes for the patterns found in the sentence around the hovered word.
+pub fn notes(dict: &DictionaryGroup, word: &HoveredWord) -> Vec<GrammarNote> {
+    let sentence = sentence_at(&word.text, word.span.start);
+    let chars = sentence.chars().collect::<Vec<_>>();
+    let words = segment(dict, &sentence)
+        .into_iter()
+        .map(|span| chars[span].iter().collect::<String>())
+        .filter(|word| word.chars().any(is_meaningful))
+        .collect::<Vec<_>>();
+    let position = |target: &str| words.iter().position(|word| word == target);
+
+    let mut notes = Vec::new();
+    if position("把").is_some_and(|i| i + 2 < words.len()) {
+        notes.push(GrammarNote::new(
+            "把",
+            "Moves the object before the verb to stress what happens to it: subject + 把 + \
+             object + verb + result.",
+            "ba sentence",
+        ));
+    }
+    if position("被").is_some_and(|i| i + 1 < words.len()) {
+        notes.push(GrammarNote::new(
+            "被",
+            "Passive voice: the subject is what the action is done to, optionally followed by \
+             who does it.",
+            "bei sentence",
+        ));
+    }
+    if position("是").is_some_and(|i| i + 2 < words.len())
+        && words.last().is_some_and(|w| w == "的")
+    {
+        notes.push(GrammarNote::new(
+            "是…的",
+            "Stresses when, where or how something that already happened was done.",
+            "shi de construction",
+        ));
+    }
+    if let Some(complement) = result_complement(&words) {
+        notes.push(GrammarNote::new(
+            &format!("V{complement}"),
+            &format!(
+                "Result complement: {complement} after the verb says what the action achieved."
+            ),
+            "result complement",
+        ));
+    }
+    notes
+}
+
+/// The first result complement following a verb, either segmented on its own or as the end of
+/// a two character word like `看完`.
+fn result_complement(words: &[String]) -> Option<&'static str> {
+    let follows_verb = |previous: &str| {
+        previous
+            .chars()
+            .last()
+            .is_some_and(|ch| !DEGREE_ADVERBS.contains(&ch))
+    };
+    words.iter().enumerate().find_map(|(i, word)| {
+        RESULT_COMPLEMENTS.iter().copied().find(|&complement| {
+            if word == complement {
+                i > 0 && follows_verb(&words[i - 1])
+            } else {
+                word.chars().count() == complement.chars().count() + 1
+                    && word.ends_with(complement)
+                    && follows_verb(word.trim_end_matches(complement))
+            }
+        })
+    })
+}
+
+/// The sentence containing the character at `index`.
+fn sentence_at(text: &str, index: usize) -> String {
+    let chars = text.chars().collect::<Vec<_>>();
+    let index = index.min(chars.len());
+    let start = chars[..index]
+        .iter()
+        .rposition(|ch| SENTENCE_ENDS.contains(ch))
+        .map_or(0, |i| i + 1);
+    let end = chars[index..]
+        .iter()
+        .position(|ch| SENTENCE_ENDS.contains(ch))
+        .map_or(chars.len(), |i| index + i);
+    chars[start..end].iter().collect()
+}
@@ -0,0 +1,163 @@
+use std::{
+    fs::OpenOptions,
+    io::{self, Write as _},
+    mem::size_of,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::dict::{DictionaryGroup, Script};
+
+/// A successful lookup.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct HistoryEntry {
+    /// Headword of the first match
+    pub word: String,
+    /// Seconds since the Unix epoch
+    pub timestamp: u64,
+    /// The OCR line the word was looked up in
+    pub line: String,
+    pub app: Option<String>,
+}
+
+/// Every word looked up, oldest first. Persisted as JSON lines so recording only appends.
+/// Only the most recent `limit` entries are kept in memory, see
+/// [`crate::memory::MemoryLimits`].
+pub struct History {
+    path: PathBuf,
+    entries: Vec<HistoryEntry>,
+    limit: usize,
+}
+
+impl History {
+    /// Records a lookup, unless it's the same word in the same line as the last one.
+    pub fn record(&mut self, word: &str, line: &str, app: Option<&str>) {
+        if let Some(last) = self.entries.last() {
+            if last.word == word && last.line == line {
+                return;
+            }
+        }
+        let entry = HistoryEntry {
+            word: word.to_string(),
+            timestamp: now(),
+            line: line.to_string(),
+            app: app.map(str::to_string),
+        };
+        if let Err(err) = self.append(&entry) {
+            log::error!("Failed to save history to {:?}: {err}", self.path);
+        }
+        self.entries.push(entry);
+        self.evict();
+    }
+
+    /// The lookups kept in memory.
+    pub fn entries(&self) -> &[HistoryEntry] {
+        &self.entries
+    }
+
+    /// Every lookup ever recorded, read back from disk.
+    pub fn all(&self) -> Vec<HistoryEntry> {
+        read_entries(&self.path)
+    }
+
+    /// Approximate heap size of the entries kept in memory, in bytes.
+    pub fn memory_bytes(&self) -> usize {
+        self.entries
+            .iter()
+            .map(|entry| {
+                size_of::<HistoryEntry>()
+                    + entry.word.len()
+                    + entry.line.len()
+                    + entry.app.as_ref().map_or(0, String::len)
+            })
+            .sum()
+    }
+
+    /// Drops the oldest entries beyond the limit.
+    fn evict(&mut self) {
+        let excess = self.entries.len().saturating_sub(self.limit);
+        self.entries.drain(..excess);
+    }
+
+    /// Lookups at or after `timestamp`, in seconds since the Unix epoch.
+    pub fn since(&self, timestamp: u64) -> &[HistoryEntry] {
+        let start = self
+            .entries
+            .partition_point(|entry| entry.timestamp < timestamp);
+        &self.entries[start..]
+    }
+
+    fn append(&self, entry: &HistoryEntry) -> io::Result<()> {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        writeln!(file, "{}", serde_json::to_string(entry)?)
+    }
+}
+
+/// Loads the most recent `limit` lookups.
+pub fn load(path: impl AsRef<Path>, limit: usize) -> History {
+    let path = path.as_ref();
+    let mut history = History {
+        path: path.to_owned(),
+        entries: read_entries(path),
+        limit,
+    };
+    history.evict();
+    history
+}
+
+fn read_entries(path: &Path) -> Vec<HistoryEntry> {
+    std::fs::read_to_string(path)
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect()
+}
+
+/// Writes `entries` as tab separated `word, reading, definitions, line` rows, which Anki and
+/// most flashcard apps can import. Readings and definitions come from the first match in
+/// `dict`.
+pub fn export_tsv(
+    entries: &[HistoryEntry],
+    dict: &DictionaryGroup,
+    script: Script,
+    path: impl AsRef<Path>,
+) -> io::Result<()> {
+    let clean = |text: &str| text.replace(['\t', '\n'], " ");
+    let rows = entries
+        .iter()
+        .map(|entry| {
+            let matched = dict
+                .matches(&entry.word)
+                .into_iter()
+                .find(|it| it.simplified == entry.word);
+            let (word, reading, definitions) = match matched {
+                Some(it) => (
+                    it.headword(script),
+                    it.pronunciation(),
+                    it.translations.join("; "),
+                ),
+                None => (entry.word.clone(), String::new(), String::new()),
+            };
+            format!(
+                "{}\t{}\t{}\t{}\n",
+                clean(&word),
+                clean(&reading),
+                clean(&definitions),
+                clean(&entry.line)
+            )
+        })
+        .collect::<String>();
+    std::fs::write(path, rows)
+}
+
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
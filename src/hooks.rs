@@ -0,0 +1,103 @@
+//! User configured shell commands run on events, for integrations that don't need changes to
+//! the app. Commands run in the background through the system shell, with the event's data in
+//! environment variables.
+
+use std::{env, fs, path::PathBuf, process::Command};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{character::Block, dict::DictionaryEntry};
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct Hooks {
+    /// Runs after OCR results change. `LIVE_OCRS_TRANSCRIPT` is the path of a text file with
+    /// one recognized line per row.
+    pub on_ocr_complete: Option<String>,
+    /// Runs when a word is exported to Anki or marked as known. `LIVE_OCRS_ENTRY` is its
+    /// dictionary entry as JSON, `LIVE_OCRS_EVENT` is `anki` or `known`.
+    pub on_word_saved: Option<String>,
+}
+
+/// How a word was saved, passed to the `on_word_saved` hook
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SaveEvent {
+    Anki,
+    Known,
+}
+
+impl SaveEvent {
+    fn name(self) -> &'static str {
+        match self {
+            Self::Anki => "anki",
+            Self::Known => "known",
+        }
+    }
+}
+
+impl Hooks {
+    pub fn ocr_complete(&self, blocks: &[Block]) {
+        let Some(command) = &self.on_ocr_complete else {
+            return;
+        };
+        let path = transcript_path();
+        let transcript = blocks
+            .iter()
+            .map(|block| block.text.as_str())
+            .collect::<Vec<_>>()
+            .join("\n");
+        if let Err(err) = fs::write(&path, transcript) {
+            log::error!("Failed to write transcript to {path:?}: {err}");
+            return;
+        }
+        run(
+            command,
+            vec![("LIVE_OCRS_TRANSCRIPT", path.display().to_string())],
+        );
+    }
+
+    pub fn word_saved(&self, entry: &DictionaryEntry, event: SaveEvent) {
+        let Some(command) = &self.on_word_saved else {
+            return;
+        };
+        let entry = match serde_json::to_string(entry) {
+            Ok(entry) => entry,
+            Err(err) => {
+                log::error!("Failed to serialize {} for hook: {err}", entry.simplified);
+                return;
+            }
+        };
+        run(
+            command,
+            vec![
+                ("LIVE_OCRS_ENTRY", entry),
+                ("LIVE_OCRS_EVENT", event.name().to_string()),
+            ],
+        );
+    }
+}
+
+/// Overwritten on every OCR pass, hooks that need to keep it have to copy it.
+fn transcript_path() -> PathBuf {
+    env::temp_dir().join("live-ocrs-transcript.txt")
+}
+
+/// Starts `command` in the system shell without waiting for it. Failures are only logged.
+fn run(command: &str, vars: Vec<(&'static str, String)>) {
+    let mut shell = if cfg!(windows) {
+        let mut shell = Command::new("cmd");
+        shell.arg("/C");
+        shell
+    } else {
+        let mut shell = Command::new("sh");
+        shell.arg("-c");
+        shell
+    };
+    shell.arg(command).envs(vars);
+    let command = command.to_string();
+    std::thread::spawn(move || match shell.status() {
+        Ok(status) if !status.success() => log::warn!("Hook {command:?} exited with {status}"),
+        Ok(_) => {}
+        Err(err) => log::error!("Failed to run hook {command:?}: {err}"),
+    });
+}
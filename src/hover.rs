@@ -0,0 +1,196 @@
+use std::{ops::Range, time::Instant};
+
+use geo::Rect;
+
+use crate::{
+    character::Block, dict::DictionaryEntry, known::flag_known, longest_meaningful_string, LiveOcr,
+};
+
+/// The character closest to the cursor.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Hover {
+    /// Text of the block containing the character
+    pub text: String,
+    /// Character index within `text`
+    pub index: usize,
+    /// Distance from the cursor, zero if it's on the character
+    pub distance: f32,
+    pub rect: Rect<f32>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct HoveredWord {
+    /// Text of the block containing the word
+    pub text: String,
+    /// Character range of the looked up text within the block
+    pub span: Range<usize>,
+    /// Bounds of the hovered character
+    pub rect: Rect<f32>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub enum HoverState {
+    #[default]
+    Idle,
+    Hovering(HoveredWord),
+    /// The cursor left `word`. The tooltip is hidden at `hide_at` unless the cursor returns.
+    Cooling {
+        word: HoveredWord,
+        hide_at: Instant,
+    },
+}
+
+impl HoverState {
+    pub fn word(&self) -> Option<&HoveredWord> {
+        match self {
+            HoverState::Idle => None,
+            HoverState::Hovering(word) | HoverState::Cooling { word, .. } => Some(word),
+        }
+    }
+}
+
+/// A word the cursor arrived on, looked up once it rests there for the dwell delay.
+#[derive(Clone, Debug)]
+pub struct Dwell {
+    /// Text of the block containing the word
+    text: String,
+    /// Character index the word starts at
+    start: usize,
+    position: (i32, i32),
+    since: Instant,
+}
+
+/// Transition events for the frontends to act on.
+#[derive(Clone, Debug)]
+pub enum HoverEvent {
+    Show {
+        rect: Rect<f32>,
+        definitions: Vec<DictionaryEntry>,
+    },
+    Hide,
+}
+
+pub fn update_hover(state: &mut LiveOcr, position: (i32, i32)) -> Option<HoverEvent> {
+    let point = geo::point!(x: position.0 as f32, y: position.1 as f32);
+    let Ok(Hover {
+        text: closest_string,
+        index: closest_char,
+        distance: closest_distance,
+        rect: closest_rect,
+    }) = state.definitions.closest_char(point)
+    else {
+        return None;
+    };
+
+    if closest_distance < state.config.hover_distance {
+        // The hovered character may be in the middle of a word, so look up from where it starts
+        let start = state.definitions.word_start(&closest_string, closest_char);
+        if let Some(word) = state.hover.word() {
+            if word.text == closest_string && word.span.start == start {
+                state.hover = HoverState::Hovering(word.clone());
+                state.dwell = None;
+                return None;
+            }
+        }
+        if !dwelled(state, &closest_string, start, position) {
+            return leave_word(state);
+        }
+        state.dwell = None;
+        let longest_string = longest_meaningful_string(&closest_string, start);
+        state
+            .definitions
+            .update(&longest_string, state.config.fuzzy_lookup);
+        flag_known(state);
+        if let Some(entry) = state.definitions.definitions.first() {
+            state
+                .history
+                .record(&entry.simplified, &closest_string, state.app.as_deref());
+        }
+        state.metrics.record_lookup();
+        state.hover = HoverState::Hovering(HoveredWord {
+            span: start..start + longest_string.chars().count(),
+            text: closest_string,
+            rect: closest_rect,
+        });
+        Some(HoverEvent::Show {
+            rect: closest_rect,
+            definitions: state.definitions.definitions.clone(),
+        })
+    } else {
+        state.dwell = None;
+        leave_word(state)
+    }
+}
+
+/// Whether the cursor has rested on the word starting at `start` for the dwell delay. Starts
+/// the delay over when it arrives on a different word.
+fn dwelled(state: &mut LiveOcr, text: &str, start: usize, position: (i32, i32)) -> bool {
+    let delay = state.config.hover_dwell();
+    if delay.is_zero() {
+        return true;
+    }
+    match &mut state.dwell {
+        Some(dwell) if dwell.text == text && dwell.start == start => {
+            dwell.position = position;
+            dwell.since.elapsed() >= delay
+        }
+        _ => {
+            state.dwell = Some(Dwell {
+                text: text.to_string(),
+                start,
+                position,
+                since: Instant::now(),
+            });
+            false
+        }
+    }
+}
+
+fn leave_word(state: &mut LiveOcr) -> Option<HoverEvent> {
+    if let HoverState::Hovering(word) = &state.hover {
+        state.hover = HoverState::Cooling {
+            word: word.clone(),
+            hide_at: Instant::now() + state.config.auto_hide_delay(),
+        };
+    }
+    hide_expired(state)
+}
+
+/// Looks up the word the cursor rests on once the dwell delay has passed, and hides the
+/// tooltip once the auto-hide delay has passed. Needs to be called periodically, since
+/// `update_hover` only runs when the mouse moves.
+pub fn expire_hover(state: &mut LiveOcr) -> Option<HoverEvent> {
+    let dwelled = state
+        .dwell
+        .as_ref()
+        .filter(|dwell| dwell.since.elapsed() >= state.config.hover_dwell())
+        .map(|dwell| dwell.position);
+    match dwelled {
+        Some(position) => update_hover(state, position),
+        None => hide_expired(state),
+    }
+}
+
+fn hide_expired(state: &mut LiveOcr) -> Option<HoverEvent> {
+    match state.hover {
+        HoverState::Cooling { hide_at, .. } if Instant::now() >= hide_at => {
+            state.hover = HoverState::Idle;
+            state.definitions.definitions.clear();
+            Some(HoverEvent::Hide)
+        }
+        _ => None,
+    }
+}
+
+/// Index of the block `word` was found in.
+pub fn find_block(blocks: &[Block], word: &HoveredWord) -> Option<usize> {
+    blocks.iter().position(|block| {
+        block.text == word.text && block.chars.iter().any(|it| it.rect == word.rect)
+    })
+}
+
+pub fn reset_hover(state: &mut LiveOcr) {
+    state.hover = HoverState::Idle;
+    state.dwell = None;
+    state.definitions.definitions.clear();
+}
@@ -0,0 +1,120 @@
+//! Loader for JMdict in the JSON format of jmdict-simplified
+//! (<https://github.com/scriptin/jmdict-simplified>).
+
+use itertools::Itertools;
+use serde::Deserialize;
+
+use crate::dict::DictionaryEntry;
+
+#[derive(Deserialize)]
+struct JmdictFile {
+    words: Vec<Word>,
+}
+
+#[derive(Deserialize)]
+struct Word {
+    kanji: Vec<Kanji>,
+    kana: Vec<Kana>,
+    sense: Vec<Sense>,
+}
+
+#[derive(Deserialize)]
+struct Kanji {
+    text: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Kana {
+    text: String,
+    /// Kanji forms this reading applies to, `*` for all
+    applies_to_kanji: Vec<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Sense {
+    part_of_speech: Vec<String>,
+    gloss: Vec<Gloss>,
+}
+
+#[derive(Deserialize)]
+struct Gloss {
+    text: String,
+}
+
+/// Converts every written form of every word into an entry keyed by that form. Japanese has no
+/// simplified/traditional split, so both hold the written form and the kana goes in `reading`.
+pub fn parse(data: &str) -> serde_json::Result<Vec<DictionaryEntry>> {
+    let file: JmdictFile = serde_json::from_str(data)?;
+    Ok(file.words.into_iter().flat_map(into_entries).collect())
+}
+
+fn into_entries(word: Word) -> Vec<DictionaryEntry> {
+    let translations = word
+        .sense
+        .iter()
+        .map(|sense| sense.gloss.iter().map(|gloss| &gloss.text).join("; "))
+        .collect::<Vec<_>>();
+    let word_classes = word
+        .sense
+        .iter()
+        .flat_map(|sense| sense.part_of_speech.iter().cloned())
+        .unique()
+        .collect::<Vec<_>>();
+    let entry = |text: &str, reading: Option<String>| DictionaryEntry {
+        simplified: text.to_string(),
+        traditional: text.to_string(),
+        pinyin: Vec::new(),
+        translations: translations.clone(),
+        fuzzy: false,
+        reading,
+        word_classes: word_classes.clone(),
+        inflections: Vec::new(),
+        tags: Vec::new(),
+        frequency: None,
+        source: String::new(),
+        known: false,
+        rank: None,
+        levels: Vec::new(),
+        matched_script: None,
+    };
+
+    let kanji_entries = word.kanji.iter().map(|kanji| {
+        let reading = word.kana.iter().find(|kana| {
+            kana.applies_to_kanji
+                .iter()
+                .any(|it| it == "*" || *it == kanji.text)
+        });
+        entry(&kanji.text, reading.map(|kana| kana.text.clone()))
+    });
+    // Words usually written in kana are looked up by their reading
+    let kana_entries = word.kana.iter().map(|kana| entry(&kana.text, None));
+    kanji_entries.chain(kana_entries).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_kanji_and_kana_forms() {
+        let data = r#"{"words": [{
+            "kanji": [{"text": "日本"}],
+            "kana": [{"text": "にほん", "appliesToKanji": ["*"]}],
+            "sense": [{"partOfSpeech": ["n"], "gloss": [{"text": "Japan"}]}]
+        }]}"#;
+        let entries = parse(data).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].simplified, "日本");
+        assert_eq!(entries[0].reading.as_deref(), Some("にほん"));
+        assert_eq!(entries[1].simplified, "にほん");
+        assert_eq!(entries[1].translations, ["Japan"]);
+    }
+
+    #[test]
+    fn rejects_malformed_input() {
+        assert!(parse("not json").is_err());
+        assert!(parse(r#"{"words": [{"kanji": []}]}"#).is_err());
+    }
+}
@@ -0,0 +1,89 @@
+use std::{
+    collections::BTreeSet,
+    path::{Path, PathBuf},
+};
+
+use crate::{config::KnownWordDisplay, hooks::SaveEvent, hover::HoverEvent, LiveOcr};
+
+/// Words the user already knows, by simplified form. Persisted across runs.
+pub struct KnownWords {
+    path: PathBuf,
+    words: BTreeSet<String>,
+}
+
+impl KnownWords {
+    pub fn contains(&self, word: &str) -> bool {
+        self.words.contains(word)
+    }
+
+    pub fn mark(&mut self, word: &str) {
+        if self.words.insert(word.to_string()) {
+            self.save();
+        }
+    }
+
+    pub fn unmark(&mut self, word: &str) {
+        if self.words.remove(word) {
+            self.save();
+        }
+    }
+
+    fn save(&self) {
+        let data = serde_json::to_string(&self.words).unwrap();
+        if let Err(err) = std::fs::write(&self.path, data) {
+            log::error!("Failed to save known words to {:?}: {err}", self.path);
+        }
+    }
+}
+
+pub fn load(path: impl AsRef<Path>) -> KnownWords {
+    let path = path.as_ref();
+    let words = std::fs::read_to_string(path)
+        .ok()
+        .and_then(|data| serde_json::from_str(&data).ok())
+        .unwrap_or_default();
+    KnownWords {
+        path: path.to_owned(),
+        words,
+    }
+}
+
+/// Flags the current definitions the user knows, or drops them if known words are skipped.
+pub(crate) fn flag_known(state: &mut LiveOcr) {
+    let definitions = &mut state.definitions.definitions;
+    for entry in definitions.iter_mut() {
+        entry.known = state.known_words.contains(&entry.simplified);
+    }
+    if state.config.known_words == KnownWordDisplay::Skip {
+        definitions.retain(|entry| !entry.known);
+    }
+}
+
+/// Marks the hovered word as known, or unmarks it if it already is. Works on the longest match
+/// even if known words are skipped, so they can be unmarked again.
+pub fn toggle_hovered(state: &mut LiveOcr) -> Option<HoverEvent> {
+    let word = state.hover.word()?.clone();
+    let text = word
+        .text
+        .chars()
+        .skip(word.span.start)
+        .take(word.span.len())
+        .collect::<String>();
+    state.definitions.update(&text, state.config.fuzzy_lookup);
+    let headword = state.definitions.definitions.first()?.simplified.clone();
+    if state.known_words.contains(&headword) {
+        log::info!("Unmarked {headword} as known");
+        state.known_words.unmark(&headword);
+    } else {
+        log::info!("Marked {headword} as known");
+        state.known_words.mark(&headword);
+        if let Some(entry) = state.definitions.definitions.first() {
+            state.config.hooks.word_saved(entry, SaveEvent::Known);
+        }
+    }
+    flag_known(state);
+    Some(HoverEvent::Show {
+        rect: word.rect,
+        definitions: state.definitions.definitions.clone(),
+    })
+}
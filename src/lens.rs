@@ -0,0 +1,143 @@
+//! Magnifier showing the captured pixels around the cursor enlarged, with the recognized
+//! characters drawn over them at a readable size.
+
+use std::io::Cursor;
+
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use geo::{coord, Contains, Rect};
+use image::{imageops, ImageFormat, RgbaImage};
+use serde::{Deserialize, Serialize};
+
+use crate::{live::capture_current, LiveOcr};
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct LensConfig {
+    /// Key to hold for the lens, by its `device_query` name like `"LControl"` or `"CapsLock"`.
+    pub key: String,
+    /// Half the width of the magnified area, in screen pixels
+    pub radius: u32,
+    pub zoom: f32,
+}
+
+impl Default for LensConfig {
+    fn default() -> Self {
+        Self {
+            key: "LControl".to_string(),
+            radius: 60,
+            zoom: 3.0,
+        }
+    }
+}
+
+/// What the lens window shows. Coordinates are in lens pixels.
+#[derive(Serialize, Clone, Debug)]
+pub struct LensView {
+    /// Magnified pixels as a PNG data URL
+    pub image: String,
+    pub width: u32,
+    pub height: u32,
+    pub cursor: (f32, f32),
+    pub chars: Vec<LensChar>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct LensChar {
+    pub text: String,
+    pub rect: Rect<f32>,
+    /// The cursor is over this character
+    pub hovered: bool,
+}
+
+/// A frame captured when the lens opens, reused while the cursor moves so the lens doesn't
+/// capture the screen on every mouse move.
+pub struct Lens {
+    frame: RgbaImage,
+    origin: (i32, i32),
+}
+
+impl Lens {
+    /// Captures the screen OCR is running on. `None` while OCR is off.
+    pub fn open(state: &LiveOcr) -> Option<Self> {
+        let (frame, origin) = capture_current(state)?;
+        Some(Self { frame, origin })
+    }
+
+    /// Magnifies the area around `cursor`, in screen space. `None` if the cursor is outside
+    /// the captured frame.
+    pub fn view(
+        &self,
+        state: &LiveOcr,
+        cursor: (i32, i32),
+        config: &LensConfig,
+    ) -> Option<LensView> {
+        let radius = config.radius as i32;
+        let (x, y) = (cursor.0 - self.origin.0, cursor.1 - self.origin.1);
+        let min_x = (x - radius).max(0);
+        let min_y = (y - radius).max(0);
+        let max_x = (x + radius).min(self.frame.width() as i32);
+        let max_y = (y + radius).min(self.frame.height() as i32);
+        if max_x <= min_x || max_y <= min_y {
+            return None;
+        }
+
+        let crop = imageops::crop_imm(
+            &self.frame,
+            min_x as u32,
+            min_y as u32,
+            (max_x - min_x) as u32,
+            (max_y - min_y) as u32,
+        );
+        let width = ((max_x - min_x) as f32 * config.zoom) as u32;
+        let height = ((max_y - min_y) as f32 * config.zoom) as u32;
+        let zoomed = imageops::resize(&*crop, width, height, imageops::FilterType::Triangle);
+        let mut png = Vec::new();
+        if let Err(err) = zoomed.write_to(&mut Cursor::new(&mut png), ImageFormat::Png) {
+            log::warn!("Failed to encode lens image: {err}");
+            return None;
+        }
+
+        let area = Rect::new(
+            coord![x: (min_x + self.origin.0) as f32, y: (min_y + self.origin.1) as f32],
+            coord![x: (max_x + self.origin.0) as f32, y: (max_y + self.origin.1) as f32],
+        );
+        let to_lens = |point: geo::Coord<f32>| {
+            coord![
+                x: (point.x - area.min().x) * config.zoom,
+                y: (point.y - area.min().y) * config.zoom,
+            ]
+        };
+        let screen_cursor = coord![x: cursor.0 as f32, y: cursor.1 as f32];
+        let chars = state
+            .definitions
+            .ocr_strings
+            .iter()
+            .flat_map(|block| {
+                let text = block.text.chars().collect::<Vec<_>>();
+                block.chars.iter().filter_map(move |char_box| {
+                    let overlaps = char_box.rect.min().x < area.max().x
+                        && char_box.rect.max().x > area.min().x
+                        && char_box.rect.min().y < area.max().y
+                        && char_box.rect.max().y > area.min().y;
+                    overlaps.then(|| LensChar {
+                        text: text
+                            .get(char_box.index)
+                            .map(char::to_string)
+                            .unwrap_or_default(),
+                        rect: Rect::new(to_lens(char_box.rect.min()), to_lens(char_box.rect.max())),
+                        hovered: char_box.rect.contains(&screen_cursor),
+                    })
+                })
+            })
+            .collect();
+        let cursor = to_lens(screen_cursor);
+
+        Some(LensView {
+            image: format!("data:image/png;base64,{}", STANDARD.encode(png)),
+            width,
+            height,
+            cursor: (cursor.x, cursor.y),
+            chars,
+        })
+    }
+}
@@ -1,47 +1,210 @@
-use std::sync::Arc;
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
-use capture::CaptureState;
+use capture::{
+    find_window, window_at, CaptureError, CaptureOptions, CaptureState, CaptureTarget, FrameSource,
+    ImageSource, RangeMap,
+};
 use character::Block;
-use device_query::{DeviceQuery as _, DeviceState, MouseState};
-use dict::{Dictionary, DictionaryEntry};
+use collocation::Collocations;
+use config::Config;
+use correction::Corrections;
+use dict::{DictionaryEntry, DictionaryGroup};
+use environment::cursor_position;
+pub use error::{Error, Result};
 use geo::{Coord, EuclideanDistance as _, LineString, Polygon, Rect};
+use history::History;
+use hover::{reset_hover, update_hover, Dwell, Hover, HoverState};
 use image::{Rgb, RgbImage};
 use imageproc::point::Point;
+use known::KnownWords;
+use live::LiveCapture;
+use metrics::Metrics;
 use ordered_float::OrderedFloat;
-use parking_lot::RwLock;
+use parking_lot::{Mutex, RwLock};
+use payload::{build_block_info, BlockInfo, OverlayBlocks};
+use segment::SegmentCache;
+use spatial::CharIndex;
+use stats::Stats;
 use unicode_blocks::{is_cjk, CJK_SYMBOLS_AND_PUNCTUATION, HALFWIDTH_AND_FULLWIDTH_FORMS};
+use user_dict::UserDictionary;
 use xcap::Monitor;
 
+pub mod anki;
 pub mod capture;
+pub mod cedict;
 pub mod character;
+pub mod collocation;
+pub mod color;
+pub mod config;
+pub mod correction;
+pub mod deinflect;
 pub mod dict;
+#[cfg(windows)]
+pub mod duplication;
+pub mod engine_cache;
+pub mod environment;
+pub mod error;
+pub mod filter;
+pub mod grammar;
+pub mod history;
+pub mod hooks;
+pub mod hover;
+pub mod jmdict;
+pub mod known;
+pub mod lens;
+pub mod live;
+pub mod memory;
+pub mod metrics;
+pub mod payload;
+pub mod pinyin;
+pub mod reading;
+pub mod ruby;
+#[cfg(target_os = "macos")]
+pub mod screen_stream;
+pub mod segment;
+#[cfg(feature = "server")]
+pub mod server;
+pub mod session;
+pub mod spatial;
+pub mod speech;
+pub mod stardict;
+pub mod stats;
+pub mod user_dict;
+#[cfg(feature = "websocket")]
+pub mod websocket;
+pub mod yomitan;
 
 pub struct Definitions {
-    pub dict: Dictionary,
+    pub dict: DictionaryGroup,
+    /// Call [`Definitions::reindex`] after changing these
     pub ocr_strings: Vec<Block>,
     pub definitions: Vec<DictionaryEntry>,
+    char_index: CharIndex,
+    segments: SegmentCache,
 }
 
 impl Definitions {
-    pub fn new(dict: Dictionary) -> Self {
+    pub fn new(dict: DictionaryGroup) -> Self {
         Self {
             dict,
             ocr_strings: Vec::new(),
             definitions: Vec::new(),
+            char_index: CharIndex::default(),
+            segments: SegmentCache::default(),
         }
     }
 
-    pub fn update(&mut self, text: &str) {
+    /// Rebuilds the hit-testing index over the character boxes of `ocr_strings`.
+    pub fn reindex(&mut self) {
+        self.char_index = CharIndex::new(&self.ocr_strings);
+        self.segments.clear();
+    }
+
+    /// Replaces the user dictionary, see [`DictionaryGroup::set_user_entries`].
+    pub fn set_user_entries(&mut self, entries: Vec<DictionaryEntry>) {
+        self.dict.set_user_entries(entries);
+        self.segments.clear();
+    }
+
+    /// See [`SegmentCache::word_start`]
+    pub fn word_start(&mut self, text: &str, index: usize) -> usize {
+        self.segments.word_start(&self.dict, text, index)
+    }
+
+    /// Same as [`find_closest_char`], but through the index built by [`Definitions::reindex`].
+    pub fn closest_char(&self, cursor: geo::Point<f32>) -> Result<Hover> {
+        let closest = self.char_index.closest(cursor).ok_or(Error::NoText)?;
+        let block = self.ocr_strings.get(closest.block).ok_or(Error::NoText)?;
+        Ok(Hover {
+            text: block.text.clone(),
+            index: closest.char,
+            distance: closest.distance,
+            rect: closest.rect,
+        })
+    }
+
+    /// Looks up `text`. With `fuzzy`, words that match once a misread character is replaced
+    /// are listed first if they're longer than any exact match, since a single misread
+    /// character otherwise cuts the lookup down to the characters before it.
+    pub fn update(&mut self, text: &str, fuzzy: bool) {
         self.definitions = self.dict.matches(text);
+        if fuzzy {
+            let longest = self.dict.longest_match(text);
+            // A misread character can't hide in a run that's already matched in full
+            if longest >= text.chars().count() {
+                return;
+            }
+            let mut definitions = self.dict.fuzzy_matches(text, longest);
+            definitions.append(&mut self.definitions);
+            self.definitions = definitions;
+        }
+    }
+
+    /// Up to `limit` of the current definitions starting at `offset`. Common single characters
+    /// can have hundreds of matches, so frontends send them in pages.
+    pub fn page(&self, offset: usize, limit: usize) -> &[DictionaryEntry] {
+        let start = offset.min(self.definitions.len());
+        let end = offset.saturating_add(limit).min(self.definitions.len());
+        &self.definitions[start..end]
     }
 }
 
 pub struct LiveOcr {
     pub capture_state: Arc<CaptureState>,
+    pub config: Config,
     pub enabled: bool,
     pub definitions: Definitions,
-    pub hovering: Option<(String, usize, Rect<f32>)>,
+    pub hover: HoverState,
+    /// Word waiting for the hover dwell delay before it's looked up
+    pub dwell: Option<Dwell>,
     pub monitor: Option<Monitor>,
+    /// Window the current OCR pass captured, `None` if it captured the whole monitor. Live
+    /// mode recaptures the same window.
+    pub target_window: Option<String>,
+    /// Range stretch measured on the first frame of the current OCR pass, applied to every
+    /// frame of it. Only set for monitors listed in [`Config::hdr_monitors`].
+    pub range_map: Option<RangeMap>,
+    /// Name of the application OCR last ran on
+    pub app: Option<String>,
+    pub corrections: Corrections,
+    pub known_words: KnownWords,
+    pub user_dictionary: UserDictionary,
+    /// Words seen in the same sentences, for collocation hints
+    pub collocations: Collocations,
+    pub history: History,
+    /// Frame the current OCR results were recognized from, for change detection in live mode
+    /// and screenshots of exported words
+    pub last_frame: Option<ImageSource>,
+    /// The OCR results weren't captured from the screen, like a clipboard image or a restored
+    /// session, so live mode leaves them alone
+    pub static_results: bool,
+    pub live_capture: Arc<Mutex<LiveCapture>>,
+    pub stats: Stats,
+    pub metrics: Metrics,
+}
+
+impl LiveOcr {
+    /// The blocks of the current OCR pass with their geometry. Empty while OCR is off.
+    pub fn blocks(&self) -> Vec<BlockInfo> {
+        self.definitions
+            .ocr_strings
+            .iter()
+            .map(build_block_info)
+            .collect()
+    }
+
+    /// The blocks with the monitor they were captured on, for overlays covering it. `None`
+    /// while OCR is off.
+    pub fn overlay_blocks(&self) -> Option<OverlayBlocks> {
+        let monitor = self.monitor.as_ref().filter(|_| self.enabled)?;
+        Some(OverlayBlocks {
+            monitor: monitor_bounds(monitor),
+            blocks: self.blocks(),
+        })
+    }
 }
 
 pub fn to_geo_poly(points: &[Point<i32>]) -> Polygon<f32> {
@@ -55,6 +218,19 @@ pub fn to_geo_poly(points: &[Point<i32>]) -> Polygon<f32> {
     Polygon::new(LineString::new(points), vec![])
 }
 
+pub fn monitor_bounds(monitor: &Monitor) -> Rect<f32> {
+    Rect::new(
+        Coord {
+            x: monitor.x() as f32,
+            y: monitor.y() as f32,
+        },
+        Coord {
+            x: monitor.x() as f32 + monitor.width() as f32,
+            y: monitor.y() as f32 + monitor.height() as f32,
+        },
+    )
+}
+
 pub fn draw_outline_geo(image: &mut RgbImage, b_box: geo::Rect<f32>, color: Rgb<u8>) {
     let min_x = (b_box.min().x.round() as u32).clamp(0, image.width() - 1);
     let min_y = (b_box.min().y.round() as u32).clamp(0, image.height() - 1);
@@ -72,96 +248,206 @@ pub fn draw_outline_geo(image: &mut RgbImage, b_box: geo::Rect<f32>, color: Rgb<
     }
 }
 
+/// Whether `ch` can be part of a word, as opposed to punctuation or non-CJK text.
+pub fn is_meaningful(ch: char) -> bool {
+    is_cjk(ch)
+        && ![CJK_SYMBOLS_AND_PUNCTUATION, HALFWIDTH_AND_FULLWIDTH_FORMS]
+            .contains(&unicode_blocks::find_unicode_block(ch).unwrap())
+}
+
 pub fn longest_meaningful_string(text: &str, from: usize) -> String {
     text.chars()
         .skip(from)
-        .take_while(|ch| {
-            is_cjk(*ch)
-                && ![CJK_SYMBOLS_AND_PUNCTUATION, HALFWIDTH_AND_FULLWIDTH_FORMS]
-                    .contains(&unicode_blocks::find_unicode_block(*ch).unwrap())
-        })
+        .take_while(|ch| is_meaningful(*ch))
         .collect()
 }
 
-pub fn find_closest_char(
-    ocr_strings: &[Block],
-    cursor: geo::Point<f32>,
-) -> (String, usize, f32, Rect<f32>) {
+pub fn find_closest_char(ocr_strings: &[Block], cursor: geo::Point<f32>) -> Result<Hover> {
     ocr_strings
         .iter()
-        .map(|(text, chars)| {
-            let (closest_char, closest_distance, closest_rect) = chars
-                .iter()
-                .map(|(ch, rect)| (*ch, OrderedFloat(rect.euclidean_distance(&cursor)), *rect))
-                .min_by_key(|(_, distance, _)| *distance)
-                .unwrap_or((
-                    0,
-                    OrderedFloat(f32::INFINITY),
-                    Rect::new(Coord::zero(), Coord::zero()),
-                ));
-            (text.as_str(), closest_char, closest_distance, closest_rect)
+        .flat_map(|block| {
+            block.chars.iter().map(|it| Hover {
+                text: block.text.clone(),
+                index: it.index,
+                distance: it.rect.euclidean_distance(&cursor),
+                rect: it.rect,
+            })
         })
-        .min_by_key(|(_, _, distance, _)| *distance)
-        .map(|(a, b, c, d)| (a.to_string(), b, *c, d))
-        .unwrap()
+        .min_by_key(|hover| OrderedFloat(hover.distance))
+        .ok_or(Error::NoText)
 }
 
 pub type OcrState = Arc<RwLock<LiveOcr>>;
 
-pub fn update_hover(
-    state: &mut LiveOcr,
-    position: (i32, i32),
-) -> Option<(Option<Rect<f32>>, Vec<DictionaryEntry>)> {
-    let point = geo::point!(x: position.0 as f32, y: position.1 as f32);
-    let (closest_string, closest_char, closest_distance, closest_rect) =
-        find_closest_char(&state.definitions.ocr_strings, point);
-
-    if closest_distance < 5.0 {
-        if let Some((prev_str, prev_char, _)) = &state.hovering {
-            if &closest_string == prev_str && closest_char == *prev_char {
-                return None;
-            }
-        }
-        state.hovering = Some((closest_string.to_owned(), closest_char, closest_rect));
-        let longest_string = longest_meaningful_string(&closest_string, closest_char);
-        state.definitions.update(&longest_string);
-        Some((Some(closest_rect), state.definitions.definitions.clone()))
-    } else if state.hovering.is_some() {
-        state.definitions.definitions.clear();
-        state.hovering.take();
-
-        Some((None, Vec::new()))
-    } else {
-        None
-    }
-}
-
 pub enum Action {
     UpdateOcr,
     CloseTooltip,
     None,
 }
 
-pub fn toggle(state: &mut LiveOcr) -> Action {
+pub fn toggle(state: &mut LiveOcr, options: &CaptureOptions) -> Result<Action> {
     log::info!("Toggled");
     state.enabled = !state.enabled;
     if state.enabled {
-        state.definitions.ocr_strings.clear();
-        let device_state = DeviceState::new();
-        let MouseState {
-            coords: (cursor_x, cursor_y),
-            ..
-        } = device_state.get_mouse();
-        let monitor = Monitor::from_point(cursor_x, cursor_y).unwrap();
-        let ocr_state = state.capture_state.clone().capture(&monitor);
-        state.monitor = Some(monitor);
-        state.definitions.ocr_strings = ocr_state;
-        update_hover(state, device_state.get_mouse().coords);
-        Action::UpdateOcr
+        if let Err(err) = enable(state, options) {
+            state.enabled = false;
+            return Err(err);
+        }
+        Ok(Action::UpdateOcr)
     } else {
-        state.hovering = None;
+        reset_hover(state);
         state.monitor = None;
-        state.definitions.definitions.clear();
-        Action::CloseTooltip
+        state.range_map = None;
+        state.last_frame = None;
+        state.static_results = false;
+        state.live_capture = Default::default();
+        state.stats.stop_reading();
+        Ok(Action::CloseTooltip)
+    }
+}
+
+/// Name recorded as the application for OCR on images that weren't captured from the screen
+pub const IMAGE_APP: &str = "Image";
+
+/// Runs OCR on an image shown on screen at `frame.origin`, e.g. a pasted screenshot in a
+/// window, instead of capturing the screen. Hovering it works the same as a capture.
+pub fn recognize_image(
+    state: &RwLock<LiveOcr>,
+    frame: ImageSource,
+    options: &CaptureOptions,
+) -> Result<Action> {
+    let (x, y) = frame.origin;
+    let monitor =
+        Monitor::from_point(x, y).map_err(|err| CaptureError::Capture(err.to_string()))?;
+    // Don't hold the lock while OCR runs, hovering needs it
+    let (capture_state, small_text, options) = {
+        let state = state.read();
+        let options = options.with_config(&state.config);
+        (
+            state.capture_state.clone(),
+            state.config.small_text,
+            options,
+        )
+    };
+    let started = Instant::now();
+    let blocks = capture_state
+        .clone()
+        .capture(&frame, &options)
+        .map(|blocks| capture_state.process_small_text(&frame, blocks, small_text, &options));
+
+    let mut state = state.write();
+    let state = &mut *state;
+    state.metrics.record_ocr(started.elapsed(), blocks.is_ok());
+    state.metrics.save();
+    let mut blocks = state.config.block_filter.apply(blocks?);
+
+    state.enabled = true;
+    state.monitor = Some(monitor);
+    state.target_window = None;
+    state.range_map = None;
+    state.last_frame = Some(frame);
+    state.static_results = true;
+    state.corrections.apply(IMAGE_APP, &mut blocks);
+    state.definitions.ocr_strings = blocks;
+    state.definitions.reindex();
+    collocation::record_ocr(state);
+    state.app = Some(IMAGE_APP.to_string());
+    if let Some(position) = cursor_position() {
+        update_hover(state, position);
+    }
+    Ok(Action::UpdateOcr)
+}
+
+/// Captures and recognizes the screen or target window for a new OCR pass.
+fn enable(state: &mut LiveOcr, options: &CaptureOptions) -> Result<()> {
+    // Captures with an explicit detection size don't say anything about the tuned one
+    let budget = state
+        .config
+        .latency_budget_ms
+        .filter(|_| options.max_side_len.is_none())
+        .map(Duration::from_millis);
+    let mut options = options.with_config(&state.config);
+    if budget.is_some() {
+        options.max_side_len = state.metrics.tuned_max_side_len;
+    }
+    let options = &options;
+    state.definitions.ocr_strings.clear();
+    state.definitions.reindex();
+    let cursor = cursor_position();
+    let target_window = match &options.target {
+        Some(CaptureTarget::Window(target)) => Some(target.clone()),
+        _ => None,
+    };
+    let window = target_window
+        .as_ref()
+        .map(|target| {
+            find_window(target).ok_or_else(|| CaptureError::WindowNotFound(target.clone()))
+        })
+        .transpose()?;
+    let monitor = match &window {
+        Some(window) => window.current_monitor(),
+        None => {
+            let (x, y) = cursor.ok_or(CaptureError::NoCursor)?;
+            Monitor::from_point(x, y).map_err(|err| CaptureError::Capture(err.to_string()))?
+        }
+    };
+    let source: &dyn FrameSource = match &window {
+        Some(window) => window,
+        None => &monitor,
+    };
+    log::info!("Capturing monitor {}", monitor.name());
+    let hdr = state
+        .config
+        .hdr_monitors
+        .iter()
+        .any(|name| monitor.name() == name.as_str());
+    let mut range_map = None;
+    let started = Instant::now();
+    // Capture the frame separately from OCR so live mode can diff against it later
+    let ocr_state = source.capture_frame().and_then(|image| {
+        range_map = hdr.then(|| RangeMap::measure(&image)).flatten();
+        let image = match range_map {
+            Some(range_map) => range_map.apply(image),
+            None => image,
+        };
+        let frame = ImageSource {
+            image,
+            origin: source.origin(),
+        };
+        let blocks = state.capture_state.clone().capture(&frame, options)?;
+        let blocks = state.capture_state.process_small_text(
+            &frame,
+            blocks,
+            state.config.small_text,
+            options,
+        );
+        Ok((blocks, frame))
+    });
+    let elapsed = started.elapsed();
+    state.metrics.record_ocr(elapsed, ocr_state.is_ok());
+    if let Some(budget) = budget.filter(|_| ocr_state.is_ok()) {
+        state.metrics.tune_max_side_len(elapsed, budget);
+    }
+    state.metrics.save();
+    let (ocr_state, frame) = ocr_state?;
+    state.monitor = Some(monitor);
+    state.target_window = target_window;
+    state.range_map = range_map;
+    state.last_frame = Some(frame);
+    state.static_results = false;
+    let app = window
+        .or_else(|| cursor.and_then(|(x, y)| window_at(x, y)))
+        .map(|window| window.app_name().to_string())
+        .unwrap_or_else(|| "Unknown".to_string());
+    state.stats.start_reading(app.clone());
+    let mut blocks = state.config.block_filter.apply(ocr_state);
+    state.corrections.apply(&app, &mut blocks);
+    state.definitions.ocr_strings = blocks;
+    state.definitions.reindex();
+    collocation::record_ocr(state);
+    state.app = Some(app);
+    if let Some(position) = cursor_position() {
+        update_hover(state, position);
     }
+    Ok(())
 }
@@ -0,0 +1,478 @@
+use std::sync::Arc;
+
+use geo::{coord, Intersects, Rect, Translate};
+use image::{imageops, RgbaImage};
+#[cfg(any(windows, target_os = "macos"))]
+use parking_lot::Mutex;
+use parking_lot::RwLock;
+use xcap::Monitor;
+
+#[cfg(windows)]
+use crate::duplication::Duplication;
+#[cfg(target_os = "macos")]
+use crate::screen_stream::ScreenStream;
+use crate::{
+    capture::{find_window, CaptureOptions, CaptureState, FrameSource, ImageSource, RangeMap},
+    character::Block,
+    collocation,
+    config::SmallText,
+    environment::cursor_position,
+    hover::{find_block, update_hover, HoverEvent},
+    LiveOcr,
+};
+
+/// Side length of the tiles frames are compared in, in pixels
+const TILE_SIZE: u32 = 32;
+/// Sum of channel differences a pixel needs before it counts as changed, to ignore compression
+/// noise and dithering
+const PIXEL_THRESHOLD: u32 = 48;
+
+pub enum Refresh {
+    Unchanged,
+    /// OCR results changed, with the resulting hover transition
+    Changed(Option<HoverEvent>),
+}
+
+/// A frame captured for a live mode refresh.
+struct LiveFrame {
+    image: RgbaImage,
+    /// Screen space position of the frame's top left corner
+    origin: (i32, i32),
+    /// Regions redrawn since the previous frame, in frame pixels. `None` if the capture backend
+    /// doesn't know, in which case the frames are compared.
+    dirty: Option<Vec<Rect<f32>>>,
+}
+
+/// Capture resources kept between live mode refreshes.
+#[derive(Default)]
+pub struct LiveCapture {
+    /// Desktop duplication session for the monitor OCR runs on
+    #[cfg(windows)]
+    duplication: Option<Duplication>,
+    /// Desktop duplication couldn't be started, don't retry on every refresh
+    #[cfg(windows)]
+    unavailable: bool,
+    /// ScreenCaptureKit stream of the monitor OCR runs on
+    #[cfg(target_os = "macos")]
+    stream: Option<ScreenStream>,
+    /// The stream couldn't be started, don't retry on every refresh
+    #[cfg(target_os = "macos")]
+    unavailable: bool,
+}
+
+#[cfg(windows)]
+enum Duplicated {
+    Frame(RgbaImage, Vec<Rect<f32>>),
+    Unchanged,
+    Unavailable,
+}
+
+#[cfg(windows)]
+impl LiveCapture {
+    fn duplicate(&mut self, origin: (i32, i32)) -> Duplicated {
+        if self.unavailable {
+            return Duplicated::Unavailable;
+        }
+        let mut duplication = match self.duplication.take() {
+            Some(duplication) if duplication.origin() == origin => duplication,
+            _ => match Duplication::new(origin) {
+                Ok(duplication) => duplication,
+                Err(err) => {
+                    log::warn!("Desktop duplication unavailable, using screenshots: {err}");
+                    self.unavailable = true;
+                    return Duplicated::Unavailable;
+                }
+            },
+        };
+        match duplication.next_frame() {
+            Ok(frame) => {
+                self.duplication = Some(duplication);
+                match frame {
+                    Some(frame) => Duplicated::Frame(frame.image, frame.dirty),
+                    None => Duplicated::Unchanged,
+                }
+            }
+            // The session is dropped and started again on the next refresh
+            Err(err) => {
+                log::warn!("Desktop duplication lost: {err}");
+                Duplicated::Unavailable
+            }
+        }
+    }
+}
+
+#[cfg(target_os = "macos")]
+enum Streamed {
+    Frame(RgbaImage),
+    Unchanged,
+    Unavailable,
+}
+
+#[cfg(target_os = "macos")]
+impl LiveCapture {
+    fn stream(&mut self, monitor: &Monitor) -> Streamed {
+        if self.unavailable {
+            return Streamed::Unavailable;
+        }
+        let mut stream = match self.stream.take() {
+            Some(stream) if stream.monitor_id() == monitor.id() => stream,
+            _ => match ScreenStream::new(monitor) {
+                Ok(stream) => stream,
+                Err(err) => {
+                    log::warn!("ScreenCaptureKit unavailable, using screenshots: {err}");
+                    self.unavailable = true;
+                    return Streamed::Unavailable;
+                }
+            },
+        };
+        let frame = stream.next_frame();
+        self.stream = Some(stream);
+        match frame {
+            Some(frame) => Streamed::Frame(frame),
+            None => Streamed::Unchanged,
+        }
+    }
+}
+
+/// What a refresh needs from the state, copied out under a short lock so capture and OCR can
+/// run without holding it. Hovering needs the lock on every mouse move.
+struct LivePass {
+    capture_state: Arc<CaptureState>,
+    #[cfg(any(windows, target_os = "macos"))]
+    live_capture: Arc<Mutex<LiveCapture>>,
+    monitor: Monitor,
+    target_window: Option<String>,
+    range_map: Option<RangeMap>,
+    small_text: Option<SmallText>,
+    options: CaptureOptions,
+}
+
+impl LivePass {
+    /// `None` while OCR is off.
+    fn of(state: &LiveOcr, options: &CaptureOptions) -> Option<Self> {
+        let monitor = state.monitor.clone().filter(|_| state.enabled)?;
+        Some(Self {
+            capture_state: state.capture_state.clone(),
+            #[cfg(any(windows, target_os = "macos"))]
+            live_capture: state.live_capture.clone(),
+            monitor,
+            target_window: state.target_window.clone(),
+            range_map: state.range_map,
+            small_text: state.config.small_text,
+            options: options.with_config(&state.config),
+        })
+    }
+
+    /// Whether OCR still runs on the same source, so results from this pass still apply.
+    fn is_current(&self, state: &LiveOcr) -> bool {
+        state.enabled
+            && state.target_window == self.target_window
+            && state
+                .monitor
+                .as_ref()
+                .is_some_and(|monitor| monitor.id() == self.monitor.id())
+    }
+
+    /// Captures the next live mode frame, with the regions redrawn since the previous one if
+    /// the capture backend knows them. Whole monitors are captured through desktop duplication
+    /// on Windows and ScreenCaptureKit on macOS, which are faster than a screenshot and skip
+    /// frames where nothing was redrawn. `None` if nothing changed or capture failed.
+    fn capture_live(&self) -> Option<LiveFrame> {
+        #[cfg(windows)]
+        if self.target_window.is_none() {
+            let origin = (self.monitor.x(), self.monitor.y());
+            match self.live_capture.lock().duplicate(origin) {
+                Duplicated::Frame(image, dirty) => {
+                    let image = match self.range_map {
+                        Some(range_map) => range_map.apply(image),
+                        None => image,
+                    };
+                    return Some(LiveFrame {
+                        image,
+                        origin,
+                        dirty: Some(dirty),
+                    });
+                }
+                Duplicated::Unchanged => return None,
+                Duplicated::Unavailable => {}
+            }
+        }
+        #[cfg(target_os = "macos")]
+        if self.target_window.is_none() {
+            let origin = (self.monitor.x(), self.monitor.y());
+            match self.live_capture.lock().stream(&self.monitor) {
+                Streamed::Frame(image) => {
+                    let image = match self.range_map {
+                        Some(range_map) => range_map.apply(image),
+                        None => image,
+                    };
+                    return Some(LiveFrame {
+                        image,
+                        origin,
+                        dirty: None,
+                    });
+                }
+                Streamed::Unchanged => return None,
+                Streamed::Unavailable => {}
+            }
+        }
+        self.capture().map(|(image, origin)| LiveFrame {
+            image,
+            origin,
+            dirty: None,
+        })
+    }
+
+    fn capture(&self) -> Option<(RgbaImage, (i32, i32))> {
+        capture_source(&self.monitor, self.target_window.as_deref(), self.range_map)
+    }
+
+    /// Runs OCR on `region` of the frame, in frame pixels. Returns the region in screen space
+    /// with the blocks found in it, or `None` if OCR failed.
+    fn recognize(
+        &self,
+        frame: &RgbaImage,
+        origin: (i32, i32),
+        region: Rect<f32>,
+    ) -> Option<(Rect<f32>, Vec<Block>)> {
+        let region = intersection(region, frame_rect(frame));
+        let (x, y) = (region.min().x as u32, region.min().y as u32);
+        let crop = ImageSource {
+            image: imageops::crop_imm(frame, x, y, region.width() as u32, region.height() as u32)
+                .to_image(),
+            origin: (origin.0 + x as i32, origin.1 + y as i32),
+        };
+        let blocks = match self.capture_state.clone().capture(&crop, &self.options) {
+            Ok(blocks) => blocks,
+            Err(err) => {
+                log::warn!("OCR failed: {err}");
+                return None;
+            }
+        };
+        let blocks =
+            self.capture_state
+                .process_small_text(&crop, blocks, self.small_text, &self.options);
+        log::info!("Re-ran OCR on {region:?}, found {} blocks", blocks.len());
+        Some((region.translate(origin.0 as f32, origin.1 as f32), blocks))
+    }
+}
+
+/// Recaptures the current source and re-runs OCR only on the part of the frame that changed
+/// since the last capture. Blocks that overlap the changed part are replaced.
+pub fn refresh(state: &RwLock<LiveOcr>, options: &CaptureOptions) -> Refresh {
+    let pass = {
+        let state = state.read();
+        LivePass::of(&state, options).filter(|_| !state.static_results)
+    };
+    let Some(pass) = pass else {
+        return Refresh::Unchanged;
+    };
+    let Some(LiveFrame {
+        image: frame,
+        origin,
+        dirty,
+    }) = pass.capture_live()
+    else {
+        return Refresh::Unchanged;
+    };
+    let changed = {
+        let state = state.read();
+        let changed = match &state.last_frame {
+            Some(previous) => changed_region_within(&previous.image, &frame, dirty.as_deref()),
+            None => Some(frame_rect(&frame)),
+        };
+        // Grow the region to whole blocks, so lines that only partially changed are
+        // recognized in full rather than cut off at the region border.
+        changed.map(|changed| {
+            state
+                .definitions
+                .ocr_strings
+                .iter()
+                .map(|block| block.bounds)
+                .map(|bounds| bounds.translate(-origin.0 as f32, -origin.1 as f32))
+                .filter(|bounds| bounds.intersects(&changed))
+                .fold(changed, union)
+        })
+    };
+    let Some(changed) = changed else {
+        return Refresh::Unchanged;
+    };
+    let Some((region, blocks)) = pass.recognize(&frame, origin, changed) else {
+        return Refresh::Unchanged;
+    };
+
+    let mut state = state.write();
+    if !pass.is_current(&state) {
+        return Refresh::Unchanged;
+    }
+    replace_region(&mut state, region, blocks);
+    state.last_frame = Some(ImageSource {
+        image: frame,
+        origin,
+    });
+    let update = cursor_position().and_then(|position| update_hover(&mut state, position));
+    Refresh::Changed(update)
+}
+
+/// Recaptures the hovered block and recognizes it again from a crop at native resolution,
+/// rather than the downscaled full frame detection normally sees. Fixes individual misreads
+/// without rescanning the whole screen.
+pub fn recapture_hovered(state: &RwLock<LiveOcr>, options: &CaptureOptions) -> Refresh {
+    let (pass, bounds) = {
+        let state = state.read();
+        let Some(word) = state.hover.word() else {
+            return Refresh::Unchanged;
+        };
+        let bounds = find_block(&state.definitions.ocr_strings, word)
+            .map(|index| state.definitions.ocr_strings[index].bounds);
+        let Some(bounds) = bounds else {
+            return Refresh::Unchanged;
+        };
+        let Some(pass) = LivePass::of(&state, options) else {
+            return Refresh::Unchanged;
+        };
+        (pass, bounds)
+    };
+    let Some((frame, origin)) = pass.capture() else {
+        return Refresh::Unchanged;
+    };
+
+    // Leave some background around the line, detection misses text touching the border
+    let margin = bounds.height() / 2.0;
+    let region = Rect::new(
+        coord![x: bounds.min().x - margin, y: bounds.min().y - margin],
+        coord![x: bounds.max().x + margin, y: bounds.max().y + margin],
+    )
+    .translate(-origin.0 as f32, -origin.1 as f32);
+    let Some((region, blocks)) = pass.recognize(&frame, origin, region) else {
+        return Refresh::Unchanged;
+    };
+
+    let mut state = state.write();
+    if !pass.is_current(&state) {
+        return Refresh::Unchanged;
+    }
+    replace_region(&mut state, region, blocks);
+    let update = cursor_position().and_then(|position| update_hover(&mut state, position));
+    Refresh::Changed(update)
+}
+
+/// Captures a frame of what OCR is currently running on, with its screen space origin.
+pub(crate) fn capture_current(state: &LiveOcr) -> Option<(RgbaImage, (i32, i32))> {
+    let monitor = state.monitor.as_ref().filter(|_| state.enabled)?;
+    capture_source(monitor, state.target_window.as_deref(), state.range_map)
+}
+
+/// Captures `target_window`, or `monitor` if there is none, with the range stretch applied.
+fn capture_source(
+    monitor: &Monitor,
+    target_window: Option<&str>,
+    range_map: Option<RangeMap>,
+) -> Option<(RgbaImage, (i32, i32))> {
+    let window = target_window.and_then(find_window);
+    let source: &dyn FrameSource = match &window {
+        Some(window) => window,
+        None => monitor,
+    };
+    match source.capture_frame() {
+        Ok(frame) => {
+            let frame = match range_map {
+                Some(range_map) => range_map.apply(frame),
+                None => frame,
+            };
+            Some((frame, source.origin()))
+        }
+        Err(err) => {
+            log::warn!("Recapture failed: {err}");
+            None
+        }
+    }
+}
+
+/// Replaces the blocks overlapping `region`, in screen space, with `blocks`.
+fn replace_region(state: &mut LiveOcr, region: Rect<f32>, blocks: Vec<Block>) {
+    state
+        .definitions
+        .ocr_strings
+        .retain(|block| !block.bounds.intersects(&region));
+    let mut blocks = state.config.block_filter.apply(blocks);
+    if let Some(app) = &state.app {
+        state.corrections.apply(app, &mut blocks);
+    }
+    state.definitions.ocr_strings.extend(blocks);
+    state.definitions.reindex();
+    collocation::record_ocr(state);
+}
+
+/// Bounding box of the tiles that differ between two frames, in frame pixels. Frames of
+/// different sizes are treated as entirely changed.
+pub fn changed_region(previous: &RgbaImage, current: &RgbaImage) -> Option<Rect<f32>> {
+    changed_region_within(previous, current, None)
+}
+
+/// Like [`changed_region`], but only compares the tiles overlapping `dirty`, the regions the
+/// capture backend reported as redrawn.
+fn changed_region_within(
+    previous: &RgbaImage,
+    current: &RgbaImage,
+    dirty: Option<&[Rect<f32>]>,
+) -> Option<Rect<f32>> {
+    if previous.dimensions() != current.dimensions() {
+        return Some(frame_rect(current));
+    }
+
+    let (width, height) = current.dimensions();
+    let mut region: Option<Rect<f32>> = None;
+    for tile_y in (0..height).step_by(TILE_SIZE as usize) {
+        for tile_x in (0..width).step_by(TILE_SIZE as usize) {
+            let tile_width = TILE_SIZE.min(width - tile_x);
+            let tile_height = TILE_SIZE.min(height - tile_y);
+            let tile = Rect::new(
+                coord![x: tile_x as f32, y: tile_y as f32],
+                coord![x: (tile_x + tile_width) as f32, y: (tile_y + tile_height) as f32],
+            );
+            if dirty.is_some_and(|dirty| !dirty.iter().any(|rect| overlaps(*rect, tile))) {
+                continue;
+            }
+            let changed = (tile_y..tile_y + tile_height).any(|y| {
+                (tile_x..tile_x + tile_width).any(|x| {
+                    let a = previous.get_pixel(x, y).0;
+                    let b = current.get_pixel(x, y).0;
+                    let diff: u32 = (0..3).map(|i| a[i].abs_diff(b[i]) as u32).sum();
+                    diff > PIXEL_THRESHOLD
+                })
+            });
+            if changed {
+                region = Some(region.map_or(tile, |region| union(region, tile)));
+            }
+        }
+    }
+    region
+}
+
+fn frame_rect(frame: &RgbaImage) -> Rect<f32> {
+    Rect::new(
+        coord![x: 0.0, y: 0.0],
+        coord![x: frame.width() as f32, y: frame.height() as f32],
+    )
+}
+
+fn union(a: Rect<f32>, b: Rect<f32>) -> Rect<f32> {
+    Rect::new(
+        coord![x: a.min().x.min(b.min().x), y: a.min().y.min(b.min().y)],
+        coord![x: a.max().x.max(b.max().x), y: a.max().y.max(b.max().y)],
+    )
+}
+
+/// Whether the rectangles share any area. Unlike `Intersects`, touching edges don't count, so
+/// a dirty rect ending on a tile border doesn't pull in the next tile.
+fn overlaps(a: Rect<f32>, b: Rect<f32>) -> bool {
+    a.min().x < b.max().x && b.min().x < a.max().x && a.min().y < b.max().y && b.min().y < a.max().y
+}
+
+fn intersection(a: Rect<f32>, b: Rect<f32>) -> Rect<f32> {
+    Rect::new(
+        coord![x: a.min().x.max(b.min().x), y: a.min().y.max(b.min().y)],
+        coord![x: a.max().x.min(b.max().x), y: a.max().y.min(b.max().y)],
+    )
+}
@@ -0,0 +1,58 @@
+//! Caps on the session data kept in memory, so continuous mode doesn't keep growing over a
+//! long session, and a rough account of what's currently held for the diagnostics panel.
+
+use std::mem::size_of;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{character::CharBox, LiveOcr};
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct MemoryLimits {
+    /// Lookups kept in memory for the recent lookups list and history queries. The oldest are
+    /// evicted first, the full history stays on disk.
+    pub history_entries: usize,
+    /// Recently counted sentences remembered so they aren't counted again for collocations.
+    /// Older sentences count again if they come back.
+    pub counted_sentences: usize,
+}
+
+impl Default for MemoryLimits {
+    fn default() -> Self {
+        Self {
+            history_entries: 2000,
+            counted_sentences: 10000,
+        }
+    }
+}
+
+/// Approximate memory held by session data. Sizes are in bytes.
+#[derive(Serialize, Clone, Debug)]
+pub struct MemoryUsage {
+    pub history_entries: usize,
+    pub history_bytes: usize,
+    pub ocr_blocks: usize,
+    pub ocr_bytes: usize,
+    /// Frame the current OCR results were recognized from
+    pub frame_bytes: usize,
+}
+
+impl MemoryUsage {
+    pub fn of(state: &LiveOcr) -> Self {
+        let blocks = &state.definitions.ocr_strings;
+        Self {
+            history_entries: state.history.entries().len(),
+            history_bytes: state.history.memory_bytes(),
+            ocr_blocks: blocks.len(),
+            ocr_bytes: blocks
+                .iter()
+                .map(|block| block.text.len() + block.chars.len() * size_of::<CharBox>())
+                .sum(),
+            frame_bytes: state
+                .last_frame
+                .as_ref()
+                .map_or(0, |frame| frame.image.as_raw().len()),
+        }
+    }
+}
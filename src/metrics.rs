@@ -0,0 +1,96 @@
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+
+/// Detection size used until OCR was timed against the latency budget, same as the default
+const DEFAULT_SIDE_LEN: u32 = 2048;
+const MIN_SIDE_LEN: u32 = 960;
+const MAX_SIDE_LEN: u32 = 4096;
+/// Weight of the latest run in the smoothed OCR time, so one slow frame doesn't cause a jump
+const SMOOTHING: f32 = 0.3;
+
+/// Anonymous usage metrics. They are only written to a local file that users can attach to
+/// bug reports, nothing is ever sent anywhere.
+#[derive(Serialize, Deserialize, Default, Clone, Debug)]
+#[serde(default)]
+pub struct Metrics {
+    /// Execution provider used for the current run
+    pub engine: String,
+    pub ocr_runs: u64,
+    pub ocr_failures: u64,
+    pub total_ocr_millis: u64,
+    pub average_ocr_millis: u64,
+    pub lookups: u64,
+    /// Smoothed duration of recent full OCR runs
+    pub recent_ocr_millis: Option<f32>,
+    /// Detection size that keeps OCR within the latency budget on this machine
+    pub tuned_max_side_len: Option<u32>,
+    #[serde(skip)]
+    path: PathBuf,
+}
+
+impl Metrics {
+    pub fn record_ocr(&mut self, duration: Duration, success: bool) {
+        if !success {
+            self.ocr_failures += 1;
+            return;
+        }
+        self.ocr_runs += 1;
+        self.total_ocr_millis += duration.as_millis() as u64;
+        self.average_ocr_millis = self.total_ocr_millis / self.ocr_runs;
+    }
+
+    /// Scales the detection size down when OCR is slower than `budget` and back up when it's
+    /// well within it. Larger detection sizes find smaller text but take longer.
+    pub fn tune_max_side_len(&mut self, duration: Duration, budget: Duration) {
+        let millis = duration.as_millis() as f32;
+        let recent = match self.recent_ocr_millis {
+            Some(recent) => recent * (1.0 - SMOOTHING) + millis * SMOOTHING,
+            None => millis,
+        };
+        self.recent_ocr_millis = Some(recent);
+
+        let side_len = self.tuned_max_side_len.unwrap_or(DEFAULT_SIDE_LEN);
+        let budget = budget.as_millis() as f32;
+        let scale = if recent > budget {
+            // Detection time grows with the area, so shrink the side by the square root
+            (budget / recent).sqrt()
+        } else if recent < budget * 0.6 {
+            1.1
+        } else {
+            return;
+        };
+        let tuned = ((side_len as f32 * scale) as u32).clamp(MIN_SIDE_LEN, MAX_SIDE_LEN);
+        if tuned != side_len {
+            log::info!("OCR took {recent:.0}ms on average, detection size {side_len} -> {tuned}");
+            self.tuned_max_side_len = Some(tuned);
+        }
+    }
+
+    pub fn record_lookup(&mut self) {
+        self.lookups += 1;
+    }
+
+    pub fn save(&self) {
+        let data = serde_json::to_string_pretty(self).unwrap();
+        if let Err(err) = std::fs::write(&self.path, data) {
+            log::error!("Failed to save metrics to {:?}: {err}", self.path);
+        }
+    }
+}
+
+pub fn load(path: impl AsRef<Path>, engine: &str) -> Metrics {
+    let path = path.as_ref();
+    let metrics: Metrics = std::fs::read_to_string(path)
+        .ok()
+        .and_then(|data| serde_json::from_str(&data).ok())
+        .unwrap_or_default();
+    Metrics {
+        engine: engine.to_string(),
+        path: path.to_owned(),
+        ..metrics
+    }
+}
@@ -0,0 +1,396 @@
+use geo::Rect;
+use serde::{Deserialize, Serialize};
+use unicode_blocks::is_cjk;
+
+use crate::{
+    character::{Block, CharBox},
+    collocation::{Collocate, Collocations},
+    config::Config,
+    dict::{DictionaryEntry, DictionaryGroup, IdiomStory, Pinyin, Script, Tone},
+    grammar::GrammarNote,
+};
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TooltipSection {
+    Headword,
+    Traditional,
+    Pinyin,
+    /// Kana reading of Japanese entries
+    Reading,
+    /// Conjugations that were undone to find a Japanese entry
+    Inflection,
+    Definitions,
+    /// Grammar patterns in the hovered sentence, see [`crate::grammar`]. Shown once, after the
+    /// first entry.
+    Grammar,
+}
+
+impl TooltipSection {
+    pub fn defaults() -> Vec<Self> {
+        vec![
+            Self::Headword,
+            Self::Pinyin,
+            Self::Reading,
+            Self::Inflection,
+            Self::Definitions,
+        ]
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(tag = "type", content = "content", rename_all = "snake_case")]
+pub enum Section {
+    Headword(String),
+    Traditional(String),
+    Pinyin(Vec<ToneSyllable>),
+    Reading(String),
+    Inflection(Vec<String>),
+    Definitions(Vec<String>),
+    Grammar(Vec<GrammarNote>),
+}
+
+/// Text colors for light and dark mode, as CSS colors
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ToneColor {
+    pub light: String,
+    pub dark: String,
+}
+
+impl ToneColor {
+    fn new(light: &str, dark: &str) -> Self {
+        Self {
+            light: light.to_string(),
+            dark: dark.to_string(),
+        }
+    }
+}
+
+/// Colors used for pinyin syllables by tone.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum TonePalette {
+    #[default]
+    Solarized,
+    /// Okabe-Ito colors, distinguishable with the common kinds of color blindness
+    ColorblindSafe,
+    /// Saturated colors with maximum contrast against the background
+    HighContrast,
+    /// No colors, tones are only shown by the diacritics
+    Monochrome,
+}
+
+impl TonePalette {
+    /// Color for syllables with `tone`, `None` to use the regular text color.
+    pub fn color(&self, tone: Tone) -> Option<ToneColor> {
+        let (light, dark) = match (self, tone) {
+            (_, Tone::None) | (Self::Monochrome, _) => return None,
+            (Self::Solarized, Tone::First) => ("#268bd2", "#6c71c4"),
+            (Self::Solarized, Tone::Second) => ("#b58900", "#cb4b16"),
+            (Self::Solarized, Tone::Third) => ("#859900", "#2aa198"),
+            (Self::Solarized, Tone::Fourth) => ("#d33682", "#dc322f"),
+            (Self::Solarized, Tone::Fifth) => ("#586e75", "#93a1a1"),
+            (Self::ColorblindSafe, Tone::First) => ("#0072b2", "#56b4e9"),
+            (Self::ColorblindSafe, Tone::Second) => ("#b07800", "#e69f00"),
+            (Self::ColorblindSafe, Tone::Third) => ("#007a5a", "#009e73"),
+            (Self::ColorblindSafe, Tone::Fourth) => ("#c04f00", "#d55e00"),
+            (Self::ColorblindSafe, Tone::Fifth) => ("#6b6b6b", "#999999"),
+            (Self::HighContrast, Tone::First) => ("#0000cc", "#66ccff"),
+            (Self::HighContrast, Tone::Second) => ("#006600", "#66ff66"),
+            (Self::HighContrast, Tone::Third) => ("#7a3d00", "#ffdd00"),
+            (Self::HighContrast, Tone::Fourth) => ("#b00000", "#ff6666"),
+            (Self::HighContrast, Tone::Fifth) => ("#000000", "#ffffff"),
+        };
+        Some(ToneColor::new(light, dark))
+    }
+
+    /// Colors for every tone, indexed by tone number. Index 0 is for syllables without a tone.
+    pub fn colors(&self) -> Vec<Option<ToneColor>> {
+        [
+            Tone::None,
+            Tone::First,
+            Tone::Second,
+            Tone::Third,
+            Tone::Fourth,
+            Tone::Fifth,
+        ]
+        .into_iter()
+        .map(|tone| self.color(tone))
+        .collect()
+    }
+}
+
+/// A pinyin syllable with the color for its tone resolved from the configured palette.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ToneSyllable {
+    pub syllable: String,
+    pub tone: Tone,
+    pub color: Option<ToneColor>,
+}
+
+/// A dictionary entry as displayed in the tooltip, with sections in display order.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct TooltipEntry {
+    /// The entry is a guess at what OCR misread, see [`crate::dict::Dictionary::fuzzy_matches`]
+    pub fuzzy: bool,
+    /// Name of the dictionary the entry came from, entries are grouped by it
+    pub source: String,
+    /// The user marked the word as known, it's shown de-emphasized
+    pub known: bool,
+    /// Proficiency levels, shown as badges
+    pub levels: Vec<String>,
+    /// Script the text on screen matched, if the entry's forms differ
+    pub matched_script: Option<Script>,
+    /// Plain text summary of the entry for screen readers, which can't make sense of the
+    /// per-syllable tone markup
+    pub label: String,
+    pub sections: Vec<Section>,
+}
+
+/// Number of entries sent to the tooltip at once, see [`crate::Definitions::page`]
+pub const TOOLTIP_PAGE_SIZE: usize = 10;
+
+pub fn build_tooltip(definitions: &[DictionaryEntry], config: &Config) -> Vec<TooltipEntry> {
+    definitions
+        .iter()
+        .map(|entry| TooltipEntry {
+            fuzzy: entry.fuzzy,
+            source: entry.source.clone(),
+            known: entry.known,
+            levels: entry.levels.clone(),
+            matched_script: entry.matched_script,
+            label: build_label(entry, config.headword_script),
+            sections: config
+                .tooltip_sections
+                .iter()
+                .filter_map(|section| build_section(entry, *section, config))
+                .collect(),
+        })
+        .collect()
+}
+
+/// The first entry cut down to its headword, pronunciation and first definition, for the
+/// tooltip next to the text while the full definitions are shown in the details panel.
+pub fn build_compact_tooltip(
+    definitions: &[DictionaryEntry],
+    config: &Config,
+) -> Vec<TooltipEntry> {
+    let Some(entry) = definitions.first() else {
+        return Vec::new();
+    };
+    let sections = [
+        TooltipSection::Headword,
+        TooltipSection::Pinyin,
+        TooltipSection::Reading,
+    ]
+    .into_iter()
+    .filter_map(|section| build_section(entry, section, config))
+    .chain(
+        entry
+            .translations
+            .first()
+            .map(|gloss| Section::Definitions(vec![gloss.clone()])),
+    )
+    .collect();
+    vec![TooltipEntry {
+        fuzzy: entry.fuzzy,
+        source: entry.source.clone(),
+        known: entry.known,
+        levels: entry.levels.clone(),
+        matched_script: entry.matched_script,
+        label: build_label(entry, config.headword_script),
+        sections,
+    }]
+}
+
+/// Definitions for each kind of window showing lookups.
+#[derive(Serialize, Clone, Debug)]
+pub struct TooltipTargets {
+    /// For the tooltip next to the text
+    pub tooltip: Vec<TooltipEntry>,
+    /// For the reading strip and the details panel, always the full definitions
+    pub docked: Vec<TooltipEntry>,
+}
+
+/// Builds the tooltip payloads, routing the full definitions to the details panel and a
+/// compact version to the tooltip if the panel is enabled. `grammar` notes are added to the
+/// first full entry if the grammar section is enabled.
+pub fn route_tooltip(
+    definitions: &[DictionaryEntry],
+    grammar: Vec<GrammarNote>,
+    config: &Config,
+) -> TooltipTargets {
+    let mut docked = build_tooltip(definitions, config);
+    if let Some(first) = docked.first_mut().filter(|_| {
+        !grammar.is_empty() && config.tooltip_sections.contains(&TooltipSection::Grammar)
+    }) {
+        first.sections.push(Section::Grammar(grammar));
+    }
+    let tooltip = match config.details_panel {
+        Some(_) => build_compact_tooltip(definitions, config),
+        None => docked.clone(),
+    };
+    TooltipTargets { tooltip, docked }
+}
+
+fn build_label(entry: &DictionaryEntry, script: Script) -> String {
+    let mut parts = Vec::new();
+    if entry.fuzzy {
+        parts.push("Possible misread".to_string());
+    }
+    if entry.matched_script == Some(Script::Traditional) {
+        parts.push("Matched traditional".to_string());
+    }
+    parts.push(entry.headword(script));
+    if script == Script::Simplified && entry.traditional != entry.simplified {
+        parts.push(format!("traditional {}", entry.traditional));
+    }
+    let pronunciation = entry.pronunciation();
+    if !pronunciation.is_empty() {
+        parts.push(pronunciation);
+    }
+    parts.extend(entry.levels.iter().cloned());
+    parts.push(entry.translations.join("; "));
+    parts.join(". ")
+}
+
+fn build_section(
+    entry: &DictionaryEntry,
+    section: TooltipSection,
+    config: &Config,
+) -> Option<Section> {
+    match section {
+        TooltipSection::Headword => Some(Section::Headword(entry.headword(config.headword_script))),
+        // Traditional headwords already include the simplified form
+        TooltipSection::Traditional => (config.headword_script == Script::Simplified
+            && entry.traditional != entry.simplified)
+            .then(|| Section::Traditional(entry.traditional.clone())),
+        TooltipSection::Pinyin => (!entry.pinyin.is_empty())
+            .then(|| Section::Pinyin(build_syllables(&entry.pinyin, config.tone_palette))),
+        TooltipSection::Reading => entry.reading.clone().map(Section::Reading),
+        TooltipSection::Inflection => {
+            (!entry.inflections.is_empty()).then(|| Section::Inflection(entry.inflections.clone()))
+        }
+        TooltipSection::Definitions => Some(Section::Definitions(entry.translations.clone())),
+        // Belongs to the sentence rather than an entry, added by `route_tooltip`
+        TooltipSection::Grammar => None,
+    }
+}
+
+pub(crate) fn build_syllables(pinyin: &[Pinyin], palette: TonePalette) -> Vec<ToneSyllable> {
+    pinyin
+        .iter()
+        .map(|it| ToneSyllable {
+            syllable: it.syllable.clone(),
+            tone: it.tone,
+            color: palette.color(it.tone),
+        })
+        .collect()
+}
+
+/// Everything known about a word, for the full entry window.
+#[derive(Serialize, Clone, Debug)]
+pub struct EntryDetails {
+    /// All entries sharing the headword, e.g. different readings
+    pub entries: Vec<DictionaryEntry>,
+    /// Single character entries for each character of a multi-character headword
+    pub characters: Vec<DictionaryEntry>,
+    /// Pinyin colors by tone number, see [`TonePalette::colors`]
+    pub tone_colors: Vec<Option<ToneColor>>,
+    /// Words most often seen in the same sentences as the headword
+    pub collocates: Vec<Collocate>,
+    /// Origin story if the headword is an idiom in a loaded data pack
+    pub idiom: Option<IdiomStory>,
+}
+
+/// Number of collocates listed in the entry window
+const COLLOCATE_LIMIT: usize = 8;
+
+pub fn build_entry_details(
+    dict: &DictionaryGroup,
+    definitions: &[DictionaryEntry],
+    collocations: &Collocations,
+    palette: TonePalette,
+) -> Option<EntryDetails> {
+    let first = definitions.first()?;
+    let headword = &first.simplified;
+    let entries = definitions
+        .iter()
+        .filter(|entry| entry.simplified == *headword)
+        .cloned()
+        .collect();
+    let characters = if headword.chars().count() > 1 {
+        headword
+            .chars()
+            .flat_map(|ch| dict.matches(&ch.to_string()))
+            .filter(|entry| entry.simplified.chars().count() == 1)
+            .collect()
+    } else {
+        Vec::new()
+    };
+    Some(EntryDetails {
+        entries,
+        characters,
+        tone_colors: palette.colors(),
+        collocates: collocations.top(headword, COLLOCATE_LIMIT),
+        idiom: dict.idiom_story(first).cloned(),
+    })
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Language {
+    Chinese,
+    Japanese,
+    Korean,
+    Other,
+}
+
+/// Guesses the language of OCR text from its script. Any kana makes it Japanese, since
+/// Japanese text mixes kana with kanji.
+pub fn detect_language(text: &str) -> Language {
+    let is_kana = |ch: char| ('\u{3040}'..='\u{30ff}').contains(&ch);
+    let is_hangul = |ch: char| {
+        ('\u{ac00}'..='\u{d7af}').contains(&ch) || ('\u{1100}'..='\u{11ff}').contains(&ch)
+    };
+    if text.chars().any(is_kana) {
+        Language::Japanese
+    } else if text.chars().any(is_hangul) {
+        Language::Korean
+    } else if text.chars().any(is_cjk) {
+        Language::Chinese
+    } else {
+        Language::Other
+    }
+}
+
+/// A recognized block of text with its geometry, for consumers of the OCR layer outside the
+/// tooltip, e.g. overlays and accessibility tools. Coordinates are in screen space.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct BlockInfo {
+    pub text: String,
+    pub rect: Rect<f32>,
+    pub chars: Vec<CharBox>,
+    pub language: Language,
+    pub confidence: f32,
+    /// Outline color as RGB that contrasts with the text's background
+    pub color: [u8; 3],
+}
+
+/// The blocks of an OCR pass with the screen space bounds of the monitor they're on.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct OverlayBlocks {
+    pub monitor: Rect<f32>,
+    pub blocks: Vec<BlockInfo>,
+}
+
+pub fn build_block_info(block: &Block) -> BlockInfo {
+    BlockInfo {
+        text: block.text.clone(),
+        rect: block.bounds,
+        chars: block.chars.clone(),
+        language: detect_language(&block.text),
+        confidence: block.confidence,
+        color: block.color,
+    }
+}
@@ -0,0 +1,342 @@
+//! Pinyin conversion between tone numbers and tone marks.
+
+use bitcode::{Decode, Encode};
+use serde::{Deserialize, Serialize};
+use serde_repr::{Deserialize_repr, Serialize_repr};
+use type_hash::TypeHash;
+
+#[derive(Serialize, Deserialize, Clone, Debug, Encode, Decode, TypeHash)]
+pub struct Pinyin {
+    pub tone: Tone,
+    pub syllable: String,
+}
+
+#[derive(Serialize_repr, Deserialize_repr, Encode, Decode, Clone, Debug, TypeHash, Copy)]
+#[repr(u8)]
+pub enum Tone {
+    First = 1,
+    Second = 2,
+    Third = 3,
+    Fourth = 4,
+    Fifth = 5,
+    None = 0,
+}
+
+impl Tone {
+    pub fn from_u8(tone: u8) -> Self {
+        match tone {
+            1 => Self::First,
+            2 => Self::Second,
+            3 => Self::Third,
+            4 => Self::Fourth,
+            5 => Self::Fifth,
+            _ => panic!("Invalid tone number"),
+        }
+    }
+
+    /// The precomposed letter with the tone mark, or `tone_ch` unchanged if there is none.
+    /// Only lowercase letters are marked, see [`apply_tone`] for the rest.
+    pub fn apply(&self, tone_ch: char) -> char {
+        match self {
+            Tone::First => match tone_ch {
+                'ü' => 'ǖ',
+                'a' => 'ā',
+                'e' => 'ē',
+                'i' => 'ī',
+                'o' => 'ō',
+                'u' => 'ū',
+                _ => tone_ch,
+            },
+            Tone::Second => match tone_ch {
+                'ü' => 'ǘ',
+                'a' => 'á',
+                'e' => 'é',
+                'i' => 'í',
+                'o' => 'ó',
+                'm' => 'ḿ',
+                'n' => 'ń',
+                'u' => 'ú',
+                _ => tone_ch,
+            },
+            Tone::Third => match tone_ch {
+                'ü' => 'ǚ',
+                'a' => 'ǎ',
+                'e' => 'ě',
+                'i' => 'ǐ',
+                'o' => 'ǒ',
+                'u' => 'ǔ',
+                'n' => 'ň',
+                _ => tone_ch,
+            },
+            Tone::Fourth => match tone_ch {
+                'ü' => 'ǜ',
+                'n' => 'ǹ',
+                'a' => 'à',
+                'e' => 'è',
+                'i' => 'ì',
+                'o' => 'ò',
+                'u' => 'ù',
+                _ => tone_ch,
+            },
+            _ => tone_ch,
+        }
+    }
+}
+
+/// Parses space separated pinyin with tone numbers as used by CC-CEDICT, e.g. `ni3 hao3`, into
+/// syllables with tone marks. Syllables without a tone number, e.g. letters in `T恤`, are kept as
+/// they are. Capitalization of proper nouns is kept.
+pub fn parse_pinyin(pinyin: &str) -> Vec<Pinyin> {
+    pinyin
+        .split_whitespace()
+        .map(
+            |it| match it.chars().last().and_then(|ch| ch.to_digit(10)) {
+                Some(tone @ 1..=5) if it.len() > 1 => {
+                    let tone = Tone::from_u8(tone as u8);
+                    let syllable = apply_tone(&normalize_syllable(it), tone);
+                    Pinyin { syllable, tone }
+                }
+                _ => Pinyin {
+                    syllable: it.to_string(),
+                    tone: Tone::None,
+                },
+            },
+        )
+        .collect()
+}
+
+/// Drops the tone number and spells `ü` the way CC-CEDICT writes it (`u:`) or typed input
+/// often does (`v`) properly.
+fn normalize_syllable(syllable: &str) -> String {
+    syllable
+        .trim_end_matches(|ch: char| ch.is_ascii_digit())
+        .replace("u:", "ü")
+        .replace("U:", "Ü")
+        .replace('v', "ü")
+        .replace('V', "Ü")
+}
+
+/// Puts the tone mark for `tone` on the right vowel of a syllable without tone number, e.g.
+/// `hao` and [`Tone::Third`] become `hǎo`. Interjections without vowels like `hm` or `hng` get it
+/// on the nasal. Syllables with nothing to mark are returned unchanged.
+pub fn apply_tone(syllable: &str, tone: Tone) -> String {
+    let chars = syllable.chars().collect::<Vec<_>>();
+    let Some(index) = tonal_letter(&chars) else {
+        return syllable.to_string();
+    };
+    let mut marked = chars[..index].iter().collect::<String>();
+    marked.push_str(&mark(chars[index], tone));
+    marked.extend(&chars[index + 1..]);
+    marked
+}
+
+/// Index of the letter that takes the tone mark.
+fn tonal_letter(chars: &[char]) -> Option<usize> {
+    const VOWELS: &[char] = &['a', 'e', 'i', 'o', 'u', 'ü'];
+    const PREFERENTIAL_VOWELS: &[char] = &['a', 'e', 'o'];
+    let lower = chars
+        .iter()
+        .map(|ch| ch.to_lowercase().next().unwrap_or(*ch))
+        .collect::<Vec<_>>();
+    let vowels = (0..lower.len())
+        .filter(|i| VOWELS.contains(&lower[*i]))
+        .collect::<Vec<_>>();
+    match vowels.as_slice() {
+        [] => lower.iter().position(|ch| matches!(ch, 'm' | 'n')),
+        [vowel] => Some(*vowel),
+        // In iu and ui the mark goes on the second vowel
+        [_, second, ..] => vowels
+            .iter()
+            .copied()
+            .find(|i| PREFERENTIAL_VOWELS.contains(&lower[*i]))
+            .or(Some(*second)),
+    }
+}
+
+/// `letter` with the tone mark, keeping its case. Uses a combining mark for letters without a
+/// precomposed form, e.g. `m̀`.
+fn mark(letter: char, tone: Tone) -> String {
+    let lower = letter.to_lowercase().next().unwrap_or(letter);
+    let combining = match tone {
+        Tone::First => '\u{304}',
+        Tone::Second => '\u{301}',
+        Tone::Third => '\u{30c}',
+        Tone::Fourth => '\u{300}',
+        Tone::Fifth | Tone::None => return letter.to_string(),
+    };
+    let marked = match tone.apply(lower) {
+        precomposed if precomposed != lower => precomposed.to_string(),
+        _ => format!("{lower}{combining}"),
+    };
+    if letter.is_uppercase() {
+        marked.to_uppercase()
+    } else {
+        marked
+    }
+}
+
+/// Converts space separated pinyin with tone numbers to tone marks, e.g. `ni3 hao3` to `nǐ hǎo`.
+pub fn to_tone_marks(numbered: &str) -> String {
+    parse_pinyin(numbered)
+        .into_iter()
+        .map(|it| it.syllable)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Removes tone marks and tone numbers, e.g. `nǐ hǎo` and `ni3 hao3` both become `ni hao`. `ü`
+/// is kept, since it's a different letter rather than a tone.
+pub fn strip_tones(pinyin: &str) -> String {
+    let mut stripped = String::with_capacity(pinyin.len());
+    let mut previous = ' ';
+    for ch in pinyin.chars() {
+        // Tone numbers only ever follow a letter
+        let tone_number = ('1'..='5').contains(&ch) && previous.is_alphabetic();
+        let combining_mark = ('\u{300}'..='\u{30c}').contains(&ch);
+        if !tone_number && !combining_mark {
+            stripped.push(base_letter(ch));
+        }
+        previous = ch;
+    }
+    stripped
+}
+
+/// The letter without its tone mark.
+fn base_letter(ch: char) -> char {
+    match ch {
+        'ā' | 'á' | 'ǎ' | 'à' => 'a',
+        'ē' | 'é' | 'ě' | 'è' => 'e',
+        'ī' | 'í' | 'ǐ' | 'ì' => 'i',
+        'ō' | 'ó' | 'ǒ' | 'ò' => 'o',
+        'ū' | 'ú' | 'ǔ' | 'ù' => 'u',
+        'ǖ' | 'ǘ' | 'ǚ' | 'ǜ' => 'ü',
+        'ḿ' => 'm',
+        'ń' | 'ň' | 'ǹ' => 'n',
+        'Ā' | 'Á' | 'Ǎ' | 'À' => 'A',
+        'Ē' | 'É' | 'Ě' | 'È' => 'E',
+        'Ī' | 'Í' | 'Ǐ' | 'Ì' => 'I',
+        'Ō' | 'Ó' | 'Ǒ' | 'Ò' => 'O',
+        'Ū' | 'Ú' | 'Ǔ' | 'Ù' => 'U',
+        'Ǖ' | 'Ǘ' | 'Ǚ' | 'Ǜ' => 'Ü',
+        'Ḿ' => 'M',
+        'Ń' | 'Ň' | 'Ǹ' => 'N',
+        _ => ch,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn syllables(pinyin: &str) -> Vec<String> {
+        parse_pinyin(pinyin)
+            .into_iter()
+            .map(|it| it.syllable)
+            .collect()
+    }
+
+    #[test]
+    fn parses_tone_numbers() {
+        assert_eq!(syllables("ni3 hao3"), ["nǐ", "hǎo"]);
+        assert_eq!(syllables("zhong1 guo2"), ["zhōng", "guó"]);
+        assert_eq!(syllables("xie4 xie5"), ["xiè", "xie"]);
+        assert!(matches!(parse_pinyin("ma5")[0].tone, Tone::Fifth));
+    }
+
+    #[test]
+    fn parses_u_umlaut_spellings() {
+        assert_eq!(syllables("lu:4 nu:3"), ["lǜ", "nǚ"]);
+        assert_eq!(syllables("lv4 nv3"), ["lǜ", "nǚ"]);
+    }
+
+    #[test]
+    fn keeps_syllables_without_tone_number() {
+        assert_eq!(syllables("T xu4"), ["T", "xù"]);
+        assert!(matches!(parse_pinyin("T")[0].tone, Tone::None));
+        assert_eq!(syllables("3"), ["3"]);
+    }
+
+    #[test]
+    fn marks_the_right_vowel() {
+        assert_eq!(apply_tone("hao", Tone::Third), "hǎo");
+        assert_eq!(apply_tone("xue", Tone::Second), "xué");
+        assert_eq!(apply_tone("zhou", Tone::First), "zhōu");
+        // In iu and ui the second vowel takes the mark
+        assert_eq!(apply_tone("liu", Tone::Second), "liú");
+        assert_eq!(apply_tone("gui", Tone::Fourth), "guì");
+        assert_eq!(apply_tone("lü", Tone::Fourth), "lǜ");
+    }
+
+    #[test]
+    fn marks_nasal_interjections() {
+        assert_eq!(apply_tone("hm", Tone::Second), "hḿ");
+        assert_eq!(apply_tone("hng", Tone::Fourth), "hǹg");
+        // There's no precomposed m with a grave accent
+        assert_eq!(apply_tone("hm", Tone::Fourth), "hm\u{300}");
+    }
+
+    #[test]
+    fn leaves_unmarkable_syllables() {
+        assert_eq!(apply_tone("xyz", Tone::First), "xyz");
+        assert_eq!(apply_tone("ma", Tone::Fifth), "ma");
+    }
+
+    #[test]
+    fn converts_to_tone_marks() {
+        assert_eq!(to_tone_marks("ni3 hao3"), "nǐ hǎo");
+        assert_eq!(to_tone_marks("xian1 sheng5"), "xiān sheng");
+        assert_eq!(to_tone_marks("  ka3   la1 OK  "), "kǎ lā OK");
+    }
+
+    #[test]
+    fn strips_tones() {
+        assert_eq!(strip_tones("nǐ hǎo"), "ni hao");
+        assert_eq!(strip_tones("ni3 hao3"), "ni hao");
+        assert_eq!(strip_tones("lǜ"), "lü");
+        assert_eq!(strip_tones("Běi jīng"), "Bei jing");
+        assert_eq!(strip_tones("hm\u{300}"), "hm");
+        // Numbers that aren't tones are kept
+        assert_eq!(strip_tones("21 san1"), "21 san");
+    }
+
+    #[test]
+    fn keeps_capitalization() {
+        assert_eq!(syllables("Bei3 jing1"), ["Běi", "jīng"]);
+        assert_eq!(syllables("Lu:3 Xun4"), ["Lǚ", "Xùn"]);
+        assert_eq!(syllables("A1 Ou1"), ["Ā", "Ōu"]);
+        assert_eq!(syllables("Hng2 M4"), ["Hńg", "M\u{300}"]);
+    }
+
+    #[test]
+    fn keeps_capitalization_of_interjections() {
+        assert_eq!(syllables("hm5 hng1"), ["hm", "hn\u{304}g"]);
+        assert_eq!(syllables("Hm2 HNG3"), ["Hḿ", "HŇG"]);
+    }
+
+    /// Every pinyin in the bundled dictionary converts without panicking, gets a tone mark on
+    /// every toned syllable and strips back to the same letters.
+    #[test]
+    fn converts_all_of_cedict() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/data/cedict.json");
+        let entries =
+            serde_json::from_str::<Vec<serde_json::Value>>(&std::fs::read_to_string(path).unwrap())
+                .unwrap();
+        for entry in entries {
+            let numbered = entry["pinyin"].as_str().unwrap();
+            for it in parse_pinyin(numbered) {
+                if !matches!(it.tone, Tone::Fifth | Tone::None) {
+                    assert_ne!(strip_tones(&it.syllable), it.syllable, "{numbered}");
+                }
+            }
+            let expected = strip_tones(&numbered.replace("u:", "ü").replace("U:", "Ü"))
+                .split_whitespace()
+                .collect::<Vec<_>>()
+                .join(" ");
+            assert_eq!(
+                strip_tones(&to_tone_marks(numbered)),
+                expected,
+                "{numbered}"
+            );
+        }
+    }
+}
@@ -0,0 +1,86 @@
+//! Reading sessions, stretches of lookups without long breaks in between. Sessions are derived
+//! from the lookup history, so they also cover reading from before they were introduced and
+//! don't need to be started or stopped by hand.
+
+use std::collections::HashSet;
+
+use serde::Serialize;
+
+use crate::history::HistoryEntry;
+
+/// Summary of one reading session. Timestamps are seconds since the Unix epoch.
+#[derive(Serialize, Clone, Debug)]
+pub struct ReadingSession {
+    /// Time of the first lookup, also used to identify the session
+    pub start: u64,
+    /// Time of the last lookup
+    pub end: u64,
+    pub lookups: usize,
+    /// Distinct words looked up
+    pub words: usize,
+    /// Words looked up for the first time ever
+    pub new_words: Vec<String>,
+    /// Applications read in, in order of first lookup
+    pub apps: Vec<String>,
+}
+
+impl ReadingSession {
+    pub fn duration_secs(&self) -> u64 {
+        self.end - self.start
+    }
+}
+
+/// Splits `entries`, oldest first, into sessions wherever there's no lookup for `gap_secs`.
+/// Returns the newest session first.
+pub fn sessions(entries: &[HistoryEntry], gap_secs: u64) -> Vec<ReadingSession> {
+    let mut seen = HashSet::new();
+    let mut sessions = Vec::new();
+    for chunk in split(entries, gap_secs) {
+        let mut words = HashSet::new();
+        let mut new_words = Vec::new();
+        let mut apps = Vec::<String>::new();
+        for entry in chunk {
+            words.insert(entry.word.as_str());
+            if seen.insert(entry.word.as_str()) {
+                new_words.push(entry.word.clone());
+            }
+            if let Some(app) = &entry.app {
+                if !apps.contains(app) {
+                    apps.push(app.clone());
+                }
+            }
+        }
+        sessions.push(ReadingSession {
+            start: chunk[0].timestamp,
+            end: chunk[chunk.len() - 1].timestamp,
+            lookups: chunk.len(),
+            words: words.len(),
+            new_words,
+            apps,
+        });
+    }
+    sessions.reverse();
+    sessions
+}
+
+/// The lookups of the session starting at `start`, for exporting it.
+pub fn session_entries(entries: &[HistoryEntry], gap_secs: u64, start: u64) -> &[HistoryEntry] {
+    split(entries, gap_secs)
+        .find(|chunk| chunk[0].timestamp == start)
+        .unwrap_or_default()
+}
+
+/// The distinct lines words were looked up in during a session, in reading order.
+pub fn transcript(entries: &[HistoryEntry]) -> Vec<String> {
+    let mut lines = Vec::<String>::new();
+    for entry in entries {
+        if lines.last() != Some(&entry.line) {
+            lines.push(entry.line.clone());
+        }
+    }
+    lines
+}
+
+fn split(entries: &[HistoryEntry], gap_secs: u64) -> impl Iterator<Item = &[HistoryEntry]> {
+    entries.chunk_by(move |a, b| b.timestamp.saturating_sub(a.timestamp) < gap_secs)
+}
@@ -0,0 +1,84 @@
+//! Readings for every word on screen, drawn above the text like ruby annotations in an
+//! overlay.
+
+use geo::Rect;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    dict::DictionaryGroup,
+    filter::block_bounds,
+    monitor_bounds,
+    payload::{build_syllables, TonePalette, ToneSyllable},
+    segment::segment,
+    LiveOcr,
+};
+
+/// A word with its reading, resolved from the dictionary.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct RubyWord {
+    /// Screen space box around the word's characters
+    pub rect: Rect<f32>,
+    pub pinyin: Vec<ToneSyllable>,
+    /// Kana reading of Japanese words
+    pub reading: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct RubyOverlay {
+    /// Screen space bounds of the monitor the overlay covers
+    pub monitor: Rect<f32>,
+    pub words: Vec<RubyWord>,
+}
+
+/// Readings for every word of the current OCR pass, or `None` while OCR is disabled. Lines are
+/// split into words with [`segment`], words the dictionary doesn't know are left out.
+pub fn build_ruby(state: &LiveOcr) -> Option<RubyOverlay> {
+    let monitor = state.monitor.as_ref().filter(|_| state.enabled)?;
+    let dict = &state.definitions.dict;
+    let palette = state.config.tone_palette;
+    let words = state
+        .definitions
+        .ocr_strings
+        .iter()
+        .flat_map(|block| {
+            let chars = block.text.chars().collect::<Vec<_>>();
+            segment(dict, &block.text)
+                .into_iter()
+                .filter_map(|span| {
+                    let word = chars[span.clone()].iter().collect::<String>();
+                    let boxes = block
+                        .chars
+                        .iter()
+                        .filter(|it| span.contains(&it.index))
+                        .copied()
+                        .collect::<Vec<_>>();
+                    let (pinyin, reading) = annotate(dict, &word, palette)?;
+                    Some(RubyWord {
+                        rect: block_bounds(&boxes)?,
+                        pinyin,
+                        reading,
+                    })
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect();
+    Some(RubyOverlay {
+        monitor: monitor_bounds(monitor),
+        words,
+    })
+}
+
+fn annotate(
+    dict: &DictionaryGroup,
+    word: &str,
+    palette: TonePalette,
+) -> Option<(Vec<ToneSyllable>, Option<String>)> {
+    let entry = dict
+        .matches(word)
+        .into_iter()
+        .find(|entry| entry.simplified == word || entry.traditional == word)?;
+    if entry.pinyin.is_empty() && entry.reading.is_none() {
+        return None;
+    }
+    Some((build_syllables(&entry.pinyin, palette), entry.reading))
+}
@@ -0,0 +1,128 @@
+//! Monitor capture through ScreenCaptureKit on macOS. Keeps a stream open between captures,
+//! which is much faster than taking a screenshot every time, and only receives a frame when
+//! something on the monitor was redrawn.
+
+use std::{fmt::Display, sync::Arc};
+
+use core_media_rs::cm_sample_buffer::CMSampleBuffer;
+use core_video_rs::cv_pixel_buffer::CVPixelBuffer;
+use image::RgbaImage;
+use parking_lot::Mutex;
+use screencapturekit::{
+    shareable_content::SCShareableContent,
+    stream::{
+        configuration::{pixel_format::PixelFormat, SCStreamConfiguration},
+        content_filter::SCContentFilter,
+        output_trait::SCStreamOutputTrait,
+        output_type::SCStreamOutputType,
+        SCStream,
+    },
+};
+use xcap::Monitor;
+
+use crate::capture::CaptureError;
+
+/// The newest frame the stream delivered that hasn't been taken yet
+type Latest = Arc<Mutex<Option<RgbaImage>>>;
+
+/// A ScreenCaptureKit stream of one monitor.
+pub struct ScreenStream {
+    stream: SCStream,
+    latest: Latest,
+    monitor_id: u32,
+}
+
+// The stream is only started and stopped through `&mut self`, so never from two threads at once.
+// Frames arrive on ScreenCaptureKit's own queue and are handed over through `latest`.
+unsafe impl Send for ScreenStream {}
+
+impl ScreenStream {
+    /// Starts streaming `monitor`. Fails before macOS 12.3, and when the app doesn't have the
+    /// screen recording permission.
+    pub fn new(monitor: &Monitor) -> Result<Self, CaptureError> {
+        let content = SCShareableContent::get().map_err(to_capture_error)?;
+        let display = content
+            .displays()
+            .into_iter()
+            .find(|display| display.display_id() == monitor.id())
+            .ok_or_else(|| {
+                CaptureError::Capture("ScreenCaptureKit doesn't list the monitor".to_string())
+            })?;
+        // Frames are the monitor's size in points rather than pixels, so character boxes line
+        // up with the cursor on Retina displays
+        let config = SCStreamConfiguration::new()
+            .set_width(monitor.width())
+            .and_then(|config| config.set_height(monitor.height()))
+            .and_then(|config| config.set_pixel_format(PixelFormat::BGRA))
+            .and_then(|config| config.set_shows_cursor(false))
+            .map_err(to_capture_error)?;
+        let filter = SCContentFilter::new().with_display_excluding_windows(&display, &[]);
+        let latest = Latest::default();
+        let mut stream = SCStream::new(&filter, &config);
+        stream.add_output_handler(
+            Output {
+                latest: latest.clone(),
+            },
+            SCStreamOutputType::Screen,
+        );
+        stream.start_capture().map_err(to_capture_error)?;
+        Ok(Self {
+            stream,
+            latest,
+            monitor_id: monitor.id(),
+        })
+    }
+
+    pub fn monitor_id(&self) -> u32 {
+        self.monitor_id
+    }
+
+    /// The newest frame, or `None` if nothing was redrawn since the last call.
+    pub fn next_frame(&mut self) -> Option<RgbaImage> {
+        self.latest.lock().take()
+    }
+}
+
+impl Drop for ScreenStream {
+    fn drop(&mut self) {
+        if let Err(err) = self.stream.stop_capture() {
+            log::warn!("Failed to stop the screen capture stream: {err}");
+        }
+    }
+}
+
+struct Output {
+    latest: Latest,
+}
+
+impl SCStreamOutputTrait for Output {
+    fn did_output_sample_buffer(&self, sample: CMSampleBuffer, _of_type: SCStreamOutputType) {
+        // Frames sent while nothing changed don't carry an image
+        let Ok(pixels) = sample.get_pixel_buffer() else {
+            return;
+        };
+        match to_image(&pixels) {
+            Some(image) => *self.latest.lock() = Some(image),
+            None => log::warn!("Failed to read a screen capture frame"),
+        }
+    }
+}
+
+/// Copies a BGRA pixel buffer to memory, converting it to RGBA.
+fn to_image(pixels: &CVPixelBuffer) -> Option<RgbaImage> {
+    let (width, height) = (pixels.get_width(), pixels.get_height());
+    let row_pitch = pixels.get_bytes_per_row() as usize;
+    let guard = pixels.lock().ok()?;
+    let data = guard.as_slice();
+    let mut image = Vec::with_capacity(width as usize * height as usize * 4);
+    for row in data.chunks_exact(row_pitch).take(height as usize) {
+        for bgra in row[..width as usize * 4].chunks_exact(4) {
+            image.extend_from_slice(&[bgra[2], bgra[1], bgra[0], 255]);
+        }
+    }
+    RgbaImage::from_raw(width, height, image)
+}
+
+fn to_capture_error(err: impl Display) -> CaptureError {
+    CaptureError::Capture(err.to_string())
+}
@@ -0,0 +1,178 @@
+//! Word segmentation over the dictionary headwords. Chinese and Japanese text has no spaces,
+//! so the words a line is made of have to be guessed from what the dictionary knows.
+
+use std::{collections::HashMap, ops::Range};
+
+use crate::{dict::DictionaryGroup, is_meaningful};
+
+/// Splits `text` into words, as character ranges. Prefers segmentations with the fewest
+/// characters that aren't part of any known word, then the fewest words, which favors long
+/// dictionary matches without letting one greedy match split its neighbours into nonsense.
+pub fn segment(dict: &DictionaryGroup, text: &str) -> Vec<Range<usize>> {
+    segment_with(&prefix_table(dict, text))
+}
+
+/// The lengths of the headwords starting at each character of `text`, empty for characters
+/// that aren't meaningful.
+fn prefix_table(dict: &DictionaryGroup, text: &str) -> Vec<Vec<usize>> {
+    text.char_indices()
+        .map(|(i, ch)| {
+            if is_meaningful(ch) {
+                dict.prefix_lengths(&text[i..])
+            } else {
+                Vec::new()
+            }
+        })
+        .collect()
+}
+
+/// [`segment`] over the headword lengths from [`prefix_table`].
+fn segment_with(prefixes: &[Vec<usize>]) -> Vec<Range<usize>> {
+    // best[i] is the cost of the best segmentation of the first i characters and the start of
+    // its last word
+    let mut best = vec![((usize::MAX, usize::MAX), 0); prefixes.len() + 1];
+    best[0].0 = (0, 0);
+    for (start, lengths) in prefixes.iter().enumerate() {
+        let ((unknown, words), _) = best[start];
+        if unknown == usize::MAX {
+            continue;
+        }
+        let mut relax = |len: usize, cost: (usize, usize)| {
+            if cost < best[start + len].0 {
+                best[start + len] = (cost, start);
+            }
+        };
+        relax(1, (unknown + 1, words + 1));
+        for &len in lengths {
+            relax(len, (unknown, words + 1));
+        }
+    }
+
+    let mut words = Vec::new();
+    let mut end = prefixes.len();
+    while end > 0 {
+        let start = best[end].1;
+        words.push(start..end);
+        end = start;
+    }
+    words.reverse();
+    words
+}
+
+/// Segmentations of the runs of CJK text hovered so far, so moving the mouse within the same
+/// text doesn't segment it again. Has to be cleared when the text or the dictionaries change.
+#[derive(Default)]
+pub struct SegmentCache {
+    runs: HashMap<String, Run>,
+}
+
+/// A segmented run, with the headword lengths it was segmented from.
+struct Run {
+    words: Vec<Range<usize>>,
+    prefixes: Vec<Vec<usize>>,
+}
+
+impl SegmentCache {
+    pub fn clear(&mut self) {
+        self.runs.clear();
+    }
+
+    /// Start of the word containing the character at `index`, within the run of CJK text
+    /// around it. Falls back to the longest dictionary match covering the character if
+    /// segmentation leaves it on its own, which happens when a misread character splits a word.
+    pub fn word_start(&mut self, dict: &DictionaryGroup, text: &str, index: usize) -> usize {
+        let Some(run) = run_around(text, index) else {
+            return index;
+        };
+        let run_text = text
+            .chars()
+            .skip(run.start)
+            .take(run.len())
+            .collect::<String>();
+        let segmented = self.runs.entry(run_text.clone()).or_insert_with(|| {
+            let prefixes = prefix_table(dict, &run_text);
+            Run {
+                words: segment_with(&prefixes),
+                prefixes,
+            }
+        });
+        let index = index - run.start;
+        let word = segmented
+            .words
+            .iter()
+            .find(|word| word.contains(&index))
+            .cloned()
+            .unwrap_or(index..index + 1);
+        if word.len() > 1 {
+            return run.start + word.start;
+        }
+        run.start + covering_match_start(&segmented.prefixes, index).unwrap_or(word.start)
+    }
+}
+
+/// The run of CJK text around the character at `index`, as a character range, or `None` if
+/// that character isn't part of one.
+fn run_around(text: &str, index: usize) -> Option<Range<usize>> {
+    let chars = text.chars().collect::<Vec<_>>();
+    if !chars.get(index).is_some_and(|ch| is_meaningful(*ch)) {
+        return None;
+    }
+    let run_start = chars[..index]
+        .iter()
+        .rposition(|ch| !is_meaningful(*ch))
+        .map_or(0, |i| i + 1);
+    let run_end = chars[index..]
+        .iter()
+        .position(|ch| !is_meaningful(*ch))
+        .map_or(chars.len(), |i| index + i);
+    Some(run_start..run_end)
+}
+
+/// Tries every substring containing the character at `index` and returns the start of the
+/// longest dictionary match, preferring the start closest to `index` on ties.
+fn covering_match_start(prefixes: &[Vec<usize>], index: usize) -> Option<usize> {
+    (0..=index)
+        .rev()
+        .filter_map(|start| {
+            let len = prefixes[start]
+                .iter()
+                .copied()
+                .filter(|len| start + len > index)
+                .max()?;
+            Some((start, len))
+        })
+        .max_by_key(|(start, len)| (*len, *start))
+        .map(|(start, _)| start)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn avoids_leaving_characters_unmatched() {
+        // 研究 | 研究生 at 0, 生命 at 2: 研究生 would leave 命 on its own
+        let prefixes = [vec![2, 3], vec![], vec![2], vec![]];
+        assert_eq!(segment_with(&prefixes), [0..2, 2..4]);
+    }
+
+    #[test]
+    fn prefers_the_longest_covering_match() {
+        let prefixes = [vec![2, 4], vec![], vec![2], vec![]];
+        assert_eq!(segment_with(&prefixes), vec![(0..4)]);
+    }
+
+    #[test]
+    fn keeps_unknown_characters_on_their_own() {
+        let prefixes = [vec![], vec![2], vec![]];
+        assert_eq!(segment_with(&prefixes), [0..1, 1..3]);
+        assert!(segment_with(&[]).is_empty());
+    }
+
+    #[test]
+    fn finds_the_longest_match_covering_a_character() {
+        let prefixes = [vec![3], vec![2], vec![], vec![]];
+        assert_eq!(covering_match_start(&prefixes, 2), Some(0));
+        assert_eq!(covering_match_start(&prefixes, 3), None);
+    }
+}
@@ -0,0 +1,136 @@
+//! Local HTTP API, so other tools like browser extensions or scripts can use the loaded OCR
+//! models and dictionaries without loading their own.
+//!
+//! - `POST /ocr` with a PNG (or any other format `image` can decode) as the body returns the
+//!   recognized blocks, see [`BlockInfo`].
+//! - `GET /lookup?q=<text>` returns the dictionary entries `text` starts with, longest first.
+//! - `GET /blocks` returns the blocks of the current OCR pass, empty while OCR is off.
+//!
+//! Requests with a `Host` other than `127.0.0.1:<port>` or `localhost:<port>` are refused, so
+//! web pages can't reach the API by rebinding their own domain to the loopback address.
+
+use std::{
+    io::{Cursor, Read as _},
+    net::ToSocketAddrs,
+};
+
+use serde::Serialize;
+use tiny_http::{Header, Method, Request, Response, Server};
+
+use crate::{
+    capture::{CaptureOptions, ImageSource},
+    payload::{build_block_info, BlockInfo},
+    OcrState, Result,
+};
+
+type JsonResponse = Response<Cursor<Vec<u8>>>;
+
+/// Largest image accepted by `POST /ocr`, in bytes
+const MAX_BODY_LEN: u64 = 32 * 1024 * 1024;
+
+/// Serves requests until the process exits. Blocks, so run it on its own thread.
+pub fn serve(addr: impl ToSocketAddrs, state: OcrState) -> Result<()> {
+    let server = Server::http(addr).map_err(|err| std::io::Error::other(err.to_string()))?;
+    let port = server.server_addr().to_ip().map_or(0, |addr| addr.port());
+    log::info!("HTTP API listening on {}", server.server_addr());
+    let hosts = [format!("127.0.0.1:{port}"), format!("localhost:{port}")];
+    for mut request in server.incoming_requests() {
+        let state = state.clone();
+        let hosts = hosts.clone();
+        // A slow client only holds up its own request
+        std::thread::spawn(move || {
+            let allowed = host(&request)
+                .is_some_and(|host| hosts.iter().any(|it| it.eq_ignore_ascii_case(host)));
+            let response = if allowed {
+                handle(&mut request, &state)
+            } else {
+                error(403, "Forbidden host")
+            };
+            if let Err(err) = request.respond(response) {
+                log::warn!("Failed to send HTTP response: {err}");
+            }
+        });
+    }
+    Ok(())
+}
+
+fn host(request: &Request) -> Option<&str> {
+    request
+        .headers()
+        .iter()
+        .find(|header| header.field.equiv("Host"))
+        .map(|header| header.value.as_str())
+}
+
+fn handle(request: &mut Request, state: &OcrState) -> JsonResponse {
+    let url = request.url().to_string();
+    let (path, query) = url.split_once('?').unwrap_or((&url, ""));
+    let result = match (request.method(), path) {
+        (Method::Post, "/ocr") => match read_body(request) {
+            Ok(Some(body)) => ocr(&body, state).and_then(|blocks| json(&blocks)),
+            Ok(None) => return error(413, "Image too large"),
+            Err(err) => Err(err),
+        },
+        (Method::Get, "/lookup") => {
+            let text = form_urlencoded::parse(query.as_bytes())
+                .find(|(key, _)| key == "q")
+                .map(|(_, value)| value.into_owned())
+                .unwrap_or_default();
+            json(&state.read().definitions.dict.matches(&text))
+        }
+        (Method::Get, "/blocks") => json(&state.read().blocks()),
+        _ => return error(404, "Not found"),
+    };
+    result.unwrap_or_else(|err| error(500, &err.to_string()))
+}
+
+/// The request body, or `None` if it's longer than [`MAX_BODY_LEN`].
+fn read_body(request: &mut Request) -> Result<Option<Vec<u8>>> {
+    if request
+        .body_length()
+        .is_some_and(|len| len as u64 > MAX_BODY_LEN)
+    {
+        return Ok(None);
+    }
+    // Chunked bodies don't declare their length up front
+    let mut body = Vec::new();
+    request
+        .as_reader()
+        .take(MAX_BODY_LEN + 1)
+        .read_to_end(&mut body)?;
+    Ok((body.len() as u64 <= MAX_BODY_LEN).then_some(body))
+}
+
+fn ocr(body: &[u8], state: &OcrState) -> Result<Vec<BlockInfo>> {
+    let image = image::load_from_memory(body)
+        .map_err(|err| std::io::Error::other(format!("Invalid image: {err}")))?
+        .to_rgba8();
+    // Don't hold the lock while OCR runs, hovering needs it
+    let (capture_state, options) = {
+        let state = state.read();
+        let options = CaptureOptions::default().with_config(&state.config);
+        (state.capture_state.clone(), options)
+    };
+    let source = ImageSource {
+        image,
+        origin: (0, 0),
+    };
+    let blocks = capture_state.capture(&source, &options)?;
+    Ok(blocks.iter().map(build_block_info).collect())
+}
+
+fn json(body: &impl Serialize) -> Result<JsonResponse> {
+    let body = serde_json::to_vec(body)?;
+    Ok(Response::from_data(body).with_header(content_type()))
+}
+
+fn error(status: u16, message: &str) -> JsonResponse {
+    let body = serde_json::json!({ "error": message }).to_string();
+    Response::from_data(body.into_bytes())
+        .with_status_code(status)
+        .with_header(content_type())
+}
+
+fn content_type() -> Header {
+    Header::from_bytes("Content-Type", "application/json").unwrap()
+}
@@ -0,0 +1,158 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use geo::Rect;
+use serde::{Deserialize, Serialize};
+use xcap::Monitor;
+
+use crate::{
+    character::Block, history, hover::reset_hover, payload::TooltipEntry, Error, LiveOcr, Result,
+};
+
+/// A tooltip pinned to the screen, independent of the hovered word.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PinnedTooltip {
+    pub rect: Rect<f32>,
+    pub monitor: Rect<f32>,
+    pub entries: Vec<TooltipEntry>,
+}
+
+/// Session state written to disk while OCR is enabled, so it can be offered for restore if the
+/// app crashes before OCR is disabled again. Also used for sessions saved by the user, see
+/// [`LiveOcr::save_session`].
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Snapshot {
+    pub blocks: Vec<Block>,
+    /// Position of the captured monitor, used to find it again on restore
+    pub monitor: (i32, i32),
+    pub pins: Vec<PinnedTooltip>,
+    /// When the snapshot was taken, in seconds since the Unix epoch
+    #[serde(default)]
+    pub timestamp: u64,
+}
+
+impl Snapshot {
+    /// Snapshot of the current session, or `None` if OCR is disabled.
+    pub fn new(state: &LiveOcr, pins: Vec<PinnedTooltip>) -> Option<Self> {
+        let monitor = state.monitor.as_ref().filter(|_| state.enabled)?;
+        Some(Self {
+            blocks: state.definitions.ocr_strings.clone(),
+            monitor: (monitor.x(), monitor.y()),
+            pins,
+            timestamp: history::now(),
+        })
+    }
+
+    /// Restores the OCR results without recapturing. Returns `false` if the monitor they were
+    /// captured on is no longer connected.
+    pub fn restore(&self, state: &mut LiveOcr) -> bool {
+        let Ok(monitor) = Monitor::from_point(self.monitor.0, self.monitor.1) else {
+            return false;
+        };
+        reset_hover(state);
+        state.enabled = true;
+        state.monitor = Some(monitor);
+        // Whatever was captured before belongs to another session, and the screen has most
+        // likely changed since these results were saved
+        state.target_window = None;
+        state.range_map = None;
+        state.last_frame = None;
+        state.live_capture = Default::default();
+        state.app = None;
+        state.static_results = true;
+        state.definitions.ocr_strings = self.blocks.clone();
+        state.definitions.reindex();
+        true
+    }
+}
+
+impl LiveOcr {
+    /// Saves the current OCR results to `path`, to look words up in them again later without
+    /// running OCR. Fails with [`Error::NoText`] while OCR is disabled.
+    pub fn save_session(&self, path: impl AsRef<Path>) -> Result<()> {
+        let snapshot = Snapshot::new(self, Vec::new()).ok_or(Error::NoText)?;
+        fs::write(path, serde_json::to_string(&snapshot)?)?;
+        Ok(())
+    }
+
+    /// Restores OCR results saved with [`LiveOcr::save_session`], returning the snapshot they
+    /// came from.
+    pub fn load_session(&mut self, path: impl AsRef<Path>) -> Result<Snapshot> {
+        let snapshot: Snapshot = serde_json::from_str(&fs::read_to_string(path)?)?;
+        if !snapshot.restore(self) {
+            return Err(Error::MonitorDisconnected);
+        }
+        Ok(snapshot)
+    }
+}
+
+/// A session saved by the user, for picking one to open.
+#[derive(Serialize, Clone, Debug)]
+pub struct SavedSession {
+    pub path: PathBuf,
+    pub timestamp: u64,
+    pub lines: usize,
+    /// First recognized line
+    pub preview: String,
+}
+
+/// Sessions saved in `dir`, newest first. Files that can't be read are skipped.
+pub fn list_saved(dir: impl AsRef<Path>) -> Vec<SavedSession> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut sessions = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+        .filter_map(|path| {
+            let data = fs::read_to_string(&path).ok()?;
+            let snapshot = serde_json::from_str::<Snapshot>(&data).ok()?;
+            Some(SavedSession {
+                timestamp: snapshot.timestamp,
+                lines: snapshot.blocks.len(),
+                preview: snapshot
+                    .blocks
+                    .first()
+                    .map(|block| block.text.clone())
+                    .unwrap_or_default(),
+                path,
+            })
+        })
+        .collect::<Vec<_>>();
+    sessions.sort_by_key(|session| std::cmp::Reverse(session.timestamp));
+    sessions
+}
+
+pub fn save(path: impl AsRef<Path>, snapshot: &Snapshot) {
+    let path = path.as_ref();
+    let data = serde_json::to_string(snapshot).unwrap();
+    if let Err(err) = fs::write(path, data) {
+        log::error!("Failed to save session to {path:?}: {err}");
+    }
+}
+
+/// Loads the snapshot left behind by a session that didn't shut down cleanly.
+pub fn load(path: impl AsRef<Path>) -> Option<Snapshot> {
+    let path = path.as_ref();
+    let data = fs::read_to_string(path).ok()?;
+    match serde_json::from_str(&data) {
+        Ok(snapshot) => Some(snapshot),
+        Err(err) => {
+            log::error!("Discarding invalid session {path:?}: {err}");
+            clear(path);
+            None
+        }
+    }
+}
+
+pub fn clear(path: impl AsRef<Path>) {
+    let path = path.as_ref();
+    if path.exists() {
+        if let Err(err) = fs::remove_file(path) {
+            log::error!("Failed to remove session {path:?}: {err}");
+        }
+    }
+}
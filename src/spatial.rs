@@ -0,0 +1,79 @@
+//! Spatial index over the character boxes of the current OCR results, so hover hit-testing
+//! doesn't scan every character on every mouse move.
+
+use geo::Rect;
+use rstar::{PointDistance, RTree, RTreeObject, AABB};
+
+use crate::character::Block;
+
+/// A character box with its position in the OCR results.
+#[derive(Clone, Debug, PartialEq)]
+struct IndexedChar {
+    block: usize,
+    /// Character index within the block's text
+    char: usize,
+    rect: Rect<f32>,
+}
+
+impl RTreeObject for IndexedChar {
+    type Envelope = AABB<[f32; 2]>;
+
+    fn envelope(&self) -> Self::Envelope {
+        AABB::from_corners(
+            [self.rect.min().x, self.rect.min().y],
+            [self.rect.max().x, self.rect.max().y],
+        )
+    }
+}
+
+impl PointDistance for IndexedChar {
+    /// Zero inside the box, like [`geo::EuclideanDistance`] between a rect and a point.
+    fn distance_2(&self, point: &[f32; 2]) -> f32 {
+        self.envelope().distance_2(point)
+    }
+}
+
+/// The character closest to a point.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ClosestChar {
+    /// Index of the block in the OCR results
+    pub block: usize,
+    /// Character index within the block's text
+    pub char: usize,
+    pub distance: f32,
+    pub rect: Rect<f32>,
+}
+
+#[derive(Default)]
+pub struct CharIndex {
+    tree: RTree<IndexedChar>,
+}
+
+impl CharIndex {
+    pub fn new(blocks: &[Block]) -> Self {
+        let chars = blocks
+            .iter()
+            .enumerate()
+            .flat_map(|(block, it)| {
+                it.chars.iter().map(move |char| IndexedChar {
+                    block,
+                    char: char.index,
+                    rect: char.rect,
+                })
+            })
+            .collect();
+        Self {
+            tree: RTree::bulk_load(chars),
+        }
+    }
+
+    pub fn closest(&self, point: geo::Point<f32>) -> Option<ClosestChar> {
+        let point = [point.x(), point.y()];
+        self.tree.nearest_neighbor(&point).map(|it| ClosestChar {
+            block: it.block,
+            char: it.char,
+            distance: it.distance_2(&point).sqrt(),
+            rect: it.rect,
+        })
+    }
+}
@@ -0,0 +1,185 @@
+//! Text to speech through the platform's speech engine (SAPI/WinRT on Windows, AVFoundation on
+//! macOS, Speech Dispatcher on Linux).
+
+use std::{
+    process::Command,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc::{self, Sender},
+    },
+};
+
+use tts::Tts;
+
+use crate::{
+    payload::{detect_language, Language},
+    Error, LiveOcr, Result,
+};
+
+/// Speaks text on a dedicated thread, since not all speech engines can be shared between
+/// threads.
+pub struct Speaker {
+    sender: Sender<String>,
+}
+
+impl Speaker {
+    pub fn new() -> Self {
+        let (sender, receiver) = mpsc::channel::<String>();
+        std::thread::spawn(move || {
+            let mut tts = match Tts::default() {
+                Ok(tts) => tts,
+                Err(err) => {
+                    log::error!("Text to speech is unavailable: {err}");
+                    return;
+                }
+            };
+            for text in receiver {
+                select_voice(&mut tts, detect_language(&text));
+                if let Err(err) = tts.speak(&text, true) {
+                    log::error!("Failed to speak {text}: {err}");
+                }
+            }
+        });
+        Self { sender }
+    }
+
+    /// Speaks `text`, interrupting anything still being spoken.
+    pub fn speak(&self, text: &str) {
+        let _ = self.sender.send(text.to_string());
+    }
+}
+
+impl Default for Speaker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Speaks the headword of the hovered word's first match and returns it, or `None` if nothing
+/// is hovered.
+pub fn speak_hovered(state: &LiveOcr, speaker: &Speaker) -> Option<String> {
+    state.hover.word()?;
+    let word = state.definitions.definitions.first()?.simplified.clone();
+    speaker.speak(&word);
+    Some(word)
+}
+
+/// Renders `text` to a WAV file and returns its contents, for attaching audio to exported cards.
+/// The speech engines behind [`Speaker`] can only play audio, so this goes through the
+/// platform's command line synthesizer instead: PowerShell with System.Speech on Windows, `say`
+/// on macOS and `espeak-ng` on Linux.
+pub fn synthesize(text: &str) -> Result<Vec<u8>> {
+    // Exports can run concurrently, so every call gets its own file
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    let path = std::env::temp_dir().join(format!(
+        "live-ocrs-speech-{}-{}.wav",
+        std::process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+    let audio = synthesize_to(text, &path.to_string_lossy());
+    let _ = std::fs::remove_file(&path);
+    audio
+}
+
+fn synthesize_to(text: &str, path: &str) -> Result<Vec<u8>> {
+    let language = detect_language(text);
+    let output = synthesize_command(text, language, path).output()?;
+    if !output.status.success() {
+        return Err(Error::Speech(
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        ));
+    }
+    Ok(std::fs::read(path)?)
+}
+
+#[cfg(target_os = "windows")]
+fn synthesize_command(text: &str, language: Language, path: &str) -> Command {
+    let culture = match language {
+        Language::Chinese => "zh-CN",
+        Language::Japanese => "ja-JP",
+        Language::Korean => "ko-KR",
+        Language::Other => "",
+    };
+    // Text and path are passed through the environment rather than spliced into the script, so
+    // they need no quoting
+    let script = "Add-Type -AssemblyName System.Speech; \
+        $s = New-Object System.Speech.Synthesis.SpeechSynthesizer; \
+        $v = $s.GetInstalledVoices() | \
+            Where-Object { $_.VoiceInfo.Culture.Name -eq $env:LIVE_OCRS_CULTURE }; \
+        if ($v) { $s.SelectVoice($v[0].VoiceInfo.Name) }; \
+        $s.SetOutputToWaveFile($env:LIVE_OCRS_PATH); \
+        $s.Speak($env:LIVE_OCRS_TEXT); $s.Dispose()";
+    let mut command = Command::new("powershell");
+    command
+        .args(["-NoProfile", "-NonInteractive", "-Command", script])
+        .env("LIVE_OCRS_TEXT", text)
+        .env("LIVE_OCRS_PATH", path)
+        .env("LIVE_OCRS_CULTURE", culture);
+    command
+}
+
+#[cfg(target_os = "macos")]
+fn synthesize_command(text: &str, language: Language, path: &str) -> Command {
+    let mut command = Command::new("say");
+    let voice = match language {
+        Language::Chinese => Some("Tingting"),
+        Language::Japanese => Some("Kyoko"),
+        Language::Korean => Some("Yuna"),
+        Language::Other => None,
+    };
+    if let Some(voice) = voice {
+        command.args(["-v", voice]);
+    }
+    command
+        .args([
+            "--file-format=WAVE",
+            "--data-format=LEI16@22050",
+            "-o",
+            path,
+        ])
+        .arg(text);
+    command
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+fn synthesize_command(text: &str, language: Language, path: &str) -> Command {
+    let mut command = Command::new("espeak-ng");
+    let voice = match language {
+        Language::Chinese => Some("cmn"),
+        Language::Japanese => Some("ja"),
+        Language::Korean => Some("ko"),
+        Language::Other => None,
+    };
+    if let Some(voice) = voice {
+        command.args(["-v", voice]);
+    }
+    command.args(["-w", path]).arg(text);
+    command
+}
+
+/// Picks an installed voice for the language, so Chinese is read in Mandarin rather than with
+/// the system language's voice. Keeps the current voice if none matches.
+fn select_voice(tts: &mut Tts, language: Language) {
+    let prefix = match language {
+        Language::Chinese => "zh",
+        Language::Japanese => "ja",
+        Language::Korean => "ko",
+        Language::Other => return,
+    };
+    if !tts.supported_features().voice {
+        return;
+    }
+    let voice = tts.voices().ok().and_then(|voices| {
+        voices
+            .into_iter()
+            .find(|voice| voice.language().as_str().starts_with(prefix))
+    });
+    match voice {
+        Some(voice) => {
+            if let Err(err) = tts.set_voice(&voice) {
+                log::warn!("Failed to switch voice: {err}");
+            }
+        }
+        None => log::warn!("No {language:?} voice installed"),
+    }
+}
@@ -0,0 +1,353 @@
+//! Importer for StarDict dictionaries, given the path of their `.ifo` file. The index (`.idx`,
+//! optionally gzipped), articles (`.dict` or dictzipped `.dict.dz`) and synonyms (`.syn`) are
+//! read from next to it. Markup in articles is reduced to plain text, one translation per line.
+
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::Read,
+    path::{Path, PathBuf},
+};
+
+use flate2::read::GzDecoder;
+
+use crate::{dict::DictionaryEntry, Error, Result};
+
+pub fn parse(path: &Path) -> Result<Vec<DictionaryEntry>> {
+    let info = std::fs::read_to_string(path)?;
+    if !info.starts_with("StarDict's dict ifo file") {
+        return Err(invalid("missing .ifo header"));
+    }
+    let info = info
+        .lines()
+        .filter_map(|line| line.split_once('='))
+        .map(|(key, value)| (key.trim(), value.trim()))
+        .collect::<HashMap<_, _>>();
+    let offset_bits = match info.get("idxoffsetbits") {
+        Some(&"64") => 64,
+        _ => 32,
+    };
+    let types = info.get("sametypesequence").copied().unwrap_or_default();
+
+    let index = read_maybe_gzipped(path, "idx")?;
+    let articles = read_maybe_gzipped(path, "dict")?;
+    let mut entries = Vec::new();
+    let mut cursor = Cursor::new(&index);
+    while !cursor.is_empty() {
+        let word = cursor.string()?;
+        let offset = match offset_bits {
+            64 => cursor.u64()? as usize,
+            _ => cursor.u32()? as usize,
+        };
+        let size = cursor.u32()? as usize;
+        let article = articles
+            .get(offset..offset.saturating_add(size))
+            .ok_or_else(|| invalid("article out of bounds"))?;
+        entries.push(to_entry(word, article, types));
+    }
+
+    // Synonyms point at index entries by position, e.g. alternative spellings
+    if let Ok(synonyms) = read_maybe_gzipped(path, "syn") {
+        let mut cursor = Cursor::new(&synonyms);
+        while !cursor.is_empty() {
+            let word = cursor.string()?;
+            let index = cursor.u32()? as usize;
+            if let Some(entry) = entries.get(index).cloned() {
+                entries.push(DictionaryEntry {
+                    simplified: word.clone(),
+                    traditional: word,
+                    ..entry
+                });
+            }
+        }
+    }
+    log::info!("Imported {} words from {path:?}", entries.len());
+    Ok(entries)
+}
+
+/// The `.ifo` file and whichever of the files read next to it exist, for invalidating caches.
+pub fn files(ifo: &Path) -> Vec<PathBuf> {
+    let mut files = vec![ifo.to_path_buf()];
+    for extension in ["idx", "dict", "syn"] {
+        files.extend(
+            [
+                extension.to_string(),
+                format!("{extension}.gz"),
+                format!("{extension}.dz"),
+            ]
+            .into_iter()
+            .map(|extension| ifo.with_extension(extension))
+            .filter(|path| path.exists()),
+        );
+    }
+    files
+}
+
+fn to_entry(word: String, article: &[u8], types: &str) -> DictionaryEntry {
+    let mut translations = Vec::new();
+    let mut reading = None;
+    for (kind, data) in fields(article, types) {
+        let data = String::from_utf8_lossy(data);
+        match kind {
+            // Phonetics, e.g. pinyin or English pronunciation
+            't' | 'y' => reading = Some(data.trim().to_string()),
+            'h' | 'g' | 'x' => translations.extend(lines(&strip_markup(&data))),
+            'm' | 'l' => translations.extend(lines(&data)),
+            // Binary data like images or sounds
+            _ => {}
+        }
+    }
+    DictionaryEntry {
+        simplified: word.clone(),
+        traditional: word,
+        pinyin: Vec::new(),
+        translations,
+        fuzzy: false,
+        reading,
+        word_classes: Vec::new(),
+        inflections: Vec::new(),
+        tags: Vec::new(),
+        frequency: None,
+        source: String::new(),
+        known: false,
+        rank: None,
+        levels: Vec::new(),
+        matched_script: None,
+    }
+}
+
+/// Splits an article into typed fields. With `sametypesequence` every article has the listed
+/// fields without type bytes, and the last one has no terminator or size. Otherwise each field
+/// starts with its type. Lowercase types are null terminated text, uppercase types are binary
+/// data prefixed with their size.
+fn fields<'a>(article: &'a [u8], types: &str) -> Vec<(char, &'a [u8])> {
+    let mut fields = Vec::new();
+    let mut rest = article;
+    let mut types = types.chars().peekable();
+    while !rest.is_empty() {
+        let (kind, last) = match types.next() {
+            Some(kind) => (kind, types.peek().is_none()),
+            None => {
+                let kind = rest[0] as char;
+                rest = &rest[1..];
+                (kind, false)
+            }
+        };
+        let len = if last {
+            rest.len()
+        } else if kind.is_ascii_uppercase() {
+            let Some(size) = rest.get(..4) else { break };
+            rest = &rest[4..];
+            u32::from_be_bytes(size.try_into().unwrap()) as usize
+        } else {
+            rest.iter()
+                .position(|&byte| byte == 0)
+                .unwrap_or(rest.len())
+        };
+        let Some(data) = rest.get(..len) else { break };
+        fields.push((kind, data));
+        // Skip the terminator of text fields
+        let skip = if kind.is_ascii_lowercase() && !last {
+            1
+        } else {
+            0
+        };
+        rest = rest.get(len + skip..).unwrap_or_default();
+    }
+    fields
+}
+
+/// Drops tags and decodes the common entities, with line breaks for block level tags.
+fn strip_markup(markup: &str) -> String {
+    let mut text = String::with_capacity(markup.len());
+    let mut rest = markup;
+    while let Some(start) = rest.find('<') {
+        text.push_str(&rest[..start]);
+        let Some(end) = rest[start..].find('>') else {
+            break;
+        };
+        let tag = rest[start + 1..start + end]
+            .trim_start_matches('/')
+            .split(|ch: char| ch.is_whitespace() || ch == '/')
+            .next()
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        if matches!(
+            tag.as_str(),
+            "br" | "p" | "div" | "li" | "tr" | "blockquote"
+        ) {
+            text.push('\n');
+        }
+        rest = &rest[start + end + 1..];
+    }
+    if !rest.contains('<') {
+        text.push_str(rest);
+    }
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&nbsp;", " ")
+        .replace("&amp;", "&")
+}
+
+fn lines(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(str::to_string)
+}
+
+/// Reads the file with extension `extension` next to the `.ifo`, or its gzipped version.
+/// Dictzip files are regular gzip files with an index for random access, which isn't needed
+/// when reading everything.
+fn read_maybe_gzipped(ifo: &Path, extension: &str) -> Result<Vec<u8>> {
+    let plain = ifo.with_extension(extension);
+    if plain.exists() {
+        return Ok(std::fs::read(plain)?);
+    }
+    let gzipped = [format!("{extension}.gz"), format!("{extension}.dz")]
+        .into_iter()
+        .map(|extension| ifo.with_extension(extension))
+        .find(|path| path.exists())
+        .ok_or_else(|| invalid(format!("missing .{extension} file")))?;
+    let mut data = Vec::new();
+    GzDecoder::new(File::open(gzipped)?).read_to_end(&mut data)?;
+    Ok(data)
+}
+
+/// Reads the big endian values and null terminated strings of `.idx` and `.syn` files.
+struct Cursor<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Cursor<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    fn string(&mut self) -> Result<String> {
+        let end = self
+            .data
+            .iter()
+            .position(|&byte| byte == 0)
+            .ok_or_else(|| invalid("unterminated headword"))?;
+        let string = String::from_utf8_lossy(&self.data[..end]).into_owned();
+        self.data = &self.data[end + 1..];
+        Ok(string)
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        Ok(u32::from_be_bytes(self.take()?))
+    }
+
+    fn u64(&mut self) -> Result<u64> {
+        Ok(u64::from_be_bytes(self.take()?))
+    }
+
+    fn take<const N: usize>(&mut self) -> Result<[u8; N]> {
+        let bytes = self
+            .data
+            .get(..N)
+            .ok_or_else(|| invalid("truncated index"))?;
+        self.data = &self.data[N..];
+        Ok(bytes.try_into().unwrap())
+    }
+}
+
+fn invalid(reason: impl Into<String>) -> Error {
+    Error::StarDict(reason.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const IFO: &str = "StarDict's dict ifo file\nversion=2.4.2\nsametypesequence=m\n";
+
+    /// A fresh directory for the files of one test.
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("live-ocrs-stardict-{}-{name}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn index_entry(word: &str, offset: u32, size: u32) -> Vec<u8> {
+        let mut entry = word.as_bytes().to_vec();
+        entry.push(0);
+        entry.extend(offset.to_be_bytes());
+        entry.extend(size.to_be_bytes());
+        entry
+    }
+
+    /// Writes a dictionary and returns the path of its `.ifo`.
+    fn write_dict(name: &str, ifo: &str, idx: &[u8], dict: &[u8]) -> PathBuf {
+        let path = temp_dir(name).join("test.ifo");
+        std::fs::write(&path, ifo).unwrap();
+        std::fs::write(path.with_extension("idx"), idx).unwrap();
+        std::fs::write(path.with_extension("dict"), dict).unwrap();
+        path
+    }
+
+    #[test]
+    fn parses_articles() {
+        let articles = "hello\nhi there";
+        let idx = [index_entry("你好", 0, 5), index_entry("嗨", 6, 8)].concat();
+        let path = write_dict("valid", IFO, &idx, articles.as_bytes());
+        let entries = parse(&path).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].simplified, "你好");
+        assert_eq!(entries[0].translations, ["hello"]);
+        assert_eq!(entries[1].translations, ["hi there"]);
+    }
+
+    #[test]
+    fn rejects_missing_header() {
+        let path = write_dict("header", "version=2.4.2\n", &[], &[]);
+        assert!(matches!(parse(&path), Err(Error::StarDict(_))));
+    }
+
+    #[test]
+    fn rejects_truncated_index() {
+        let idx = index_entry("你好", 0, 5);
+        let path = write_dict("truncated", IFO, &idx[..idx.len() - 2], b"hello");
+        assert!(matches!(parse(&path), Err(Error::StarDict(_))));
+        let path = write_dict("unterminated", IFO, "你好".as_bytes(), b"hello");
+        assert!(matches!(parse(&path), Err(Error::StarDict(_))));
+    }
+
+    #[test]
+    fn rejects_articles_out_of_bounds() {
+        let idx = [index_entry("你好", 0, 5), index_entry("嗨", u32::MAX, 8)].concat();
+        let path = write_dict("bounds", IFO, &idx, b"hello");
+        assert!(matches!(parse(&path), Err(Error::StarDict(_))));
+    }
+
+    #[test]
+    fn rejects_missing_index() {
+        let path = write_dict("missing", IFO, &[], b"hello");
+        std::fs::remove_file(path.with_extension("idx")).unwrap();
+        assert!(matches!(parse(&path), Err(Error::StarDict(_))));
+    }
+
+    #[test]
+    fn lists_existing_files() {
+        let path = write_dict("files", IFO, &[], &[]);
+        std::fs::write(path.with_extension("syn.dz"), []).unwrap();
+        assert_eq!(
+            files(&path),
+            [
+                path.clone(),
+                path.with_extension("idx"),
+                path.with_extension("dict"),
+                path.with_extension("syn.dz"),
+            ]
+        );
+    }
+}
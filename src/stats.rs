@@ -0,0 +1,76 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    time::Instant,
+};
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Default)]
+struct StatsData {
+    /// Milliseconds OCR was enabled, keyed by application name
+    reading_time: HashMap<String, u64>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct AppReadingTime {
+    pub app: String,
+    pub millis: u64,
+}
+
+/// Usage statistics persisted across runs.
+pub struct Stats {
+    path: PathBuf,
+    data: StatsData,
+    reading: Option<(String, Instant)>,
+}
+
+impl Stats {
+    pub fn start_reading(&mut self, app: String) {
+        self.stop_reading();
+        log::info!("Started reading in {app}");
+        self.reading = Some((app, Instant::now()));
+    }
+
+    pub fn stop_reading(&mut self) {
+        if let Some((app, started)) = self.reading.take() {
+            *self.data.reading_time.entry(app).or_default() += started.elapsed().as_millis() as u64;
+            self.save();
+        }
+    }
+
+    /// Total reading time per application, longest first.
+    pub fn reading_time(&self) -> Vec<AppReadingTime> {
+        let mut reading_time = self
+            .data
+            .reading_time
+            .iter()
+            .map(|(app, millis)| AppReadingTime {
+                app: app.clone(),
+                millis: *millis,
+            })
+            .collect::<Vec<_>>();
+        reading_time.sort_by_key(|it| std::cmp::Reverse(it.millis));
+        reading_time
+    }
+
+    fn save(&self) {
+        let data = serde_json::to_string(&self.data).unwrap();
+        if let Err(err) = std::fs::write(&self.path, data) {
+            log::error!("Failed to save stats to {:?}: {err}", self.path);
+        }
+    }
+}
+
+pub fn load(path: impl AsRef<Path>) -> Stats {
+    let path = path.as_ref();
+    let data = std::fs::read_to_string(path)
+        .ok()
+        .and_then(|data| serde_json::from_str(&data).ok())
+        .unwrap_or_default();
+    Stats {
+        path: path.to_owned(),
+        data,
+        reading: None,
+    }
+}
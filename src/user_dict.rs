@@ -0,0 +1,185 @@
+//! The user's own dictionary entries, for names, slang and vocabulary missing from the
+//! installed dictionaries. Persisted across runs and looked up before every other dictionary.
+
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    dict::DictionaryEntry,
+    hover::{HoverEvent, HoveredWord},
+    known::flag_known,
+    pinyin::parse_pinyin,
+    LiveOcr, Result,
+};
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct UserEntry {
+    /// Simplified form, or the written form for Japanese
+    pub word: String,
+    /// Traditional form, if it differs
+    #[serde(default)]
+    pub traditional: Option<String>,
+    /// Pinyin with tone numbers, e.g. `xue2 sheng5`
+    #[serde(default)]
+    pub pinyin: String,
+    pub translations: Vec<String>,
+}
+
+impl UserEntry {
+    fn to_dictionary_entry(&self) -> DictionaryEntry {
+        DictionaryEntry {
+            simplified: self.word.clone(),
+            traditional: self
+                .traditional
+                .clone()
+                .unwrap_or_else(|| self.word.clone()),
+            pinyin: parse_pinyin(&self.pinyin),
+            translations: self.translations.clone(),
+            fuzzy: false,
+            reading: None,
+            word_classes: Vec::new(),
+            inflections: Vec::new(),
+            tags: Vec::new(),
+            frequency: None,
+            source: String::new(),
+            known: false,
+            rank: None,
+            levels: Vec::new(),
+            matched_script: None,
+        }
+    }
+}
+
+/// Entries by word, one per word.
+pub struct UserDictionary {
+    path: PathBuf,
+    entries: Vec<UserEntry>,
+}
+
+impl UserDictionary {
+    pub fn entries(&self) -> &[UserEntry] {
+        &self.entries
+    }
+
+    /// The entries in the form lookups use.
+    pub fn dictionary_entries(&self) -> Vec<DictionaryEntry> {
+        self.entries
+            .iter()
+            .map(UserEntry::to_dictionary_entry)
+            .collect()
+    }
+
+    /// Adds an entry, replacing the existing one for the same word.
+    fn insert(&mut self, entry: UserEntry) -> Result<()> {
+        match self.entries.iter_mut().find(|it| it.word == entry.word) {
+            Some(existing) => *existing = entry,
+            None => self.entries.push(entry),
+        }
+        self.save()
+    }
+
+    /// Removes the entry for `word`. Returns `false` if there was none.
+    fn remove(&mut self, word: &str) -> Result<bool> {
+        let len = self.entries.len();
+        self.entries.retain(|entry| entry.word != word);
+        if self.entries.len() == len {
+            return Ok(false);
+        }
+        self.save()?;
+        Ok(true)
+    }
+
+    fn save(&self) -> Result<()> {
+        std::fs::write(&self.path, serde_json::to_string_pretty(&self.entries)?)?;
+        Ok(())
+    }
+}
+
+pub fn load(path: impl AsRef<Path>) -> UserDictionary {
+    let path = path.as_ref();
+    let entries = std::fs::read_to_string(path)
+        .ok()
+        .and_then(|data| serde_json::from_str(&data).ok())
+        .unwrap_or_default();
+    UserDictionary {
+        path: path.to_owned(),
+        entries,
+    }
+}
+
+/// Adds or edits an entry. Lookups see it immediately, including the hovered word, whose
+/// updated definitions are returned.
+pub fn save_entry(state: &mut LiveOcr, entry: UserEntry) -> Result<Option<HoverEvent>> {
+    log::info!("Saved {} to the user dictionary", entry.word);
+    state.user_dictionary.insert(entry)?;
+    Ok(reload(state))
+}
+
+/// Deletes the entry for `word`, returning the hovered word's updated definitions.
+pub fn delete_entry(state: &mut LiveOcr, word: &str) -> Result<Option<HoverEvent>> {
+    if !state.user_dictionary.remove(word)? {
+        return Ok(None);
+    }
+    log::info!("Removed {word} from the user dictionary");
+    Ok(reload(state))
+}
+
+/// An entry to edit for the hovered text.
+#[derive(Serialize, Clone, Debug)]
+pub struct UserEntryDraft {
+    pub entry: UserEntry,
+    /// The text from the hovered character to the end of the line, to pick the word from
+    pub context: String,
+}
+
+/// The user's entry for the hovered text if there is one, otherwise a new entry for the
+/// longest word found there, or the hovered character if none was. `None` if nothing is
+/// hovered.
+pub fn entry_for_hovered(state: &LiveOcr) -> Option<UserEntryDraft> {
+    let context = hovered_text(state.hover.word()?);
+    let existing = state
+        .user_dictionary
+        .entries()
+        .iter()
+        .filter(|entry| context.starts_with(&entry.word))
+        .max_by_key(|entry| entry.word.len());
+    let entry = existing.cloned().unwrap_or_else(|| {
+        let len = state
+            .definitions
+            .definitions
+            .first()
+            .map_or(1, |entry| entry.simplified.chars().count());
+        UserEntry {
+            word: context.chars().take(len).collect(),
+            traditional: None,
+            pinyin: String::new(),
+            translations: Vec::new(),
+        }
+    });
+    Some(UserEntryDraft { entry, context })
+}
+
+fn hovered_text(word: &HoveredWord) -> String {
+    word.text
+        .chars()
+        .skip(word.span.start)
+        .take(word.span.len())
+        .collect()
+}
+
+/// Applies the entries to lookups and looks up the hovered word again.
+fn reload(state: &mut LiveOcr) -> Option<HoverEvent> {
+    let entries = state.user_dictionary.dictionary_entries();
+    state.definitions.set_user_entries(entries);
+
+    let word = state.hover.word()?.clone();
+    state
+        .definitions
+        .update(&hovered_text(&word), state.config.fuzzy_lookup);
+    flag_known(state);
+    Some(HoverEvent::Show {
+        rect: word.rect,
+        definitions: state.definitions.definitions.clone(),
+    })
+}
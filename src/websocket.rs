@@ -0,0 +1,212 @@
+//! Stream of hover events over a local WebSocket, for texthooker-style web pages that show or
+//! collect what's being read.
+//!
+//! Every message is a JSON object. `{"type": "show", "word", "pinyin", "definitions", "bounds"}`
+//! is sent when a new word is looked up, `{"type": "hide"}` when the tooltip closes.
+//! `{"type": "blocks", "blocks"}` is sent after every OCR pass with the recognized blocks, see
+//! [`BlockInfo`].
+//!
+//! Browsers let any page connect to local WebSockets, so handshakes with an `Origin` header
+//! are refused unless the origin is listed in [`crate::config::Config::websocket_origins`].
+//! Local programs don't send one and can always connect.
+
+use std::{
+    io::ErrorKind,
+    net::{TcpListener, TcpStream, ToSocketAddrs},
+    sync::{
+        mpsc::{sync_channel, RecvTimeoutError, SyncSender, TrySendError},
+        Arc,
+    },
+    time::Duration,
+};
+
+use geo::Rect;
+use parking_lot::Mutex;
+use serde::Serialize;
+use tungstenite::{
+    handshake::server::{ErrorResponse, Request, Response},
+    http::StatusCode,
+    Message, WebSocket,
+};
+
+use crate::{hover::HoverEvent, payload::BlockInfo, Result};
+
+/// Clients that don't finish the handshake or take a write within this long are dropped
+const TIMEOUT: Duration = Duration::from_secs(5);
+/// How long a client's thread waits for the next event before reading from the client
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+/// Events queued for a client before it's considered stalled and dropped
+const QUEUE_LEN: usize = 64;
+
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum StreamEvent<'a> {
+    Show {
+        /// Headword of the best match
+        word: &'a str,
+        pinyin: String,
+        definitions: &'a [String],
+        /// Screen space bounds of the hovered character
+        bounds: Rect<f32>,
+    },
+    Hide,
+    Blocks {
+        blocks: &'a [BlockInfo],
+    },
+}
+
+type Clients = Arc<Mutex<Vec<SyncSender<String>>>>;
+type Origins = Arc<Vec<String>>;
+
+/// Connected WebSocket clients, each served by its own thread. Cloning shares the clients.
+#[derive(Clone)]
+pub struct EventStream {
+    clients: Clients,
+}
+
+impl EventStream {
+    /// Accepts clients on `addr` in the background. Web pages can only connect from
+    /// `origins`.
+    pub fn listen(addr: impl ToSocketAddrs, origins: Vec<String>) -> Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        log::info!("Event stream listening on {}", listener.local_addr()?);
+        let stream = Self {
+            clients: Arc::default(),
+        };
+        let clients = stream.clients.clone();
+        let origins = Origins::new(origins);
+        std::thread::spawn(move || {
+            for connection in listener.incoming().flatten() {
+                let clients = clients.clone();
+                let origins = origins.clone();
+                std::thread::spawn(move || serve(connection, clients, origins));
+            }
+        });
+        Ok(stream)
+    }
+
+    /// Queues a hover event for every client.
+    pub fn send(&self, event: &HoverEvent) {
+        let event = match event {
+            HoverEvent::Show { rect, definitions } => {
+                let Some(entry) = definitions.first() else {
+                    return;
+                };
+                StreamEvent::Show {
+                    word: &entry.simplified,
+                    pinyin: entry.pronunciation(),
+                    definitions: &entry.translations,
+                    bounds: *rect,
+                }
+            }
+            HoverEvent::Hide => StreamEvent::Hide,
+        };
+        self.broadcast(&event);
+    }
+
+    /// Queues the blocks of a new OCR pass for every client.
+    pub fn send_blocks(&self, blocks: &[BlockInfo]) {
+        self.broadcast(&StreamEvent::Blocks { blocks });
+    }
+
+    /// Queues `event` for every client without blocking, dropping the ones that disconnected or
+    /// fell too far behind.
+    fn broadcast(&self, event: &StreamEvent) {
+        let message = match serde_json::to_string(event) {
+            Ok(message) => message,
+            Err(err) => {
+                log::error!("Failed to serialize stream event: {err}");
+                return;
+            }
+        };
+        self.clients
+            .lock()
+            .retain(|client| match client.try_send(message.clone()) {
+                Ok(()) => true,
+                Err(TrySendError::Full(_)) => {
+                    log::warn!("Dropping a WebSocket client that stopped reading events");
+                    false
+                }
+                Err(TrySendError::Disconnected(_)) => false,
+            });
+    }
+}
+
+/// Completes the handshake with a new client and forwards queued events to it until it
+/// disconnects, stalls or is dropped from the clients.
+fn serve(connection: TcpStream, clients: Clients, origins: Origins) {
+    let timeouts = connection
+        .set_read_timeout(Some(TIMEOUT))
+        .and_then(|_| connection.set_write_timeout(Some(TIMEOUT)));
+    if let Err(err) = timeouts {
+        log::warn!("Failed to set WebSocket timeouts: {err}");
+        return;
+    }
+    let check_origin =
+        |request: &Request, response: Response| check_origin(request, response, &origins);
+    let mut client = match tungstenite::accept_hdr(connection, check_origin) {
+        Ok(client) => client,
+        Err(err) => {
+            log::warn!("WebSocket handshake failed: {err}");
+            return;
+        }
+    };
+    // Reads only check for pings and close frames, so they shouldn't hold up events
+    let _ = client
+        .get_ref()
+        .set_read_timeout(Some(Duration::from_millis(10)));
+    let (sender, receiver) = sync_channel(QUEUE_LEN);
+    clients.lock().push(sender);
+
+    loop {
+        match receiver.recv_timeout(POLL_INTERVAL) {
+            Ok(message) => {
+                if client.send(Message::Text(message)).is_err() {
+                    break;
+                }
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+        if !poll(&mut client) {
+            break;
+        }
+    }
+}
+
+/// Refuses the handshake if it comes from a web page whose origin isn't allowed.
+#[allow(clippy::result_large_err)]
+fn check_origin(
+    request: &Request,
+    response: Response,
+    origins: &[String],
+) -> std::result::Result<Response, ErrorResponse> {
+    let Some(origin) = request.headers().get("origin") else {
+        return Ok(response);
+    };
+    let origin = origin.to_str().unwrap_or_default();
+    if origins
+        .iter()
+        .any(|allowed| allowed.eq_ignore_ascii_case(origin))
+    {
+        return Ok(response);
+    }
+    log::warn!("Refused a WebSocket connection from {origin}");
+    let mut response = ErrorResponse::new(Some(format!("Origin {origin} is not allowed")));
+    *response.status_mut() = StatusCode::FORBIDDEN;
+    Err(response)
+}
+
+/// Reads whatever the client sent, which answers pings and flushes pending replies. Returns
+/// `false` once the client closed the connection.
+fn poll(client: &mut WebSocket<TcpStream>) -> bool {
+    match client.read() {
+        Ok(_) => true,
+        Err(tungstenite::Error::Io(err))
+            if matches!(err.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) =>
+        {
+            true
+        }
+        Err(_) => false,
+    }
+}
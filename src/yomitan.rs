@@ -0,0 +1,259 @@
+//! Importer for Yomitan (formerly Yomichan) dictionary zips. Reads term banks, frequency
+//! banks and tag banks of the version 3 format, which all current dictionaries use.
+
+use std::{collections::HashMap, fs::File, io::Read, path::Path};
+
+use serde::Deserialize;
+use serde_json::Value;
+use zip::ZipArchive;
+
+use crate::{dict::DictionaryEntry, Result};
+
+/// `[expression, reading, definition tags, deinflection rules, score, glossary, sequence,
+/// term tags]`
+#[derive(Deserialize)]
+struct TermRow(
+    String,
+    String,
+    Option<String>,
+    String,
+    f64,
+    Vec<Value>,
+    i64,
+    String,
+);
+
+/// `index.json`, only the frequency mode is used
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+struct Index {
+    /// `rank-based` if lower frequencies are more common, `occurrence-based` if higher ones are
+    frequency_mode: Option<String>,
+}
+
+/// `[expression, mode, data]`, only the `freq` mode is used
+#[derive(Deserialize)]
+struct MetaRow(String, String, Value);
+
+/// `[name, category, order, notes, score]`
+#[derive(Deserialize)]
+struct TagRow(String, String, f64, String, f64);
+
+pub fn parse(path: &Path) -> Result<Vec<DictionaryEntry>> {
+    let mut archive = ZipArchive::new(File::open(path)?)?;
+    let mut terms = Vec::new();
+    let mut frequencies = HashMap::new();
+    let mut tag_notes = HashMap::new();
+    let mut index = Index::default();
+    for i in 0..archive.len() {
+        let mut file = archive.by_index(i)?;
+        let name = file.name().to_string();
+        if !name.ends_with(".json") {
+            continue;
+        }
+        let mut data = String::new();
+        file.read_to_string(&mut data)?;
+
+        if name == "index.json" {
+            index = serde_json::from_str(&data).unwrap_or_default();
+        } else if name.starts_with("term_bank_") {
+            terms.extend(serde_json::from_str::<Vec<TermRow>>(&data)?);
+        } else if name.starts_with("term_meta_bank_") {
+            for MetaRow(expression, mode, data) in serde_json::from_str::<Vec<MetaRow>>(&data)? {
+                if let Some((reading, frequency)) =
+                    (mode == "freq").then(|| parse_frequency(&data)).flatten()
+                {
+                    frequencies.insert((expression, reading), frequency);
+                }
+            }
+        } else if name.starts_with("tag_bank_") {
+            for TagRow(name, _, _, notes, _) in serde_json::from_str::<Vec<TagRow>>(&data)? {
+                tag_notes.insert(name, notes);
+            }
+        }
+    }
+    log::info!("Imported {} terms from {path:?}", terms.len());
+    // Most frequency dictionaries list ranks, and the mode is missing from older ones
+    let ranks = match index.frequency_mode.as_deref() {
+        Some("occurrence-based") => occurrence_ranks(&frequencies),
+        _ => frequencies.clone(),
+    };
+
+    let entries = terms
+        .into_iter()
+        .map(
+            |TermRow(expression, reading, definition_tags, rules, _, glossary, _, term_tags)| {
+                let reading = (!reading.is_empty() && reading != expression).then_some(reading);
+                let lookup = |values: &HashMap<_, u32>| {
+                    values
+                        .get(&(expression.clone(), reading.clone()))
+                        .or_else(|| values.get(&(expression.clone(), None)))
+                        .copied()
+                };
+                let frequency = lookup(&frequencies);
+                let rank = lookup(&ranks);
+                let tags = definition_tags
+                    .iter()
+                    .flat_map(|it| it.split_whitespace())
+                    .chain(term_tags.split_whitespace())
+                    .map(|tag| match tag_notes.get(tag) {
+                        Some(notes) if !notes.is_empty() => notes.clone(),
+                        _ => tag.to_string(),
+                    })
+                    .collect();
+                DictionaryEntry {
+                    simplified: expression.clone(),
+                    traditional: expression,
+                    pinyin: Vec::new(),
+                    translations: glossary.iter().filter_map(glossary_text).collect(),
+                    fuzzy: false,
+                    reading,
+                    word_classes: rules.split_whitespace().map(str::to_string).collect(),
+                    inflections: Vec::new(),
+                    tags,
+                    frequency,
+                    source: String::new(),
+                    known: false,
+                    rank,
+                    levels: Vec::new(),
+                    matched_script: None,
+                }
+            },
+        )
+        .collect();
+    Ok(entries)
+}
+
+/// Frequency data is either a plain number, `{ value }`, or either of those nested with a
+/// reading as `{ reading, frequency }`.
+fn parse_frequency(data: &Value) -> Option<(Option<String>, u32)> {
+    match data {
+        Value::Number(value) => Some((None, value.as_u64()? as u32)),
+        Value::String(value) => Some((None, value.trim().parse().ok()?)),
+        Value::Object(object) => match (object.get("reading"), object.get("frequency")) {
+            (Some(Value::String(reading)), Some(frequency)) => {
+                let (_, frequency) = parse_frequency(frequency)?;
+                Some((Some(reading.clone()), frequency))
+            }
+            _ => Some((None, object.get("value")?.as_u64()? as u32)),
+        },
+        _ => None,
+    }
+}
+
+/// Converts occurrence counts to ranks, 1 being the most frequent. Equal counts share a rank.
+fn occurrence_ranks(
+    counts: &HashMap<(String, Option<String>), u32>,
+) -> HashMap<(String, Option<String>), u32> {
+    let mut distinct = counts.values().copied().collect::<Vec<_>>();
+    distinct.sort_unstable_by(|a, b| b.cmp(a));
+    distinct.dedup();
+    counts
+        .iter()
+        .map(|(key, count)| {
+            let position = distinct
+                .binary_search_by(|probe| count.cmp(probe))
+                .unwrap_or_default();
+            (key.clone(), position as u32 + 1)
+        })
+        .collect()
+}
+
+/// Plain text of a glossary item. Structured content is flattened, images are skipped.
+fn glossary_text(item: &Value) -> Option<String> {
+    let text = match item {
+        Value::String(text) => text.clone(),
+        Value::Object(object) => match object.get("type").and_then(Value::as_str) {
+            Some("text") => object.get("text")?.as_str()?.to_string(),
+            Some("structured-content") => content_text(object.get("content")?),
+            _ => return None,
+        },
+        _ => return None,
+    };
+    (!text.trim().is_empty()).then_some(text)
+}
+
+fn content_text(content: &Value) -> String {
+    match content {
+        Value::String(text) => text.clone(),
+        Value::Array(items) => items.iter().map(content_text).collect(),
+        Value::Object(object) if object.get("tag").and_then(Value::as_str) == Some("img") => {
+            String::new()
+        }
+        Value::Object(object) => object.get("content").map(content_text).unwrap_or_default(),
+        _ => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{io::Write, path::PathBuf};
+
+    use zip::{write::SimpleFileOptions, ZipWriter};
+
+    use super::*;
+    use crate::Error;
+
+    /// Writes a zip with the given files and returns its path.
+    fn write_zip(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "live-ocrs-yomitan-{}-{name}.zip",
+            std::process::id()
+        ));
+        let mut zip = ZipWriter::new(File::create(&path).unwrap());
+        for (name, data) in files {
+            zip.start_file(*name, SimpleFileOptions::default()).unwrap();
+            zip.write_all(data.as_bytes()).unwrap();
+        }
+        zip.finish().unwrap();
+        path
+    }
+
+    #[test]
+    fn parses_terms_with_ranks() {
+        let path = write_zip(
+            "valid",
+            &[
+                ("index.json", r#"{"title": "Test", "format": 3}"#),
+                (
+                    "term_bank_1.json",
+                    r#"[["食べる", "たべる", "v1", "v1", 0, ["to eat"], 1, "common"]]"#,
+                ),
+                ("term_meta_bank_1.json", r#"[["食べる", "freq", 120]]"#),
+                (
+                    "tag_bank_1.json",
+                    r#"[["common", "", 0, "common word", 0]]"#,
+                ),
+            ],
+        );
+        let entries = parse(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].simplified, "食べる");
+        assert_eq!(entries[0].reading.as_deref(), Some("たべる"));
+        assert_eq!(entries[0].translations, ["to eat"]);
+        assert_eq!(entries[0].word_classes, ["v1"]);
+        assert_eq!(entries[0].rank, Some(120));
+        assert!(entries[0].tags.contains(&"common word".to_string()));
+    }
+
+    #[test]
+    fn rejects_malformed_term_banks() {
+        let path = write_zip("malformed", &[("term_bank_1.json", r#"[["食べる", 5]]"#)]);
+        let result = parse(&path);
+        let _ = std::fs::remove_file(&path);
+        assert!(matches!(result, Err(Error::Json(_))));
+    }
+
+    #[test]
+    fn rejects_files_that_arent_zips() {
+        let path = std::env::temp_dir().join(format!(
+            "live-ocrs-yomitan-{}-notzip.zip",
+            std::process::id()
+        ));
+        std::fs::write(&path, "not a zip").unwrap();
+        let result = parse(&path);
+        let _ = std::fs::remove_file(&path);
+        assert!(matches!(result, Err(Error::Zip(_))));
+    }
+}